@@ -0,0 +1,155 @@
+## Analysis embed. Labels are followed by ": " in the embed.
+
+label-evaluation = Bewertung
+label-forced-mate = Forciertes Matt?
+label-best-move = Bester Zug
+label-book-moves = Buchzüge
+label-tablebase = Endspieldatenbank
+label-game-over = Partie beendet
+label-your-move = Dein Zug
+label-opponent-move = Gegner am Zug; erwartete Antwort
+label-line = Variante
+label-threat = Drohung
+label-depth = Tiefe
+label-max-depth = tiefer geht es nicht
+label-online-max-depth = mehr erlaubt stockfish.online nicht, eine lokale Engine rechnet tiefer
+label-opening = Eröffnung
+
+loading = Lädt...
+not-available = k. A.
+error-getting-help = Fehler bei der Analyse { $error }
+
+evaluation-winning = Du gewinnst mit { $chance }
+evaluation-losing-badly = Denk über Aufgeben nach. Du verlierst mit { $chance }
+evaluation-losing = Du verlierst mit { $chance }
+evaluation-equal = ausgeglichen
+forced-mate-none = Nein
+book-marker = (Buch)
+threat-mate = { $move } (Matt in { $moves })
+tablebase-win = Gewinn in { $plies }
+tablebase-loss = Verlust in { $plies }
+tablebase-cursed-win = Gewinn, aber remis durch die 50-Züge-Regel
+tablebase-blessed-loss = Verlust, aber gerettet durch die 50-Züge-Regel
+tablebase-draw = Remis
+tablebase-best-move = { $outcome } mit dem DTZ-optimalen Zug { $move }
+
+# König, Dame, Turm, Läufer und Springer.
+piece-letters = KDTLS
+
+white = Weiß
+black = Schwarz
+game-over-checkmate = Schachmatt, { $winner } gewinnt
+game-over-variant = { $variant }, { $winner } gewinnt
+game-over-stalemate = Patt
+game-over-insufficient-material = Remis durch ungenügendes Material
+
+## Buttons
+
+button-refresh = Aktualisieren
+button-flip = Brett drehen
+button-deeper = Tiefer analysieren
+button-show-lines = Varianten zeigen
+button-hide-lines = Varianten ausblenden
+button-show-threats = Drohungen zeigen
+button-hide-threats = Drohungen ausblenden
+button-track = Partie verfolgen
+button-delete = Löschen
+
+## Replies
+
+reply-restarting = Der Bot startet neu, versuch es in einer Minute noch einmal.
+reply-unsupported-button = Dieser Knopf wird nicht mehr unterstützt.
+reply-unknown-game = Ich weiß nicht mehr, welche Partie das war.
+reply-already-tracked = Diese Partie wird bereits verfolgt.
+reply-deep-analysis-busy = Hier läuft schon eine tiefe Analyse, versuch es noch einmal, wenn sie fertig ist.
+reply-not-live = Diese Analyse zeigt einen festen Zug, da gibt es nichts zu verfolgen.
+reply-delete-refused = Nur wer diese Analyse angefordert hat oder Nachrichten verwalten darf, kann sie löschen.
+
+restarted-title = Bot startet neu
+restarted-description = Der Bot wurde neu gestartet, bevor diese Analyse fertig war. Drück auf Aktualisieren, um es noch einmal zu versuchen.
+
+## Slash commands
+
+command-analyze = Eine Analyse der aktuellen oder letzten Partie eines Spielers posten
+option-platform = Seite, auf der der Spieler spielt
+option-analyze-user = Benutzername auf dieser Seite, ohne Angabe dein verknüpftes lichess-Konto
+command-game = Eine Analyse einer lichess-Partie per Link oder ID posten
+option-game = Link oder ID der lichess-Partie, #Halbzug und /black werden verstanden
+option-move = Halbzüge, die vor der Analyse gespielt werden, sonst die aktuelle Stellung
+command-subscribe = Hier eine Analyse posten, sobald ein lichess-Spieler eine Partie beginnt
+command-unsubscribe = Keine Partien eines lichess-Spielers mehr hier posten
+command-link = Dein lichess-Konto verknüpfen, indem du dich bei lichess anmeldest
+command-unlink = Das verknüpfte lichess-Konto vergessen
+command-fairplay = Festlegen, was Mitglieder von ihren eigenen laufenden Partien sehen
+option-fairplay-mode = Was passiert, wenn jemand nach seiner eigenen laufenden Partie fragt
+option-fairplay-moves = Wie viele Züge zurück gezeigt wird, für die Verzögerung
+fairplay-mode-block = Ablehnen
+fairplay-mode-delay = Ein paar Züge zurück zeigen
+fairplay-mode-after-game = Nach Partieende posten
+command-language = Die Sprache wählen, in der der Bot antwortet
+option-user = lichess-Benutzername
+option-live = Die Analyse bis zum Ende der Partie aktualisieren
+option-language = Zu verwendende Sprache
+option-server = Für den ganzen Server statt nur für dich festlegen
+command-unsupported = Dieser Befehl wird nicht unterstützt.
+
+analyze-missing-user = Bitte gib einen Benutzernamen an oder verknüpfe zuerst dein lichess-Konto mit /link.
+analyze-started = Analysiere die Partie von { $user } auf { $platform }…
+
+game-invalid = Das ist kein Link und keine ID einer lichess-Partie.
+game-started = Analysiere { $game }…
+
+subscribe-missing-user = Bitte gib einen lichess-Benutzernamen an.
+subscribe-exists = Dieser Kanal folgt { $user } bereits.
+subscribe-done = Ich poste hier, sobald { $user } eine Partie beginnt.
+subscribe-failed = Das Abo konnte nicht gespeichert werden, versuch es später noch einmal.
+unsubscribe-done = { $user } wird hier nicht mehr gefolgt.
+unsubscribe-failed = Das Abo konnte nicht entfernt werden, versuch es später noch einmal.
+unsubscribe-missing = Dieser Kanal folgt { $user } nicht.
+
+link-done = Mit { $user } auf lichess verknüpft. Deine laufenden Partien fallen jetzt unter Fair Play.
+link-failed = Das konnte nicht gespeichert werden, versuch es später noch einmal.
+link-start = [Melde dich bei lichess an]({ $url }), um dein Konto zu verknüpfen. Der Link gilt 10 Minuten.
+link-unavailable = Das Verknüpfen von lichess-Konten ist bei diesem Bot nicht eingerichtet.
+link-page-expired = Diese Anmeldung ist abgelaufen oder wurde schon benutzt. Führe /link in Discord noch einmal aus.
+link-page-denied = lichess hat dein Konto nicht verknüpft. Führe /link in Discord noch einmal aus.
+link-page-failed = Dein lichess-Konto konnte nicht geprüft werden, führe /link später noch einmal aus.
+unlink-done = Dein lichess-Konto ist nicht mehr verknüpft.
+unlink-missing = Du hast kein lichess-Konto verknüpft.
+
+fairplay-set-block = Eigene laufende Partien von Mitgliedern werden hier nicht analysiert.
+fairplay-set-delay = Eigene laufende Partien von Mitgliedern werden { $moves } Züge zurück gezeigt.
+fairplay-set-after-game = Eigene laufende Partien von Mitgliedern werden nach ihrem Ende analysiert.
+fairplay-needs-manage-server = Nur Mitglieder, die den Server verwalten dürfen, können seine Fair-Play-Regeln ändern.
+fairplay-not-in-server = Fair-Play-Regeln können nur auf einem Server festgelegt werden.
+fairplay-failed = Die Einstellung konnte nicht gespeichert werden, versuch es später noch einmal.
+
+fair-play-refused = Das ist deine eigene laufende Partie, die Fair-Play-Regeln von lichess erlauben keine Analyse.
+fair-play-after-game = Das ist deine eigene laufende Partie, ich poste die Analyse hier, sobald sie vorbei ist.
+fair-play-wait = Das ist deine eigene laufende Partie, sie kann analysiert werden, sobald sie vorbei ist.
+fair-play-unchecked = Ich konnte nicht prüfen, ob das deine eigene Partie ist, versuch es später noch einmal.
+
+language-auto = Automatisch
+language-set-user = Ich antworte dir ab jetzt auf Deutsch.
+language-set-server = Die Sprache dieses Servers ist jetzt Deutsch.
+language-cleared-user = Ich richte mich wieder nach deiner Discord-Sprache.
+language-cleared-server = Für diesen Server ist keine Sprache mehr festgelegt.
+language-needs-manage-server = Nur Mitglieder, die den Server verwalten dürfen, können seine Sprache ändern.
+language-not-in-server = Serversprachen können nur auf einem Server festgelegt werden.
+language-failed = Die Einstellung konnte nicht gespeichert werden, versuch es später noch einmal.
+
+## Game report
+
+report-title = Partiebericht: { $white } gegen { $black }
+report-result = Ergebnis: { $result }
+report-player =
+    Genauigkeit: { $accuracy }%
+    ACPL: { $acpl }
+    Ungenauigkeiten: { $inaccuracies } · Fehler: { $mistakes } · Patzer: { $blunders }
+report-win-drop = Gewinnchance -{ $drop }%
+report-best-was = , besser war { $move }
+report-view-board = Brett ansehen
+report-gaps = Nicht bewertete Züge, weil die Engine nicht antwortete: { $moves }
+judgement-inaccuracy = Ungenauigkeit
+judgement-mistake = Fehler
+judgement-blunder = Patzer
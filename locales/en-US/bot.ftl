@@ -0,0 +1,155 @@
+## Analysis embed. Labels are followed by ": " in the embed.
+
+label-evaluation = Evaluation
+label-forced-mate = Forced Mate?
+label-best-move = Best Move
+label-book-moves = Book Moves
+label-tablebase = Tablebase
+label-game-over = Game over
+label-your-move = Your move
+label-opponent-move = Opponent to move; expected reply
+label-line = Line
+label-threat = Threat
+label-depth = Depth
+label-max-depth = as deep as it goes
+label-online-max-depth = the most stockfish.online allows, a local engine goes deeper
+label-opening = Opening
+
+loading = Loading...
+not-available = N/A
+error-getting-help = Error getting help { $error }
+
+evaluation-winning = You are winning by { $chance }
+evaluation-losing-badly = Consider conceding. You are losing by { $chance }
+evaluation-losing = You are losing by { $chance }
+evaluation-equal = equal
+forced-mate-none = No
+book-marker = (book)
+threat-mate = { $move } (mate in { $moves })
+tablebase-win = Win in { $plies }
+tablebase-loss = Loss in { $plies }
+tablebase-cursed-win = Win, but drawn by the 50 move rule
+tablebase-blessed-loss = Loss, but saved by the 50 move rule
+tablebase-draw = Draw
+tablebase-best-move = { $outcome } with DTZ-optimal { $move }
+
+# King, queen, rook, bishop and knight, in that order, as written in SAN.
+piece-letters = KQRBN
+
+white = White
+black = Black
+game-over-checkmate = Checkmate, { $winner } wins
+game-over-variant = { $variant }, { $winner } wins
+game-over-stalemate = Stalemate
+game-over-insufficient-material = Draw by insufficient material
+
+## Buttons
+
+button-refresh = Refresh
+button-flip = Flip board
+button-deeper = Analyze deeper
+button-show-lines = Show lines
+button-hide-lines = Hide lines
+button-show-threats = Show threats
+button-hide-threats = Hide threats
+button-track = Track game
+button-delete = Delete
+
+## Replies
+
+reply-restarting = The bot is restarting, try again in a minute.
+reply-unsupported-button = This button is no longer supported.
+reply-unknown-game = I no longer know which game this was.
+reply-already-tracked = This game is already being tracked.
+reply-deep-analysis-busy = A deep analysis is already running here, try again when it's done.
+reply-not-live = This analysis is pinned to one move, there's nothing to track.
+reply-delete-refused = Only whoever asked for this analysis, or someone who can manage messages, can delete it.
+
+restarted-title = Bot restarting
+restarted-description = The bot restarted before this analysis finished. Press Refresh to try again.
+
+## Slash commands
+
+command-analyze = Post an analysis of a player's current or last game
+option-platform = Site the player plays on
+option-analyze-user = Their username on that site, your linked lichess account if left out
+command-game = Post an analysis of a lichess game by link or id
+option-game = lichess game link or id, a #ply anchor and /black are understood
+option-move = Half-moves to play before analysing, the latest position if left out
+command-subscribe = Post an analysis here whenever a lichess player starts a game
+command-unsubscribe = Stop posting games for a lichess player here
+command-link = Link your lichess account by signing in on lichess
+command-unlink = Forget the lichess account you linked
+command-fairplay = Choose what members see of their own games in progress
+option-fairplay-mode = What to do when someone asks about their own game in progress
+option-fairplay-moves = How many moves behind to show, for the delay mode
+fairplay-mode-block = Refuse
+fairplay-mode-delay = Show it a few moves behind
+fairplay-mode-after-game = Post it once the game is over
+command-language = Choose the language the bot replies in
+option-user = lichess username
+option-live = Keep the analysis updating until the game ends
+option-language = Language to use
+option-server = Set it for the whole server instead of just for you
+command-unsupported = That command isn't supported.
+
+analyze-missing-user = Please give a username, or link your lichess account with /link first.
+analyze-started = Analysing { $user }'s game on { $platform }…
+
+game-invalid = That isn't a lichess game link or id.
+game-started = Analysing { $game }…
+
+subscribe-missing-user = Please give a lichess username.
+subscribe-exists = This channel is already subscribed to { $user }.
+subscribe-done = I'll post here when { $user } starts a game.
+subscribe-failed = Couldn't save that subscription, try again later.
+unsubscribe-done = No longer following { $user } here.
+unsubscribe-failed = Couldn't remove that subscription, try again later.
+unsubscribe-missing = This channel isn't subscribed to { $user }.
+
+link-done = Linked to { $user } on lichess. Your games in progress are covered by fair play now.
+link-failed = Couldn't save that, try again later.
+link-start = [Sign in on lichess]({ $url }) to link your account. The link works for 10 minutes.
+link-unavailable = Linking lichess accounts isn't set up on this bot.
+link-page-expired = This sign-in has expired or was already used. Run /link again in Discord.
+link-page-denied = lichess didn't link your account. Run /link again in Discord to retry.
+link-page-failed = Couldn't check your lichess account, run /link again later.
+unlink-done = Your lichess account is no longer linked.
+unlink-missing = You haven't linked a lichess account.
+
+fairplay-set-block = Members' own games in progress won't be analysed here.
+fairplay-set-delay = Members' own games in progress will be shown { $moves } moves behind.
+fairplay-set-after-game = Members' own games in progress will be analysed once they're over.
+fairplay-needs-manage-server = Only members who can manage the server can change its fair-play rules.
+fairplay-not-in-server = Fair-play rules can only be set in a server.
+fairplay-failed = Couldn't save that setting, try again later.
+
+fair-play-refused = That's your own game in progress, lichess's fair-play rules don't allow analysing it.
+fair-play-after-game = That's your own game in progress, I'll post the analysis here once it's over.
+fair-play-wait = That's your own game in progress, it can be analysed once it's over.
+fair-play-unchecked = Couldn't check whether that's your own game, try again later.
+
+language-auto = Automatic
+language-set-user = I'll reply to you in English from now on.
+language-set-server = This server's language is now English.
+language-cleared-user = I'll follow your Discord language again.
+language-cleared-server = This server no longer has a language set.
+language-needs-manage-server = Only members who can manage the server can change its language.
+language-not-in-server = Server languages can only be set in a server.
+language-failed = Couldn't save that setting, try again later.
+
+## Game report
+
+report-title = Game report: { $white } vs { $black }
+report-result = Result: { $result }
+report-player =
+    Accuracy: { $accuracy }%
+    ACPL: { $acpl }
+    Inaccuracies: { $inaccuracies } · Mistakes: { $mistakes } · Blunders: { $blunders }
+report-win-drop = Win chance -{ $drop }%
+report-best-was = , best was { $move }
+report-view-board = View board
+report-gaps = Moves not graded because the engine didn't answer: { $moves }
+judgement-inaccuracy = Inaccuracy
+judgement-mistake = Mistake
+judgement-blunder = Blunder
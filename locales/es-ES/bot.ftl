@@ -0,0 +1,155 @@
+## Analysis embed. Labels are followed by ": " in the embed.
+
+label-evaluation = Evaluación
+label-forced-mate = ¿Mate forzado?
+label-best-move = Mejor jugada
+label-book-moves = Jugadas de libro
+label-tablebase = Tablas de finales
+label-game-over = Partida terminada
+label-your-move = Tu jugada
+label-opponent-move = Mueve el rival; respuesta esperada
+label-line = Línea
+label-threat = Amenaza
+label-depth = Profundidad
+label-max-depth = no llega más hondo
+label-online-max-depth = el máximo de stockfish.online, un motor local llega más hondo
+label-opening = Apertura
+
+loading = Cargando...
+not-available = N/D
+error-getting-help = Error al analizar { $error }
+
+evaluation-winning = Vas ganando por { $chance }
+evaluation-losing-badly = Considera abandonar. Vas perdiendo por { $chance }
+evaluation-losing = Vas perdiendo por { $chance }
+evaluation-equal = igualado
+forced-mate-none = No
+book-marker = (libro)
+threat-mate = { $move } (mate en { $moves })
+tablebase-win = Victoria en { $plies }
+tablebase-loss = Derrota en { $plies }
+tablebase-cursed-win = Victoria, pero tablas por la regla de los 50 movimientos
+tablebase-blessed-loss = Derrota, pero salvada por la regla de los 50 movimientos
+tablebase-draw = Tablas
+tablebase-best-move = { $outcome } con { $move }, óptima según DTZ
+
+# Rey, dama, torre, alfil y caballo.
+piece-letters = RDTAC
+
+white = Blancas
+black = Negras
+game-over-checkmate = Jaque mate, ganan { $winner }
+game-over-variant = { $variant }, ganan { $winner }
+game-over-stalemate = Rey ahogado
+game-over-insufficient-material = Tablas por material insuficiente
+
+## Buttons
+
+button-refresh = Actualizar
+button-flip = Girar tablero
+button-deeper = Analizar más a fondo
+button-show-lines = Mostrar líneas
+button-hide-lines = Ocultar líneas
+button-show-threats = Mostrar amenazas
+button-hide-threats = Ocultar amenazas
+button-track = Seguir partida
+button-delete = Borrar
+
+## Replies
+
+reply-restarting = El bot se está reiniciando, inténtalo de nuevo en un minuto.
+reply-unsupported-button = Este botón ya no está disponible.
+reply-unknown-game = Ya no sé qué partida era esta.
+reply-already-tracked = Esta partida ya se está siguiendo.
+reply-deep-analysis-busy = Ya hay un análisis profundo en curso aquí, inténtalo de nuevo cuando termine.
+reply-not-live = Este análisis está fijado en una jugada, no hay nada que seguir.
+reply-delete-refused = Solo quien pidió este análisis, o alguien que pueda gestionar mensajes, puede borrarlo.
+
+restarted-title = El bot se está reiniciando
+restarted-description = El bot se reinició antes de que terminara este análisis. Pulsa Actualizar para intentarlo de nuevo.
+
+## Slash commands
+
+command-analyze = Publicar un análisis de la partida actual o la última de un jugador
+option-platform = Sitio en el que juega el jugador
+option-analyze-user = Su nombre de usuario en ese sitio, tu cuenta de lichess vinculada si se omite
+command-game = Publicar un análisis de una partida de lichess por enlace o ID
+option-game = Enlace o ID de la partida de lichess, se entienden #jugada y /black
+option-move = Medias jugadas a reproducir antes de analizar, la última posición si se omite
+command-subscribe = Publicar aquí un análisis cada vez que un jugador de lichess empiece una partida
+command-unsubscribe = Dejar de publicar aquí las partidas de un jugador de lichess
+command-link = Vincular tu cuenta de lichess iniciando sesión en lichess
+command-unlink = Olvidar la cuenta de lichess vinculada
+command-fairplay = Elegir qué ven los miembros de sus propias partidas en curso
+option-fairplay-mode = Qué hacer cuando alguien pregunta por su propia partida en curso
+option-fairplay-moves = Cuántas jugadas por detrás mostrar, para el modo con retraso
+fairplay-mode-block = Rechazar
+fairplay-mode-delay = Mostrarla unas jugadas por detrás
+fairplay-mode-after-game = Publicarla cuando termine la partida
+command-language = Elegir el idioma en el que responde el bot
+option-user = nombre de usuario de lichess
+option-live = Seguir actualizando el análisis hasta que termine la partida
+option-language = Idioma a usar
+option-server = Aplicarlo a todo el servidor y no solo a ti
+command-unsupported = Ese comando no está disponible.
+
+analyze-missing-user = Indica un nombre de usuario o vincula antes tu cuenta de lichess con /link.
+analyze-started = Analizando la partida de { $user } en { $platform }…
+
+game-invalid = Eso no es un enlace ni un ID de partida de lichess.
+game-started = Analizando { $game }…
+
+subscribe-missing-user = Indica un nombre de usuario de lichess.
+subscribe-exists = Este canal ya sigue a { $user }.
+subscribe-done = Publicaré aquí cuando { $user } empiece una partida.
+subscribe-failed = No se pudo guardar la suscripción, inténtalo más tarde.
+unsubscribe-done = Ya no sigo a { $user } aquí.
+unsubscribe-failed = No se pudo eliminar la suscripción, inténtalo más tarde.
+unsubscribe-missing = Este canal no sigue a { $user }.
+
+link-done = Vinculado a { $user } en lichess. Tus partidas en curso ahora siguen las reglas de juego limpio.
+link-failed = No se pudo guardar, inténtalo más tarde.
+link-start = [Inicia sesión en lichess]({ $url }) para vincular tu cuenta. El enlace vale 10 minutos.
+link-unavailable = La vinculación de cuentas de lichess no está configurada en este bot.
+link-page-expired = Este inicio de sesión ha caducado o ya se usó. Vuelve a ejecutar /link en Discord.
+link-page-denied = lichess no vinculó tu cuenta. Vuelve a ejecutar /link en Discord para intentarlo de nuevo.
+link-page-failed = No se pudo comprobar tu cuenta de lichess, vuelve a ejecutar /link más tarde.
+unlink-done = Tu cuenta de lichess ya no está vinculada.
+unlink-missing = No has vinculado ninguna cuenta de lichess.
+
+fairplay-set-block = Las partidas en curso de los propios miembros no se analizarán aquí.
+fairplay-set-delay = Las partidas en curso de los propios miembros se mostrarán { $moves } jugadas por detrás.
+fairplay-set-after-game = Las partidas en curso de los propios miembros se analizarán cuando terminen.
+fairplay-needs-manage-server = Solo los miembros que pueden gestionar el servidor pueden cambiar sus reglas de juego limpio.
+fairplay-not-in-server = Las reglas de juego limpio solo se pueden fijar en un servidor.
+fairplay-failed = No se pudo guardar el ajuste, inténtalo más tarde.
+
+fair-play-refused = Es tu propia partida en curso, las reglas de juego limpio de lichess no permiten analizarla.
+fair-play-after-game = Es tu propia partida en curso, publicaré el análisis aquí cuando termine.
+fair-play-wait = Es tu propia partida en curso, se podrá analizar cuando termine.
+fair-play-unchecked = No pude comprobar si es tu propia partida, inténtalo más tarde.
+
+language-auto = Automático
+language-set-user = A partir de ahora te responderé en español.
+language-set-server = El idioma de este servidor ahora es español.
+language-cleared-user = Volveré a usar tu idioma de Discord.
+language-cleared-server = Este servidor ya no tiene un idioma configurado.
+language-needs-manage-server = Solo los miembros que pueden gestionar el servidor pueden cambiar su idioma.
+language-not-in-server = El idioma del servidor solo se puede configurar dentro de un servidor.
+language-failed = No se pudo guardar el ajuste, inténtalo más tarde.
+
+## Game report
+
+report-title = Informe de la partida: { $white } contra { $black }
+report-result = Resultado: { $result }
+report-player =
+    Precisión: { $accuracy }%
+    ACPL: { $acpl }
+    Imprecisiones: { $inaccuracies } · Errores: { $mistakes } · Errores graves: { $blunders }
+report-win-drop = Probabilidad de ganar -{ $drop }%
+report-best-was = , lo mejor era { $move }
+report-view-board = Ver tablero
+report-gaps = Jugadas sin valorar porque el motor no respondió: { $moves }
+judgement-inaccuracy = Imprecisión
+judgement-mistake = Error
+judgement-blunder = Error grave
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS analysis (
+	id INT AUTO_INCREMENT PRIMARY KEY,
+	message_id TEXT NOT NULL,
+	fen TEXT NOT NULL,
+	orientation TEXT NOT NULL,
+	last_moves TEXT NOT NULL,
+	depth INT NOT NULL,
+	evaluation FLOAT NOT NULL,
+	mate INT NULL,
+	bestmove TEXT NOT NULL,
+	continuation TEXT NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS subscription (
+	id INT AUTO_INCREMENT PRIMARY KEY,
+	channel_id TEXT NOT NULL,
+	lc_user TEXT NOT NULL,
+	live BOOLEAN NOT NULL DEFAULT FALSE,
+	last_game_id TEXT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
\ No newline at end of file
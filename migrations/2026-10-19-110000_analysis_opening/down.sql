@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE analysis
+	DROP COLUMN eco,
+	DROP COLUMN opening;
\ No newline at end of file
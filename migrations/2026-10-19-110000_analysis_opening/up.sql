@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE analysis
+	ADD COLUMN eco TEXT NULL,
+	ADD COLUMN opening TEXT NULL;
\ No newline at end of file
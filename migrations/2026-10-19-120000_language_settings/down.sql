@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS guild_settings;
+DROP TABLE IF EXISTS user_settings;
\ No newline at end of file
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS guild_settings (
+	id INT AUTO_INCREMENT PRIMARY KEY,
+	guild_id TEXT NOT NULL,
+	locale TEXT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS user_settings (
+	id INT AUTO_INCREMENT PRIMARY KEY,
+	user_id TEXT NOT NULL,
+	locale TEXT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
\ No newline at end of file
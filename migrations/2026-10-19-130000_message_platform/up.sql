@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE message
+	ADD COLUMN platform VARCHAR(16) NOT NULL DEFAULT 'lichess';
\ No newline at end of file
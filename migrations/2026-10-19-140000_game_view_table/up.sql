@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS game_view (
+	id INT AUTO_INCREMENT PRIMARY KEY,
+	message_id TEXT NOT NULL,
+	game_id TEXT NOT NULL,
+	ply INT NULL,
+	orientation TEXT NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
\ No newline at end of file
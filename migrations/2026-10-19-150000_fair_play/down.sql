@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS linked_account;
+
+ALTER TABLE guild_settings
+	DROP COLUMN fair_play,
+	DROP COLUMN fair_play_moves;
\ No newline at end of file
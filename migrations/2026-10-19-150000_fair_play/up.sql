@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS linked_account (
+	id INT AUTO_INCREMENT PRIMARY KEY,
+	user_id TEXT NOT NULL,
+	lichess_user TEXT NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE guild_settings
+	ADD COLUMN fair_play VARCHAR(16) NOT NULL DEFAULT 'block',
+	ADD COLUMN fair_play_moves INT NOT NULL DEFAULT 5;
\ No newline at end of file
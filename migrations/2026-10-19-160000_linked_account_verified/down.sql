@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE linked_account DROP COLUMN verified;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE linked_account ADD verified BOOLEAN NOT NULL DEFAULT FALSE;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE analysis DROP COLUMN variant;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE analysis ADD variant VARCHAR(16) NOT NULL DEFAULT 'standard';
\ No newline at end of file
@@ -0,0 +1,226 @@
+use shakmaty::Color;
+
+use crate::i18n::{tr, Locale};
+
+/// Evaluations are clamped to this many centipawns, the same cap lichess
+/// uses, so a missed mate doesn't swamp the average.
+pub const CP_CAP: i32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    /// Judges a move by how many win% points it gave away, using lichess'
+    /// 0.1 / 0.2 / 0.3 winning chance thresholds.
+    pub fn from_drop(drop: f32) -> Option<Judgement> {
+        if drop >= 15.0 {
+            Some(Judgement::Blunder)
+        } else if drop >= 10.0 {
+            Some(Judgement::Mistake)
+        } else if drop >= 5.0 {
+            Some(Judgement::Inaccuracy)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self, locale: Locale) -> String {
+        let id = match self {
+            Judgement::Inaccuracy => "judgement-inaccuracy",
+            Judgement::Mistake => "judgement-mistake",
+            Judgement::Blunder => "judgement-blunder",
+        };
+        tr(locale, id)
+    }
+
+    pub fn annotation(&self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "?!",
+            Judgement::Mistake => "?",
+            Judgement::Blunder => "??",
+        }
+    }
+}
+
+/// lichess' win% for an evaluation from one side's point of view.
+pub fn win_percent(cp: i32) -> f32 {
+    let cp = cp.clamp(-CP_CAP, CP_CAP) as f32;
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * cp).exp()) - 1.0)
+}
+
+/// lichess' per-move accuracy from the win% before and after the move.
+pub fn move_accuracy(win_before: f32, win_after: f32) -> f32 {
+    let drop = (win_before - win_after).max(0.0);
+    (103.1668 * (-0.04354 * drop).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+/// `color`'s accuracy over a game the way lichess works it out. `wins` is
+/// white's win% before the first move and after each one, `None` where the
+/// position couldn't be evaluated, and `first` made the first move.
+///
+/// Each move's accuracy is weighted by how much the win% swung around it,
+/// so moves in a quiet position count for less, and that mean is averaged
+/// with the plain harmonic mean, which a few bad moves pull down harder.
+/// Moves next to a gap are left out. `None` if `color` has no moves left.
+pub fn game_accuracy(wins: &[Option<f32>], first: Color, color: Color) -> Option<f32> {
+    let moves = wins.len().saturating_sub(1);
+    let window = (moves / 10).clamp(2, 8);
+
+    let mut weighted = (0.0, 0.0);
+    let mut harmonic = (0.0, 0);
+    for i in 0..moves {
+        let mover = if i % 2 == 0 { first } else { !first };
+        let (Some(before), Some(after)) = (wins[i], wins[i + 1]) else {
+            continue;
+        };
+        if mover != color {
+            continue;
+        }
+
+        let (before, after) = match mover {
+            Color::White => (before, after),
+            Color::Black => (100.0 - before, 100.0 - after),
+        };
+        let accuracy = move_accuracy(before, after);
+        // the first few moves share the opening window
+        let start = (i + 2).saturating_sub(window);
+        let weight = volatility(&wins[start..(start + window).min(wins.len())]);
+
+        weighted.0 += accuracy * weight;
+        weighted.1 += weight;
+        harmonic.0 += 1.0 / accuracy.max(1.0);
+        harmonic.1 += 1;
+    }
+
+    if harmonic.1 == 0 {
+        return None;
+    }
+    let weighted = weighted.0 / weighted.1;
+    let harmonic = harmonic.1 as f32 / harmonic.0;
+    Some((weighted + harmonic) / 2.0)
+}
+
+/// The standard deviation of the known win% in `window`, kept between the
+/// 0.5 and 12 lichess allows a move's weight.
+fn volatility(window: &[Option<f32>]) -> f32 {
+    let known: Vec<f32> = window.iter().flatten().copied().collect();
+    if known.is_empty() {
+        return 0.5;
+    }
+
+    let mean = known.iter().sum::<f32>() / known.len() as f32;
+    let variance = known.iter().map(|win| (win - mean).powi(2)).sum::<f32>() / known.len() as f32;
+    variance.sqrt().clamp(0.5, 12.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn win_percent_is_even_at_zero_and_symmetric() {
+        assert_eq!(win_percent(0), 50.0);
+        for cp in [35, 100, 300, 999] {
+            assert!(close(win_percent(cp) + win_percent(-cp), 100.0), "{}", cp);
+            assert!(win_percent(cp) > 50.0);
+        }
+        // lichess' curve gives a pawn up about 59%
+        assert!(close(win_percent(100), 59.1), "{}", win_percent(100));
+    }
+
+    #[test]
+    fn win_percent_is_capped() {
+        assert_eq!(win_percent(5000), win_percent(CP_CAP));
+        assert_eq!(win_percent(-5000), win_percent(-CP_CAP));
+        assert!(win_percent(CP_CAP) < 100.0);
+    }
+
+    #[test]
+    fn move_accuracy_of_known_drops() {
+        assert!(close(move_accuracy(50.0, 50.0), 100.0));
+        // gaining never costs accuracy
+        assert!(close(move_accuracy(40.0, 60.0), 100.0));
+        assert!(close(move_accuracy(60.0, 50.0), 63.58));
+        assert!(close(move_accuracy(80.0, 30.0), 8.53));
+        assert_eq!(move_accuracy(100.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn judgements_start_at_their_thresholds() {
+        assert_eq!(Judgement::from_drop(0.0), None);
+        assert_eq!(Judgement::from_drop(4.99), None);
+        assert_eq!(Judgement::from_drop(5.0), Some(Judgement::Inaccuracy));
+        assert_eq!(Judgement::from_drop(9.99), Some(Judgement::Inaccuracy));
+        assert_eq!(Judgement::from_drop(10.0), Some(Judgement::Mistake));
+        assert_eq!(Judgement::from_drop(14.99), Some(Judgement::Mistake));
+        assert_eq!(Judgement::from_drop(15.0), Some(Judgement::Blunder));
+        assert_eq!(Judgement::from_drop(60.0), Some(Judgement::Blunder));
+    }
+
+    #[test]
+    fn perfect_games_are_fully_accurate() {
+        let wins = vec![Some(52.0); 21];
+        let white = game_accuracy(&wins, Color::White, Color::White).unwrap();
+        let black = game_accuracy(&wins, Color::White, Color::Black).unwrap();
+        assert!(close(white, 100.0) && close(black, 100.0));
+    }
+
+    #[test]
+    fn one_blunder_costs_more_than_the_plain_mean() {
+        // white blunders on their third move, everything else is perfect
+        let mut wins = vec![Some(50.0); 7];
+        for win in wins.iter_mut().skip(5) {
+            *win = Some(10.0);
+        }
+        let accuracy = game_accuracy(&wins, Color::White, Color::White).unwrap();
+
+        let blunder = move_accuracy(50.0, 10.0);
+        let mean = (100.0 + 100.0 + blunder) / 3.0;
+        assert!(accuracy < mean, "{} < {}", accuracy, mean);
+        // black only kept the win they were handed
+        let black = game_accuracy(&wins, Color::White, Color::Black).unwrap();
+        assert!(close(black, 100.0));
+    }
+
+    #[test]
+    fn black_to_move_first_is_graded_as_black() {
+        // black gives away 40 points, white's reply is perfect
+        let wins = [Some(50.0), Some(90.0), Some(90.0)];
+        let black = game_accuracy(&wins, Color::Black, Color::Black).unwrap();
+        let white = game_accuracy(&wins, Color::Black, Color::White).unwrap();
+        assert!(black < 20.0, "{}", black);
+        assert!(close(white, 100.0));
+    }
+
+    #[test]
+    fn moves_next_to_gaps_are_left_out() {
+        let wins = [Some(50.0), None, Some(10.0), Some(10.0)];
+        // only white's second move has both sides of it
+        assert!(close(
+            game_accuracy(&wins, Color::White, Color::White).unwrap(),
+            100.0
+        ));
+        assert_eq!(game_accuracy(&wins, Color::White, Color::Black), None);
+        let wins = [Some(50.0), Some(50.0), None, Some(50.0), Some(50.0)];
+        assert!(close(
+            game_accuracy(&wins, Color::White, Color::Black).unwrap(),
+            100.0
+        ));
+    }
+
+    #[test]
+    fn volatility_is_bounded() {
+        assert_eq!(volatility(&[Some(50.0), Some(50.0)]), 0.5);
+        assert_eq!(volatility(&[Some(0.0), Some(100.0)]), 12.0);
+        assert!(close(volatility(&[Some(40.0), Some(50.0)]), 5.0));
+        assert_eq!(volatility(&[None, None]), 0.5);
+    }
+}
@@ -0,0 +1,243 @@
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+use dotenv::dotenv;
+use shakmaty::{Chess, Color};
+use tokio::sync::Mutex;
+
+use lichess_stockfish::board::{help, help_fen, help_pgn, GetStockFishResponse, DEFAULT_DEPTH};
+use lichess_stockfish::i18n::Locale;
+use lichess_stockfish::models::error::Error;
+use lichess_stockfish::position::{self, Variant};
+use lichess_stockfish::renderer::gen_board;
+use lichess_stockfish::{book, logging, notation};
+
+/// Analyse lichess games and positions from the terminal, without Discord.
+#[derive(Parser)]
+#[command(name = "lichess-stockfish-cli")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Analyse the game a lichess user is playing right now
+    Analyze {
+        user: String,
+        #[command(flatten)]
+        options: AnalysisOptions,
+        /// Draw the board from the opponent's side
+        #[arg(long)]
+        flip: bool,
+    },
+    /// Analyse a FEN
+    Fen {
+        fen: String,
+        #[command(flatten)]
+        options: AnalysisOptions,
+    },
+    /// Draw a FEN to a PNG without analysing it
+    Render {
+        fen: String,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Draw the board from black's side
+        #[arg(long)]
+        black: bool,
+    },
+    /// Analyse the final position of a PGN file
+    Pgn {
+        file: PathBuf,
+        #[command(flatten)]
+        options: AnalysisOptions,
+    },
+}
+
+#[derive(clap::Args)]
+struct AnalysisOptions {
+    #[arg(short, long, default_value_t = DEFAULT_DEPTH)]
+    depth: u8,
+    /// Also search for what the opponent is threatening
+    #[arg(long)]
+    threats: bool,
+    /// Write the rendered board here
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+    // keep stdout for the report, only problems go to stderr
+    logging::init("warn");
+
+    let result = match Cli::parse().command {
+        Command::Analyze {
+            user,
+            options,
+            flip,
+        } => analyze(&user, flip, &options).await,
+        Command::Fen { fen, options } => analyze_fen(&fen, &options).await,
+        Command::Render { fen, output, black } => render(&fen, black, &output),
+        Command::Pgn { file, options } => analyze_pgn(&file, &options).await,
+    };
+
+    if let Err(why) = result {
+        eprintln!("{}", why);
+        exit(1);
+    }
+}
+
+async fn analyze(user: &str, flip: bool, options: &AnalysisOptions) -> Result<(), Error> {
+    let channel = Arc::new(Mutex::new(&user));
+    let resp = help(channel, flip, options.depth, options.threats).await?;
+    report(&resp, options)
+}
+
+async fn analyze_fen(fen: &str, options: &AnalysisOptions) -> Result<(), Error> {
+    let name = uuid::Uuid::new_v4().to_string();
+    let resp = help_fen(fen, vec![], options.depth, options.threats, name).await?;
+    report(&resp, options)
+}
+
+async fn analyze_pgn(file: &Path, options: &AnalysisOptions) -> Result<(), Error> {
+    let pgn = std::fs::read_to_string(file)
+        .map_err(|e| Error::BasicError(format!("Error reading {:?}: {:?}", file, e)))?;
+    let name = uuid::Uuid::new_v4().to_string();
+    let resp = help_pgn(&pgn, options.depth, options.threats, name).await?;
+    report(&resp, options)
+}
+
+fn render(fen: &str, black: bool, output: &Path) -> Result<(), Error> {
+    let pos = parse(fen)?;
+    let orientation = if black { Color::Black } else { Color::White };
+
+    let png = gen_board(
+        position::to_board(&pos, orientation),
+        position::color_to_mode(orientation),
+        &position::to_fen(&pos),
+        Variant::Standard,
+        vec![],
+        "None".to_string(),
+        None,
+    );
+    write_png(&png, output)?;
+
+    println!("{}", draw(&pos, orientation));
+    Ok(())
+}
+
+fn parse(fen: &str) -> Result<Chess, Error> {
+    position::parse_fen(fen).ok_or_else(|| Error::BasicError(format!("Invalid FEN {:?}", fen)))
+}
+
+fn write_png(png: &[u8], output: &Path) -> Result<(), Error> {
+    std::fs::write(output, png)
+        .map_err(|e| Error::BasicError(format!("Error writing {:?}: {:?}", output, e)))?;
+    println!("Wrote {}", output.display());
+    Ok(())
+}
+
+fn report(resp: &GetStockFishResponse, options: &AnalysisOptions) -> Result<(), Error> {
+    if let Some(pos) = position::parse_fen(&resp.fen) {
+        let orientation = if resp.orientation == "b" {
+            Color::Black
+        } else {
+            Color::White
+        };
+        println!();
+        println!("{}", draw(&pos, orientation));
+    }
+
+    println!("FEN: {}", resp.fen);
+    println!("Evaluation: {:+.2}%", resp.evaluation);
+    if let Some(mate) = resp.mate {
+        println!("Forced mate: {}", mate);
+    }
+    if let Some(probe) = &resp.tablebase {
+        println!("Tablebase: {}", probe.describe(Locale::English));
+    }
+    if !resp.book_moves.is_empty() {
+        println!("Book moves: {}", book::describe(&resp.book_moves));
+    }
+    match &resp.game_over {
+        Some(game_over) => println!("Game over: {}", game_over),
+        None => {
+            println!(
+                "Best move: {}",
+                notation::best_move_to_san(&resp.fen, resp.variant, &resp.bestmove)
+            );
+            println!(
+                "Line: {}",
+                notation::line_to_san(&resp.fen, resp.variant, &resp.continuation)
+            );
+        }
+    }
+    if let Some(threat) = &resp.threat {
+        // on our move the threat was searched with the move passed over
+        let fen = match resp.turn == resp.player {
+            true => position::null_move_fen(&resp.fen).unwrap_or(resp.fen.clone()),
+            false => resp.fen.clone(),
+        };
+        println!(
+            "Threat: {}",
+            notation::best_move_to_san(&fen, resp.variant, &threat.bestmove)
+        );
+    }
+    if let Some(opening) = &resp.opening {
+        println!("Opening: {}", opening.label());
+    }
+    println!("Depth: {}", resp.depth);
+
+    match &options.output {
+        Some(output) => write_png(&resp.image, output),
+        None => Ok(()),
+    }
+}
+
+/// The position as unicode pieces with rank and file labels.
+fn draw(pos: &Chess, orientation: Color) -> String {
+    let board = position::to_board(pos, orientation);
+    let mut files: Vec<char> = ('a'..='h').collect();
+    let mut ranks: Vec<char> = ('1'..='8').rev().collect();
+    if orientation == Color::Black {
+        files.reverse();
+        ranks.reverse();
+    }
+
+    let mut out = String::new();
+    for (row, rank) in board.iter().zip(ranks) {
+        out.push(rank);
+        out.push(' ');
+        for code in row {
+            let piece = match (code >> 3, code & 0b111) {
+                (1, 1) => '♔',
+                (1, 2) => '♕',
+                (1, 3) => '♖',
+                (1, 4) => '♗',
+                (1, 5) => '♘',
+                (1, 6) => '♙',
+                (2, 1) => '♚',
+                (2, 2) => '♛',
+                (2, 3) => '♜',
+                (2, 4) => '♝',
+                (2, 5) => '♞',
+                (2, 6) => '♟',
+                _ => '·',
+            };
+            out.push(piece);
+            out.push(' ');
+        }
+        out.push('\n');
+    }
+    out.push_str("  ");
+    for file in files {
+        out.push(file);
+        out.push(' ');
+    }
+
+    out
+}
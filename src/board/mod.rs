@@ -1,11 +1,21 @@
 use core::str;
-use std::{num, process::exit, sync::Arc};
+use std::{num, sync::Arc};
 
-use image::ImageReader;
 use serde::{Deserialize, Serialize};
+use shakmaty::{Color as ShakmatyColor, Position};
 use tokio::sync::Mutex;
+use tracing::{debug, field, info, instrument, warn, Span};
 
+use crate::book::{self, BookMove};
+use crate::engine;
+use crate::i18n::{self, Locale};
+use crate::metrics;
 use crate::models::error::Error;
+use crate::openings::{self, Opening};
+use crate::position::{self, Variant};
+use crate::renderer;
+use crate::source::{ChessCom, Game, GameSource, Lichess, Platform, Player, Target};
+use crate::tablebase;
 
 #[derive(Debug)]
 enum PieceType {
@@ -47,19 +57,34 @@ impl Color {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Depth used for a plain `!help` or refresh.
+pub const DEFAULT_DEPTH: u8 = 15;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StockfishResponse {
-    success: bool,
-    evaluation: Option<f32>,
-    mate: Option<i32>,
-    bestmove: String,
-    continuation: String,
+    pub success: bool,
+    pub evaluation: Option<f32>,
+    pub mate: Option<i32>,
+    pub bestmove: String,
+    pub continuation: String,
+}
+
+/// What the opponent would play if it were their move.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Threat {
+    pub bestmove: String,
+    /// How much the threat changes the evaluation, in pawns from the
+    /// player's side. Only known when we searched a null move for it.
+    pub swing: Option<f32>,
+    pub mate: Option<i32>,
 }
 
 pub async fn get_html(url: String) -> Result<String, Error> {
+    let _timer = metrics::LICHESS_FETCH_SECONDS.start_timer();
     let res = reqwest::get(url).await;
 
     if res.is_err() {
+        metrics::LICHESS_FETCH_FAILURES.inc();
         return Err(Error::BasicError(format!(
             "Error fetching html {:?}",
             res.err()
@@ -68,7 +93,16 @@ pub async fn get_html(url: String) -> Result<String, Error> {
 
     let res = res.unwrap();
 
+    // lichess has no page for players that don't exist
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(Error::NotFound(format!(
+            "Error fetching html {:?}",
+            res.status()
+        )));
+    }
+
     if res.status().as_u16() != 200 {
+        metrics::LICHESS_FETCH_FAILURES.inc();
         return Err(Error::BasicError(format!(
             "Error fetching html {:?}",
             res.status()
@@ -78,6 +112,7 @@ pub async fn get_html(url: String) -> Result<String, Error> {
     let body = res.text().await;
 
     if body.is_err() {
+        metrics::LICHESS_FETCH_FAILURES.inc();
         return Err(Error::BasicError(format!(
             "Error fetching html {:?}",
             body.err()
@@ -90,21 +125,34 @@ pub async fn get_html(url: String) -> Result<String, Error> {
 pub async fn get_stock_fish(url: Arc<Mutex<&str>>) -> Result<StockfishResponse, Error> {
     let url = url.lock().await;
     let url = url.to_string();
-    let res = reqwest::get(url).await.unwrap();
-    let body = res.text().await.unwrap();
-    let stockfish: Result<StockfishResponse, serde_json::Error> = serde_json::from_str(&body);
-    if stockfish.is_err() {
-        println!("Error: {:?} {:?}", body, stockfish.err());
-        exit(1); // todo lets not do this
-    }
-    Ok(stockfish.unwrap())
-    // Ok(String::from("test"))
+    let res = reqwest::get(url)
+        .await
+        .map_err(|e| Error::BasicError(format!("Error fetching stockfish {:?}", e)))?;
+    let body = res
+        .text()
+        .await
+        .map_err(|e| Error::BasicError(format!("Error fetching stockfish {:?}", e)))?;
+    serde_json::from_str(&body)
+        .map_err(|e| Error::BasicError(format!("Error parsing stockfish reply {:?} {:?}", body, e)))
 }
 
-pub async fn parse_html<'a>(
+/// What `parse_html` reads off a player's TV page.
+#[derive(Debug, Clone)]
+pub struct ScrapedPosition {
+    /// The pieces in the layout `gen_board` draws, seen from `mode`'s side.
+    /// Empty for variants, which `help` reads from the moves instead.
+    pub board: [[u8; 8]; 8],
+    /// "w" or "b", the tracked player's color
+    pub mode: &'static str,
+    pub last_moves: Vec<(f32, f32)>,
+    pub variant: Variant,
+}
+
+#[instrument(name = "scrape", skip_all)]
+pub async fn parse_html(
     url: Arc<Mutex<&str>>,
     channel: Arc<Mutex<&str>>,
-) -> Result<([[u8; 8]; 8], &'a str, Vec<(f32, f32)>), Error> {
+) -> Result<ScrapedPosition, Error> {
     let url = url.lock().await;
     let url = url.to_string();
 
@@ -114,24 +162,38 @@ pub async fn parse_html<'a>(
     // set board at 3, 5 to 1
     let html = get_html(url).await?;
 
-    let dom = tl::parse(html.as_str(), tl::ParserOptions::default()).unwrap();
+    let dom = tl::parse(html.as_str(), tl::ParserOptions::default())
+        .map_err(|e| Error::BasicError(format!("Error parsing html {:?}", e)))?;
     // get title
     let title = dom.query_selector("title").and_then(|mut iter| iter.next());
     if title.is_none() {
-        println!("Title not found");
-        exit(1);
+        return Err(Error::BasicError("Title not found".to_string()));
     }
     let title = title.unwrap();
-    println!(
+    debug!(
         "Title: {:?}",
         title.get(dom.parser()).unwrap().inner_text(dom.parser())
     );
-    println!("Fetching Piece state for: {:?}", channel);
+
+    // pockets and checks given aren't on the board, so variant games are
+    // read from their moves instead, see `help`
+    let variant = match dom.get_elements_by_class_name("variant-link").next() {
+        Some(link) => Variant::parse(&link.get(dom.parser()).unwrap().inner_text(dom.parser()))?,
+        None => Variant::Standard,
+    };
+    if variant != Variant::Standard {
+        return Ok(ScrapedPosition {
+            board,
+            mode: "w",
+            last_moves: vec![],
+            variant,
+        });
+    }
+    debug!("Fetching Piece state for: {:?}", channel);
     let pieces = dom.query_selector("piece");
 
-    if !pieces.is_some() {
-        println!("Pieces not found");
-        exit(1);
+    if pieces.is_none() {
+        return Err(Error::BasicError("Pieces not found".to_string()));
     }
 
     let orientation_b = dom.get_elements_by_class_name("player").next();
@@ -211,7 +273,7 @@ pub async fn parse_html<'a>(
             .to_lowercase()
             .contains(channel.to_lowercase().as_str())
         {
-            println!("Player found at index: {:?}", i);
+            debug!("Player found at index: {:?}", i);
             classes = Some(
                 i.get(dom.parser())
                     .unwrap()
@@ -234,15 +296,15 @@ pub async fn parse_html<'a>(
     // bug here, player is not alwyas the first in the list.
     let nclasses = classes.as_utf8_str();
     let nclasses = nclasses.split_whitespace().collect::<Vec<&str>>();
-    println!("classess: {:?}", nclasses.clone());
-    println!("has white: {:?}", nclasses.contains(&"white"));
+    debug!("classess: {:?}", nclasses.clone());
+    debug!("has white: {:?}", nclasses.contains(&"white"));
     let mode = match nclasses.contains(&"white") {
         // if white is first then it means our player is black
         true => "w",
         false => "b",
     };
     // println!("Orientation W: {:?}", orientation_w);
-    println!(
+    info!(
         "Playing as: {:?}",
         match mode {
             "w" => "White",
@@ -253,7 +315,7 @@ pub async fn parse_html<'a>(
     let pieces = pieces.unwrap();
 
     if pieces.clone().count() == 0 {
-        return Err(Error::BasicError("Not in an active game".to_string()));
+        return Err(Error::NotFound("Not in an active game".to_string()));
     }
 
     for piece in pieces.clone().into_iter() {
@@ -313,12 +375,12 @@ pub async fn parse_html<'a>(
         board[top as usize][left as usize] = code;
     }
 
-    println!("\nBoard: ");
+    let mut dump = String::new();
     for i in 0..8 {
         for j in 0..8 {
             let piece = board[i][j];
             if piece == 0 {
-                print!("{piece:<2}, ");
+                dump.push_str(&format!("{piece:<2}, "));
                 continue;
             }
 
@@ -341,12 +403,18 @@ pub async fn parse_html<'a>(
                 _ => panic!("Invalid piece type"),
             };
 
-            print!("{:<2}, ", format!("{color}{piece_type}"));
+            dump.push_str(&format!("{:<2}, ", format!("{color}{piece_type}")));
         }
-        println!();
+        dump.push('\n');
     }
+    debug!("Board:\n{}", dump);
 
-    Ok((board, mode, last_moves_x_y_vec))
+    Ok(ScrapedPosition {
+        board,
+        mode,
+        last_moves: last_moves_x_y_vec,
+        variant: Variant::Standard,
+    })
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -356,25 +424,131 @@ pub struct GetStockFishResponse {
     pub mate: Option<i32>,
     pub bestmove: String,
     pub continuation: String,
+    /// The name to upload `image` as
     pub file: String,
+    /// The rendered board as a PNG, left out of the JSON
+    #[serde(skip)]
+    pub image: Vec<u8>,
+    pub depth: u8,
+    pub fen: String,
+    /// "w" or "b", whichever side the image is drawn from
+    pub orientation: String,
+    /// "w" or "b", the tracked player's color
+    pub player: String,
+    /// "w" or "b", the side to move
+    pub turn: String,
+    pub last_moves: Vec<(f32, f32)>,
+    pub opening: Option<Opening>,
+    /// The exact result when the position was found in the syzygy tables
+    pub tablebase: Option<tablebase::Probe>,
+    pub book_moves: Vec<BookMove>,
+    pub threat: Option<Threat>,
+    /// Why the game is over, when it is
+    pub game_over: Option<String>,
+    /// The rules `fen` is a position of
+    #[serde(default)]
+    pub variant: Variant,
 }
 
-pub async fn help<'a>(channel: Arc<Mutex<&'a &str>>) -> Result<GetStockFishResponse, Error> {
+/// The other side's mode string.
+pub fn opposite(mode: &str) -> &'static str {
+    if mode == "b" {
+        "w"
+    } else {
+        "b"
+    }
+}
+
+/// Works out whose turn it is from the last move highlights on a board as
+/// scraped, i.e. drawn from `orientation`'s side. Whoever owns the piece on
+/// a highlighted square just moved, so it's the other side's turn. With no
+/// highlights the game hasn't started and white is to move.
+pub fn side_to_move(
+    board: &[[u8; 8]; 8],
+    orientation: &str,
+    last_moves_x_y_vec: &[(f32, f32)],
+) -> &'static str {
+    for (x, y) in last_moves_x_y_vec {
+        let col = (x / 12.5).round() as usize;
+        let row = (y / 12.5).round() as usize;
+        if row > 7 || col > 7 {
+            continue;
+        }
+
+        match board[row][col] >> 3 {
+            1 => return "b",
+            2 => return "w",
+            _ => {}
+        }
+    }
+
+    if last_moves_x_y_vec.is_empty() {
+        return "w";
+    }
+
+    // highlights but no piece on them shouldn't happen, fall back to
+    // assuming it's the tracked player's turn like we always used to
+    warn!("Couldn't tell whose turn it is, assuming {}", orientation);
+    if orientation == "b" {
+        "b"
+    } else {
+        "w"
+    }
+}
+
+#[instrument(name = "help", skip(channel), fields(target = field::Empty))]
+pub async fn help<'a>(
+    channel: Arc<Mutex<&'a &str>>,
+    flipped: bool,
+    depth: u8,
+    threats: bool,
+) -> Result<GetStockFishResponse, Error> {
     let channel = channel.lock().await;
     let channel = channel.to_string();
+    Span::current().record("target", channel.as_str());
     let name = channel.clone();
     let url = format!("https://lichess.org/@/{}/tv", channel.clone());
     let url = Arc::new(Mutex::new(url.as_str()));
     let channel = Arc::new(Mutex::new(channel.as_str()));
-    let res = parse_html(url, channel).await?;
+    let scraped = parse_html(url, channel).await?;
+
+    if scraped.variant != Variant::Standard {
+        info!("{} is playing {}", name, scraped.variant.name());
+        let game = Lichess
+            .current_game(&name)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("No lichess game found for {}", name)))?;
+        return help_game(&game, &name, flipped, depth, threats, name.clone()).await;
+    }
 
-    let mut board = res.0;
-    let mode = res.1;
-    let last_moves_x_y_vec = res.2;
+    let mut board = scraped.board;
+    // the tracked player's color, which is also the side the board is drawn from
+    let mode = scraped.mode;
+    let last_moves_x_y_vec = scraped.last_moves;
+    let turn = side_to_move(&board, mode, &last_moves_x_y_vec);
     // set board at 3, 5 to 1
 
     // since we flip the board for stockfish we need to keep a copy of the original board
-    let board_for_image = board.clone();
+    let mut board_for_image = board.clone();
+    let mut image_mode = mode;
+    let mut last_moves_x_y_vec = last_moves_x_y_vec;
+
+    // flipped boards are drawn from the other side, so rotate the scraped board
+    // and mirror the last move squares (percentages are the top left of a 12.5% square)
+    if flipped {
+        let mut new_board = [[0u8; 8]; 8];
+        for i in 0..8 {
+            for j in 0..8 {
+                new_board[i][j] = board_for_image[7 - i][7 - j];
+            }
+        }
+        board_for_image = new_board;
+        image_mode = opposite(mode);
+        last_moves_x_y_vec = last_moves_x_y_vec
+            .into_iter()
+            .map(|(x, y)| (87.5 - x, 87.5 - y))
+            .collect();
+    }
 
     // if mode is b then we need to flip the board
     if mode == "b" {
@@ -388,19 +562,248 @@ pub async fn help<'a>(channel: Arc<Mutex<&'a &str>>) -> Result<GetStockFishRespo
         board = new_board;
     }
 
-    println!();
-    println!("Calculating FEN and sending to stockfish");
+    debug!("Calculating FEN and sending to stockfish");
     // to fen
 
-    let fen = encode_to_fen(board, mode);
-    println!("FEN: {:?}", fen);
+    let fen = encode_to_fen(board, turn);
+    info!("FEN: {:?}", fen);
 
-    println!();
-    println!("fetching stockfish evaluation...");
-    let url = format!("https://stockfish.online/api/s/v2.php?fen={}&depth=15", fen);
-    let url = Arc::new(Mutex::new(url.as_str()));
-    let stockfish = get_stock_fish(url).await?;
+    evaluate(
+        board_for_image,
+        image_mode,
+        mode,
+        last_moves_x_y_vec,
+        fen,
+        depth,
+        threats,
+        name,
+        Variant::Standard,
+    )
+    .await
+}
 
+/// Analyses a FEN for whoever is to move, drawn from white's side.
+pub async fn help_fen(
+    fen: &str,
+    last_moves_x_y_vec: Vec<(f32, f32)>,
+    depth: u8,
+    threats: bool,
+    name: String,
+) -> Result<GetStockFishResponse, Error> {
+    let pos = position::parse_fen(fen)
+        .ok_or_else(|| Error::Invalid(format!("Invalid FEN {:?}", fen)))?;
+    let fen = position::to_fen(&pos);
+    let turn = position::color_to_mode(pos.turn());
+
+    evaluate(
+        decode_fen(&fen)?,
+        "w",
+        turn,
+        last_moves_x_y_vec,
+        fen,
+        depth,
+        threats,
+        name,
+        Variant::Standard,
+    )
+    .await
+}
+
+/// Analyses the final position of a PGN, naming the opening from the moves
+/// played so transpositions are caught.
+pub async fn help_pgn(
+    pgn: &str,
+    depth: u8,
+    threats: bool,
+    name: String,
+) -> Result<GetStockFishResponse, Error> {
+    let plies = position::replay(&position::pgn_sans(pgn))?;
+    let last_moves = match plies.last() {
+        Some(ply) => position::move_to_percent(ply.played, ShakmatyColor::White),
+        None => vec![],
+    };
+    let fen = position::to_fen(&position::final_position(&plies));
+
+    let mut resp = help_fen(&fen, last_moves, depth, threats, name).await?;
+    if let Some(opening) = openings::lookup_moves(&plies) {
+        resp.opening = Some(opening);
+    }
+    Ok(resp)
+}
+
+/// Analyses `game` from `user`'s side, or white's if they aren't playing in
+/// it.
+pub async fn help_game(
+    game: &Game,
+    user: &str,
+    flipped: bool,
+    depth: u8,
+    threats: bool,
+    name: String,
+) -> Result<GetStockFishResponse, Error> {
+    let player = game.color_of(user).unwrap_or(ShakmatyColor::White);
+    help_game_at(game, None, player, flipped, depth, threats, name).await
+}
+
+/// Analyses `game` after `ply` half-moves, or at its latest position, from
+/// `player`'s side, in whichever variant it's played. The last move and
+/// opening come from the PGN; if it won't replay we fall back to the site's
+/// FEN without them, which only works for the latest position.
+#[allow(clippy::too_many_arguments)]
+pub async fn help_game_at(
+    game: &Game,
+    ply: Option<usize>,
+    player: ShakmatyColor,
+    flipped: bool,
+    depth: u8,
+    threats: bool,
+    name: String,
+) -> Result<GetStockFishResponse, Error> {
+    let orientation = if flipped { !player } else { player };
+    let variant = game.variant()?;
+    let start = match game.start_fen() {
+        Some(fen) => position::parse_variant_fen(&fen, variant)
+            .ok_or_else(|| Error::Invalid(format!("Invalid FEN {:?}", fen)))?,
+        None => variant.starting_position(),
+    };
+
+    let plies = match position::replay_from(start.clone(), &position::pgn_sans(&game.pgn)) {
+        Ok(mut plies) => {
+            plies.truncate(ply.unwrap_or(plies.len()));
+            plies
+        }
+        Err(why) if ply.is_some() || game.fen.is_none() => return Err(why),
+        Err(_) => vec![],
+    };
+    let pos = match (&game.fen, plies.is_empty() && ply.is_none()) {
+        (Some(fen), true) => position::parse_variant_fen(fen, variant)
+            .ok_or_else(|| Error::Invalid(format!("Invalid FEN {:?}", fen)))?,
+        _ => position::final_position_from(start, &plies),
+    };
+    let last_moves = match plies.last() {
+        Some(ply) => position::move_to_percent(ply.played, orientation),
+        None => vec![],
+    };
+
+    let mut resp = evaluate(
+        position::to_board(&pos, orientation),
+        position::color_to_mode(orientation),
+        position::color_to_mode(player),
+        last_moves,
+        position::to_fen(&pos),
+        depth,
+        threats,
+        name,
+        variant,
+    )
+    .await?;
+    if variant != Variant::Standard {
+        return Ok(resp);
+    }
+    if let Some(opening) = openings::lookup_moves(&plies) {
+        resp.opening = Some(opening);
+    }
+    Ok(resp)
+}
+
+/// Analyses `player`'s current game on whichever site they play on.
+pub async fn help_player(
+    player: &Player,
+    flipped: bool,
+    depth: u8,
+    threats: bool,
+) -> Result<GetStockFishResponse, Error> {
+    match player.platform {
+        Platform::Lichess => {
+            let name = player.name.as_str();
+            let channel = Arc::new(Mutex::new(&name));
+            help(channel, flipped, depth, threats).await
+        }
+        Platform::ChessCom => {
+            let game = ChessCom.current_game(&player.name).await?.ok_or_else(|| {
+                Error::NotFound(format!("No chess.com games found for {}", player.name))
+            })?;
+            help_game(&game, &player.name, flipped, depth, threats, player.name.clone()).await
+        }
+    }
+}
+
+/// Analyses whatever `target` points at.
+pub async fn help_target(
+    target: &Target,
+    flipped: bool,
+    depth: u8,
+    threats: bool,
+) -> Result<GetStockFishResponse, Error> {
+    match target {
+        Target::Player(player) => help_player(player, flipped, depth, threats).await,
+        Target::Game(game_ref) => {
+            let game = Lichess.game(&game_ref.id).await?.ok_or_else(|| {
+                Error::NotFound(format!("No lichess game {}", game_ref.id))
+            })?;
+            let player = game_ref.orientation;
+            let name = game_ref.id.clone();
+            help_game_at(&game, game_ref.ply, player, flipped, depth, threats, name).await
+        }
+    }
+}
+
+/// Runs the engine on `fen`, a position of `variant`, and renders the
+/// result. `board_for_image`, `image_mode` and `last_moves_x_y_vec` are
+/// passed straight to `gen_board`. The tablebases, opening book and threat
+/// search only know standard chess, so variants go without them.
+#[allow(clippy::too_many_arguments)]
+#[instrument(name = "evaluate", skip_all, fields(fen = %fen, depth = depth))]
+pub async fn evaluate(
+    board_for_image: [[u8; 8]; 8],
+    image_mode: &str,
+    player: &str,
+    last_moves_x_y_vec: Vec<(f32, f32)>,
+    fen: String,
+    depth: u8,
+    threats: bool,
+    name: String,
+    variant: Variant,
+) -> Result<GetStockFishResponse, Error> {
+    let turn = fen.split_whitespace().nth(1).unwrap_or("w");
+    let standard = variant == Variant::Standard;
+
+    debug!("fetching stockfish evaluation...");
+    let depth = depth.min(engine::max_depth());
+
+    // finished games have nothing to search, the engine would only return "(none)"
+    let game_over = game_over(&fen, variant);
+    if let Some(game_over) = &game_over {
+        info!("game over: {}", game_over);
+    }
+
+    // endgames the tables cover have an exact answer, no need to ask the engine
+    let tablebase = match game_over {
+        Some(_) => None,
+        None if standard => tablebase::probe(&fen),
+        None => None,
+    };
+    let stockfish = match (&game_over, &tablebase) {
+        (Some(_), _) => StockfishResponse {
+            success: true,
+            evaluation: None,
+            mate: None,
+            bestmove: "bestmove (none)".to_string(),
+            continuation: String::new(),
+        },
+        (None, Some(probe)) => {
+            info!("tablebase: {}", probe.describe(Locale::English));
+            let bestmove = probe.best_move.clone().unwrap_or_default();
+            StockfishResponse {
+                success: true,
+                evaluation: None,
+                mate: None,
+                bestmove: format!("bestmove {}", bestmove),
+                continuation: bestmove,
+            }
+        }
+        (None, None) => engine::analyse_in(&fen, depth, variant).await?,
+    };
     let bestmove = stockfish.bestmove.replace("bestmove ", "");
     let ponder = bestmove.split_whitespace().nth(2).unwrap_or_else(|| "None");
     let bestmove = bestmove.split_whitespace().nth(0).unwrap_or_else(|| "None");
@@ -411,311 +814,176 @@ pub async fn help<'a>(channel: Arc<Mutex<&'a &str>>) -> Result<GetStockFishRespo
     let mut chance_to_win;
     if stockfish.evaluation.is_some() {
         chance_to_win = (stockfish.evaluation.unwrap()) / 153.0;
-		println!("chance to win: {:?}", chance_to_win);
-        if mode == "b" {
+		debug!("chance to win: {:?}", chance_to_win);
+        // engine scores are from white's side, we report them from the player's
+        if player == "b" {
             chance_to_win = -chance_to_win;
         }
         chance_to_win = chance_to_win * 100.0;
     } else {
         chance_to_win = 0.0;
     }
+    if let Some(probe) = &tablebase {
+        chance_to_win = probe.chance_to_win();
+        if turn != player {
+            chance_to_win = -chance_to_win;
+        }
+    }
+    // the side to move is the one that got mated
+    if position::parse_variant_fen(&fen, variant).is_some_and(|pos| pos.is_checkmate()) {
+        chance_to_win = if turn == player { -100.0 } else { 100.0 };
+    }
 
-    println!("chance to win: {:?}", chance_to_win);
+    info!(
+        chance_to_win,
+        bestmove,
+        ponder,
+        evaluation = ?stockfish.evaluation,
+        mate = ?stockfish.mate,
+        "analysed"
+    );
+    debug!("Continuation: {:?}", stockfish.continuation);
 
-    println!("Best Move: {:?}", bestmove);
-    println!("Ponder: {:?}", ponder);
-    println!("Evaluation: {:?}", stockfish.evaluation);
-    if chance_to_win > 0.0 {
-        println!("You are winning by {:.2}%.", chance_to_win);
-    } else if chance_to_win < 40.0 {
-        println!("Just quit.");
-    } else if chance_to_win < 0.0 {
-        println!("You are losing by {:.2}%.", chance_to_win);
+    let threat = if !threats || !standard || tablebase.is_some() || game_over.is_some() {
+        None
+    } else if turn == player {
+        find_threat(&fen, depth, player, stockfish.evaluation).await
     } else {
-        println!("You are equal.");
-    }
-    println!("Mate: {:?}", stockfish.mate);
-    println!("Continuation: {:?}", stockfish.continuation);
+        // it's already the opponent's move, so their best move is the threat
+        Some(Threat {
+            bestmove: bestmove.to_string(),
+            swing: None,
+            mate: stockfish.mate,
+        })
+    };
 
-    let path = gen_board(
-        board_for_image,
-        mode,
-        last_moves_x_y_vec,
+    // drawing and encoding the image is CPU work, keep it off the runtime
+    let (mode, image_fen, last_moves) = (
+        image_mode.to_string(),
+        fen.clone(),
+        last_moves_x_y_vec.clone(),
+    );
+    let (best_move, threat_move) = (
         bestmove.to_string(),
-        name.to_string(),
+        threat.as_ref().map(|threat| threat.bestmove.clone()),
     );
-
-    let path = String::from(format!("{}.png", path));
+    let image = tokio::task::spawn_blocking(move || {
+        renderer::gen_board(
+            board_for_image,
+            &mode,
+            &image_fen,
+            variant,
+            last_moves,
+            best_move,
+            threat_move,
+        )
+    })
+    .await
+    .map_err(|e| Error::BasicError(format!("Error drawing board {:?}", e)))?;
 
     Ok(GetStockFishResponse {
-        is_black: mode == "b",
+        is_black: turn == "b",
         evaluation: chance_to_win,
         mate: stockfish.mate,
         bestmove: bestmove.to_string(),
         continuation: stockfish.continuation,
-        file: path,
+        file: format!("{}.png", name),
+        image,
+        depth,
+        fen: fen.clone(),
+        orientation: image_mode.to_string(),
+        player: player.to_string(),
+        turn: turn.to_string(),
+        last_moves: last_moves_x_y_vec,
+        opening: openings::lookup_fen(&fen).filter(|_| standard),
+        tablebase,
+        book_moves: if standard { book::lookup(&fen) } else { vec![] },
+        threat,
+        game_over,
+        variant,
     })
 }
 
-pub fn gen_board(
-    board_bytes: [[u8; 8]; 8],
-    mode: &str,
-    last_moves_x_y_vec: Vec<(f32, f32)>,
-    best_move: String,
-    name: String,
-) -> String {
-    fn get_piece_path(piece_type: String) -> String {
-        match piece_type.as_str() {
-            "b" => "b_bishop_png_128px.png",
-            "q" => "b_queen_png_128px.png",
-            "r" => "b_rook_png_128px.png",
-            "n" => "b_knight_png_128px.png",
-            "p" => "b_pawn_png_128px.png",
-            "k" => "b_king_png_128px.png",
-            "B" => "w_bishop_png_128px.png",
-            "Q" => "w_queen_png_128px.png",
-            "R" => "w_rook_png_128px.png",
-            "N" => "w_knight_png_128px.png",
-            "P" => "w_pawn_png_128px.png",
-            "K" => "w_king_png_128px.png",
-            _ => panic!("Invalid piece type {:?}", piece_type),
-        }
-        .to_string()
-    }
-
-    let last_moved_img = "square brown dark_png_128px.png";
-    let last_moved_img1 = "square brown light_png_128px.png";
-
-    // get project root
-    let cwd = std::env::current_dir().unwrap();
-    let mut board = image::open(cwd.join("src/assets/board.png"))
-        .unwrap_or_else(|_| panic!("Error opening {:?}/assets/board.png", cwd.display()));
-    // /mnt/dev/lichess-stockfish/src/assets/board.png
-
-    let mut count = 0;
-    for (x, y) in last_moves_x_y_vec {
-        // x, y are percentages
-        let x = x as f32 / 100.0;
-        let y = y as f32 / 100.0;
-
-        let x = (x * 8.0) as u8;
-        let y = (y * 8.0) as u8;
-
-        let x = x as usize;
-        let y = y as usize;
-
-        let x = x * 128;
-        let y = y * 128;
-
-        let mut new_board = board.to_rgba8();
-        let last_moved;
-        // in theory should never be more then 2
-        if count % 2 == 0 {
-            // last_moved = ImageReader::open(format!(
-            //     "./assets/JohnPablok Cburnett Chess set/PNGs/No shadow/128h/{}",
-            //     last_moved_img
-            // ))
-            // .unwrap();
-
-            last_moved = ImageReader::open(cwd.join(format!(
-				"src/assets/JohnPablok Cburnett Chess set/PNGs/No shadow/128h/{}",
-				last_moved_img
-			)))
-			.unwrap_or_else(|_| panic!("Error opening {:?}/assets/JohnPablok Cburnett Chess set/PNGs/No shadow/128h/{}", cwd.display(), last_moved_img));
-        } else {
-            // last_moved = ImageReader::open(format!(
-            //     "./assets/JohnPablok Cburnett Chess set/PNGs/No shadow/128h/{}",
-            //     last_moved_img1
-            // ))
-            // .unwrap();
-
-            last_moved = ImageReader::open(cwd.join(format!(
-				"src/assets/JohnPablok Cburnett Chess set/PNGs/No shadow/128h/{}",
-				last_moved_img1
-			)))
-			.unwrap_or_else(|_| panic!("Error opening {:?}/assets/JohnPablok Cburnett Chess set/PNGs/No shadow/128h/{}", cwd.display(), last_moved_img1));
-        }
-        count += 1;
-
-        let last_moved = last_moved.decode().unwrap();
-        let last_moved = last_moved.to_rgba8();
-        for i in 0..last_moved.width() {
-            for j in 0..last_moved.height() {
-                let pixel = last_moved.get_pixel(i, j);
-                let pixel = pixel.0;
-                if pixel[3] == 0 {
-                    continue;
-                }
-
-                let x = x + i as usize;
-                let y = y + j as usize;
-                let npixel = new_board.get_pixel_mut(x as u32, y as u32);
-                npixel.0 = [pixel[0], pixel[1], pixel[2], pixel[3]];
-            }
-        }
-        board = image::DynamicImage::ImageRgba8(new_board);
-    }
-
-    // save new board
-    board
-        .save_with_format("nboard.png", image::ImageFormat::Png)
-        .unwrap();
-
-    // best move is currently in b8c6 format
-    // get the x, y of the best move
-    let best_move = best_move.chars().collect::<Vec<char>>();
-    let mut x = best_move[0] as u8 - 97;
-    let mut y = best_move[1] as u8 - 49;
-
-    // if mode is 'b' then we need to revese the x
-    if mode == "b" {
-        x = 7 - x;
-        // y = 7 - y;
-    } else {
-        y = 7 - y;
-    }
-
-    let x = x as usize;
-    let y = y as usize;
-
-    let x = x * 128;
-    let y = y * 128;
-
-    let mut new_board = board.to_rgba8();
-    for i in 0..128 {
-        for j in 0..128 {
-            let x = x + i as usize;
-            let y = y + j as usize;
-            let npixel = new_board.get_pixel_mut(x as u32, y as u32);
-            // make yellow
-            npixel.0 = [204, 202, 62, 255];
-        }
-    }
-    board = image::DynamicImage::ImageRgba8(new_board);
-
-    // save new board
-    board
-        .save_with_format("nboard3.png", image::ImageFormat::Png)
-        .unwrap();
-
-    // next position
-    let mut x = best_move[2] as u8 - 97;
-    let mut y = best_move[3] as u8 - 49;
+/// "Checkmate, White wins", "Stalemate" and so on when `fen` is a finished
+/// game of `variant`.
+pub fn game_over(fen: &str, variant: Variant) -> Option<String> {
+    game_over_in(fen, variant, Locale::English)
+}
 
-    // if mode is 'b' then we need to revese the x
-    if mode == "b" {
-        x = 7 - x;
-        // y = 7 - y;
+/// `game_over` in `locale`.
+pub fn game_over_in(fen: &str, variant: Variant, locale: Locale) -> Option<String> {
+    let pos = position::parse_variant_fen(fen, variant)?;
+    let side = |color| match color {
+        ShakmatyColor::White => i18n::tr(locale, "white"),
+        ShakmatyColor::Black => i18n::tr(locale, "black"),
+    };
+    // a king on the hill or a third check
+    if let Some(winner) = pos.variant_outcome().winner() {
+        Some(i18n::tr_args(
+            locale,
+            "game-over-variant",
+            &[
+                ("variant", variant.name().into()),
+                ("winner", side(winner).into()),
+            ],
+        ))
+    } else if pos.is_checkmate() {
+        Some(i18n::tr_args(
+            locale,
+            "game-over-checkmate",
+            &[("winner", side(!pos.turn()).into())],
+        ))
+    } else if pos.is_stalemate() {
+        Some(i18n::tr(locale, "game-over-stalemate"))
+    } else if pos.is_insufficient_material() {
+        Some(i18n::tr(locale, "game-over-insufficient-material"))
     } else {
-        y = 7 - y;
+        None
     }
+}
 
-    let x = x as usize;
-    let y = y as usize;
-
-    let x = x * 128;
-    let y = y * 128;
-
-    let mut new_board = board.to_rgba8();
-
-    for i in 0..128 {
-        for j in 0..128 {
-            let x = x + i as usize;
-            let y = y + j as usize;
-            let npixel = new_board.get_pixel_mut(x as u32, y as u32);
-            // make yellow
-            npixel.0 = [206, 214, 128, 255];
-        }
-    }
-    board = image::DynamicImage::ImageRgba8(new_board);
-
-    // save new board
-    board
-        .save_with_format("nboard4.png", image::ImageFormat::Png)
-        .unwrap();
-
-    // place pieces on the board
-    let mut pieces = vec![];
-
-    for i in 0..8 {
-        for j in 0..8 {
-            let piece = board_bytes[i][j];
-            if piece == 0 {
-                continue;
-            }
-
-            let piece_type = get_piece_type(piece);
-            let piece_name = get_piece_path(piece_type.to_string());
-            pieces.push((piece_name, j, i));
-        }
-    }
-
-    let mut board = board.to_rgba8();
-
-    for (piece_name, x, y) in pieces {
-        let mut piece = ImageReader::open(cwd.join(format!(
-            "src/assets/JohnPablok Cburnett Chess set/PNGs/No shadow/128h/{}",
-            piece_name
-        )))
-        .unwrap_or_else(|_| {
-            panic!(
-                "Error opening {:?}/assets/JohnPablok Cburnett Chess set/PNGs/No shadow/128h/{}",
-                cwd.display(),
-                piece_name
-            )
-        });
-
-        piece.set_format(image::ImageFormat::Png);
-        let mut piece = piece.decode().unwrap();
-
-        let width = piece.width();
-
-        if width > 128 {
-            let ratio = 128.0 / width as f32;
-            let height = piece.height() as f32 * ratio;
-            piece = piece.resize(128, height as u32, image::imageops::FilterType::Nearest);
-        }
-
-        let width = piece.width() as usize;
-
-        let offset = (128 - width) / 2;
-
-        let x = (x * 128) + offset;
-
-        let height = piece.height() as usize;
-        let offset = (128 - height) / 2;
-        let y = (y * 128) + offset;
-        let piece = piece.to_rgba8();
-
-        for i in 0..piece.width() {
-            for j in 0..piece.height() {
-                let pixel = piece.get_pixel(i, j);
-                let pixel = pixel.0;
-                if pixel[3] == 0 {
-                    continue;
-                }
-
-                let x = x + i as usize;
-                let y = y + j as usize;
-                let npixel = board.get_pixel_mut(x as u32, y as u32);
-                npixel.0 = [pixel[0], pixel[1], pixel[2], pixel[3]];
-            }
+/// Passes the move to the opponent and asks the engine what they'd do with
+/// it. `evaluation` is the engine's score for the real position, in pawns
+/// from white's side.
+async fn find_threat(
+    fen: &str,
+    depth: u8,
+    player: &str,
+    evaluation: Option<f32>,
+) -> Option<Threat> {
+    let null_fen = position::null_move_fen(fen)?;
+    let stockfish = match engine::analyse(&null_fen, depth).await {
+        Ok(stockfish) => stockfish,
+        Err(why) => {
+            warn!("Error finding threat: {}", why);
+            return None;
         }
-    }
+    };
 
-    // random name
-    // let name = channel; // uuid::Uuid::new_v4().to_string();
-    println!("Saving board to pics/{}.png", name);
-    board
-        .save_with_format(format!("pics/{}.png", name), image::ImageFormat::Png)
-        .unwrap();
+    let bestmove = stockfish
+        .bestmove
+        .replace("bestmove ", "")
+        .split_whitespace()
+        .next()
+        .filter(|m| m.len() >= 4)?
+        .to_string();
+
+    let sign = if player == "b" { -1.0 } else { 1.0 };
+    let swing = match (evaluation, stockfish.evaluation) {
+        (Some(before), Some(after)) => Some((after - before) * sign),
+        _ => None,
+    };
 
-    // trim . from start
-    // let name = name.trim_start_matches(".").to_string();
-    println!("Name: {:?}", name);
-    return name;
+    Some(Threat {
+        bestmove,
+        swing,
+        mate: stockfish.mate,
+    })
 }
 
+/// The FEN of a scraped board with `mode` to move. The page doesn't show
+/// castling rights, so they're whatever the kings and rooks still on their
+/// starting squares allow.
 pub fn encode_to_fen(board: [[u8; 8]; 8], mode: &str) -> String {
     let mut fen = String::new();
     for i in 0..8 {
@@ -751,12 +1019,89 @@ pub fn encode_to_fen(board: [[u8; 8]; 8], mode: &str) -> String {
         }
     }
 
-    fen = format!("{}%20{}%20KQkq%20-%200%201", fen, mode);
+    fen = format!(
+        "{}%20{}%20{}%20-%200%201",
+        fen,
+        mode,
+        castling_rights(&board)
+    );
     let fen_unhtml = fen.replace("%20", " ");
 
     fen_unhtml
 }
 
+/// Castling rights for a board with rank 8 in the first row. A king or rook
+/// that moved away and came back still counts, we can't tell from here.
+fn castling_rights(board: &[[u8; 8]; 8]) -> String {
+    let is = |row: usize, col: usize, piece: &str| {
+        board[row][col] != 0 && get_piece_type(board[row][col]) == piece
+    };
+
+    let mut rights = String::new();
+    if is(7, 4, "K") && is(7, 7, "R") {
+        rights.push('K');
+    }
+    if is(7, 4, "K") && is(7, 0, "R") {
+        rights.push('Q');
+    }
+    if is(0, 4, "k") && is(0, 7, "r") {
+        rights.push('k');
+    }
+    if is(0, 4, "k") && is(0, 0, "r") {
+        rights.push('q');
+    }
+    if rights.is_empty() {
+        rights.push('-');
+    }
+    rights
+}
+
+/// Inverse of `encode_to_fen`, returns the board with rank 8 in the first row.
+pub fn decode_fen(fen: &str) -> Result<[[u8; 8]; 8], Error> {
+    let mut board = [[0u8; 8]; 8];
+    let placement = fen.split_whitespace().next().unwrap_or("");
+    // a Crazyhouse pocket follows the board in brackets
+    let placement = placement.split('[').next().unwrap_or("");
+    let ranks: Vec<&str> = placement.split('/').collect();
+
+    if ranks.len() != 8 {
+        return Err(Error::Invalid(format!("Invalid FEN {:?}", fen)));
+    }
+
+    for (i, rank) in ranks.iter().enumerate() {
+        let mut j = 0;
+        for c in rank.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                j += empty as usize;
+                continue;
+            }
+            // marks a promoted piece in Crazyhouse, which drops as a pawn
+            if c == '~' {
+                continue;
+            }
+
+            let piece_type = match c.to_ascii_lowercase() {
+                'k' => 1,
+                'q' => 2,
+                'r' => 3,
+                'b' => 4,
+                'n' => 5,
+                'p' => 6,
+                _ => return Err(Error::Invalid(format!("Invalid FEN {:?}", fen))),
+            };
+            let color = if c.is_ascii_uppercase() { 1 } else { 2 };
+
+            if j > 7 {
+                return Err(Error::Invalid(format!("Invalid FEN {:?}", fen)));
+            }
+            board[i][j] = (color << 3) | piece_type;
+            j += 1;
+        }
+    }
+
+    Ok(board)
+}
+
 pub fn get_color(number: u8) -> String {
     let color = match number {
         1 => "W",
@@ -788,3 +1133,92 @@ pub fn get_piece_type(number: u8) -> String {
 
     piece_type.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> [[u8; 8]; 8] {
+        decode_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn encoded_fens_castle_only_where_the_pieces_allow() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(encode_to_fen(board(start), "w"), start);
+
+        // white has castled, black's queen rook is gone
+        let fen = "1nbqk2r/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 b - - 0 1";
+        assert_eq!(
+            encode_to_fen(board(fen), "b"),
+            "1nbqk2r/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 b k - 0 1"
+        );
+
+        let fen = "8/8/8/4k3/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(
+            encode_to_fen(board(fen), "w"),
+            "8/8/8/4k3/8/8/8/R3K3 w Q - 0 1"
+        );
+        let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(encode_to_fen(board(fen), "w"), fen);
+    }
+
+    #[test]
+    fn encoded_fens_parse() {
+        // a rook on h1 without a king on e1 used to claim castling anyway
+        let fen = "4k2r/8/8/8/8/8/8/3K3R w - - 0 1";
+        let encoded = encode_to_fen(board(fen), "w");
+        assert_eq!(encoded, "4k2r/8/8/8/8/8/8/3K3R w k - 0 1");
+        assert!(position::parse_fen(&encoded).is_some());
+    }
+
+    /// `board` as drawn from black's side.
+    fn rotated(board: [[u8; 8]; 8]) -> [[u8; 8]; 8] {
+        let mut rotated = [[0u8; 8]; 8];
+        for (i, row) in board.iter().enumerate() {
+            for (j, piece) in row.iter().enumerate() {
+                rotated[7 - i][7 - j] = *piece;
+            }
+        }
+        rotated
+    }
+
+    #[test]
+    fn black_to_move_after_white_moved() {
+        // 1.e4, the page highlights e2 and e4 as percentages from the top left
+        let after_e4 = board("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+        let highlights = [(50.0, 75.0), (50.0, 50.0)];
+        assert_eq!(side_to_move(&after_e4, "w", &highlights), "b");
+
+        // the same move on a board drawn for black
+        let highlights = [(37.5, 12.5), (37.5, 37.5)];
+        assert_eq!(side_to_move(&rotated(after_e4), "b", &highlights), "b");
+    }
+
+    #[test]
+    fn white_to_move_after_black_moved() {
+        // 1.e4 e5 highlights e7 and e5
+        let after_e5 = board("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+        let highlights = [(50.0, 12.5), (50.0, 37.5)];
+        assert_eq!(side_to_move(&after_e5, "w", &highlights), "w");
+
+        let highlights = [(37.5, 75.0), (37.5, 50.0)];
+        assert_eq!(side_to_move(&rotated(after_e5), "b", &highlights), "w");
+    }
+
+    #[test]
+    fn white_to_move_without_highlights() {
+        let start = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(side_to_move(&start, "w", &[]), "w");
+        assert_eq!(side_to_move(&rotated(start), "b", &[]), "w");
+    }
+
+    #[test]
+    fn unreadable_highlights_fall_back_to_the_player() {
+        let start = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        // empty squares, and one off the board
+        let highlights = [(50.0, 50.0), (112.5, 0.0)];
+        assert_eq!(side_to_move(&start, "w", &highlights), "w");
+        assert_eq!(side_to_move(&start, "b", &highlights), "b");
+    }
+}
@@ -0,0 +1,259 @@
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use shakmaty::san::San;
+use shakmaty::uci::UciMove;
+use shakmaty::zobrist::Zobrist64;
+use shakmaty::{CastlingMode, Chess, EnPassantMode, Position};
+use tracing::{info, warn};
+
+use crate::position;
+
+/// Each Polyglot entry is a big endian key, move, weight and learn value.
+const ENTRY_SIZE: usize = 16;
+
+/// How many book moves we show.
+pub const MAX_BOOK_MOVES: usize = 5;
+
+static BOOK: OnceLock<Option<Vec<u8>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookMove {
+    pub uci: String,
+    pub san: String,
+    pub weight: u16,
+    /// Share of the position's total weight, in percent
+    pub share: f32,
+}
+
+/// The book at `BOOK_PATH`, read once.
+fn book() -> Option<&'static [u8]> {
+    BOOK.get_or_init(|| {
+        let path = env::var("BOOK_PATH").ok()?;
+        match fs::read(&path) {
+            Ok(bytes) => {
+                info!(
+                    "Loaded opening book {:?} ({} entries)",
+                    path,
+                    bytes.len() / ENTRY_SIZE
+                );
+                Some(bytes)
+            }
+            Err(why) => {
+                warn!("Error reading opening book {:?}: {why:?}", path);
+                None
+            }
+        }
+    })
+    .as_deref()
+}
+
+fn entry(book: &[u8], i: usize) -> (u64, u16, u16) {
+    let entry = &book[i * ENTRY_SIZE..(i + 1) * ENTRY_SIZE];
+    (
+        u64::from_be_bytes(entry[0..8].try_into().unwrap()),
+        u16::from_be_bytes(entry[8..10].try_into().unwrap()),
+        u16::from_be_bytes(entry[10..12].try_into().unwrap()),
+    )
+}
+
+/// Polyglot moves are `from`/`to` squares plus a promotion piece, with
+/// castling written as the king taking its own rook, which is how the
+/// Chess960 UCI notation writes it too.
+fn decode_move(raw: u16) -> String {
+    let square = |bits: u16| {
+        let file = (b'a' + (bits & 7) as u8) as char;
+        let rank = (b'1' + ((bits >> 3) & 7) as u8) as char;
+        format!("{}{}", file, rank)
+    };
+    let promotion = match (raw >> 12) & 7 {
+        1 => "n",
+        2 => "b",
+        3 => "r",
+        4 => "q",
+        _ => "",
+    };
+
+    format!("{}{}{}", square(raw >> 6), square(raw), promotion)
+}
+
+/// Book moves for `pos`, most played first.
+pub fn lookup_position(pos: &Chess) -> Vec<BookMove> {
+    match book() {
+        Some(book) => moves_in(book, pos),
+        None => vec![],
+    }
+}
+
+/// The Polyglot key of `pos`.
+fn key(pos: &Chess) -> u64 {
+    pos.zobrist_hash::<Zobrist64>(EnPassantMode::PseudoLegal).0
+}
+
+/// `pos`' moves in the Polyglot entries of `book`.
+fn moves_in(book: &[u8], pos: &Chess) -> Vec<BookMove> {
+    let key = key(pos);
+
+    // entries are sorted by key, so find the first one for ours
+    let count = book.len() / ENTRY_SIZE;
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let mid = (low + high) / 2;
+        if entry(book, mid).0 < key {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    let mut moves = vec![];
+    for i in low..count {
+        let (entry_key, raw, weight) = entry(book, i);
+        if entry_key != key {
+            break;
+        }
+
+        let book_uci = decode_move(raw);
+        let played = pos
+            .legal_moves()
+            .into_iter()
+            .find(|m| UciMove::from_move(*m, CastlingMode::Chess960).to_string() == book_uci);
+
+        if let Some(played) = played {
+            moves.push(BookMove {
+                uci: position::to_uci(played),
+                san: San::from_move(pos, played).to_string(),
+                weight,
+                share: 0.0,
+            });
+        }
+    }
+
+    let total: f32 = moves.iter().map(|m| m.weight as f32).sum();
+    for m in moves.iter_mut() {
+        if total > 0.0 {
+            m.share = m.weight as f32 / total * 100.0;
+        }
+    }
+    moves.sort_by_key(|m| std::cmp::Reverse(m.weight));
+
+    moves
+}
+
+pub fn lookup(fen: &str) -> Vec<BookMove> {
+    match position::parse_fen(fen) {
+        Some(pos) => lookup_position(&pos),
+        None => vec![],
+    }
+}
+
+/// "e4 (45%), d4 (38%), Nf3 (9%)"
+pub fn describe(moves: &[BookMove]) -> String {
+    moves
+        .iter()
+        .take(MAX_BOOK_MOVES)
+        .map(|m| format!("{} ({:.0}%)", m.san, m.share))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    /// A raw Polyglot move from `from` to `to`, squares numbered a1 = 0.
+    fn raw(from: u16, to: u16) -> u16 {
+        (from << 6) | to
+    }
+
+    /// A book of `(key, move, weight)` entries, sorted the way Polyglot files are.
+    fn book_of(entries: &[(u64, u16, u16)]) -> Vec<u8> {
+        let mut entries = entries.to_vec();
+        entries.sort_by_key(|entry| entry.0);
+        entries
+            .iter()
+            .flat_map(|(key, raw, weight)| {
+                let mut bytes = key.to_be_bytes().to_vec();
+                bytes.extend(raw.to_be_bytes());
+                bytes.extend(weight.to_be_bytes());
+                bytes.extend([0; 4]);
+                bytes
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keys_match_the_polyglot_reference() {
+        // from the Polyglot book format's own examples
+        let cases = [
+            ("", 0x463b96181691fc9c),
+            ("e4", 0x823c9b50fd114196),
+            ("e4 d5", 0x0756b94461c50fb0),
+            ("e4 d5 e5", 0x662fafb965db29d4),
+            ("e4 d5 e5 f5", 0x22a48b5a8e47ff78),
+        ];
+        for (moves, expected) in cases {
+            let sans: Vec<String> = moves.split_whitespace().map(str::to_string).collect();
+            let plies = position::replay(&sans).unwrap();
+            let pos = position::final_position(&plies);
+            assert_eq!(key(&pos), expected, "{:?}", moves);
+        }
+    }
+
+    #[test]
+    fn moves_are_decoded() {
+        assert_eq!(decode_move(raw(12, 28)), "e2e4");
+        assert_eq!(decode_move(raw(6, 21)), "g1f3");
+        // castling is the king taking its rook
+        assert_eq!(decode_move(raw(4, 7)), "e1h1");
+        assert_eq!(decode_move(raw(52, 60) | (4 << 12)), "e7e8q");
+        assert_eq!(decode_move(raw(52, 60) | (1 << 12)), "e7e8n");
+    }
+
+    #[test]
+    fn book_moves_most_played_first() {
+        let start = position::parse_fen(START).unwrap();
+        let book = book_of(&[
+            (key(&start), raw(11, 27), 30),
+            (key(&start), raw(12, 28), 60),
+            (key(&start), raw(6, 21), 10),
+            // another position's move that happens to be legal here too
+            (key(&start) + 1, raw(10, 26), 99),
+        ]);
+
+        let moves = moves_in(&book, &start);
+        let sans: Vec<&str> = moves.iter().map(|m| m.san.as_str()).collect();
+        assert_eq!(sans, ["e4", "d4", "Nf3"]);
+        assert_eq!(moves[0].uci, "e2e4");
+        assert!((moves[0].share - 60.0).abs() < 0.01);
+        assert_eq!(describe(&moves), "e4 (60%), d4 (30%), Nf3 (10%)");
+    }
+
+    #[test]
+    fn book_castling_is_played_as_standard_uci() {
+        let pos =
+            position::parse_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1").unwrap();
+        let book = book_of(&[(key(&pos), raw(4, 7), 5), (key(&pos), raw(4, 0), 1)]);
+
+        let moves = moves_in(&book, &pos);
+        let ucis: Vec<&str> = moves.iter().map(|m| m.uci.as_str()).collect();
+        assert_eq!(ucis, ["e1g1", "e1c1"]);
+        assert_eq!(moves[0].san, "O-O");
+        assert_eq!(moves[1].san, "O-O-O");
+    }
+
+    #[test]
+    fn positions_out_of_book_have_no_moves() {
+        let start = position::parse_fen(START).unwrap();
+        let book = book_of(&[(key(&start), raw(12, 28), 1)]);
+        let after =
+            position::parse_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+                .unwrap();
+        assert!(moves_in(&book, &after).is_empty());
+        assert!(moves_in(&[], &start).is_empty());
+    }
+}
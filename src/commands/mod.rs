@@ -0,0 +1,420 @@
+use serenity::all::{
+    Command, CommandDataOptionValue, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, EditInteractionResponse, Permissions,
+};
+use tracing::{error, warn, Span};
+
+use crate::fairplay::{FairPlay, DEFAULT_DELAY_MOVES};
+use crate::guard;
+use crate::i18n::{tr, tr_args, Locale};
+use crate::interactions;
+use crate::locale;
+use crate::metrics;
+use crate::models::guild_settings::GuildSettings;
+use crate::models::linked_account::LinkedAccount;
+use crate::models::subscription::Subscription;
+use crate::models::user_settings::UserSettings;
+use crate::source::{GameRef, Platform, Player, Target};
+
+/// The message `id` in every language we have a catalog for, keyed by
+/// Discord locale.
+fn translations(id: &str) -> Vec<(&'static str, String)> {
+    Locale::ALL
+        .into_iter()
+        .flat_map(|locale| {
+            locale
+                .discord_locales()
+                .iter()
+                .map(move |tag| (*tag, tr(locale, id)))
+        })
+        .collect()
+}
+
+/// A command described by the message `id`, translated where we can.
+fn command(name: &str, id: &str) -> CreateCommand {
+    translations(id).into_iter().fold(
+        CreateCommand::new(name).description(tr(Locale::English, id)),
+        |command, (tag, description)| command.description_localized(tag, description),
+    )
+}
+
+/// An option described by the message `id`, translated where we can.
+fn option(kind: CommandOptionType, name: &str, id: &str) -> CreateCommandOption {
+    translations(id).into_iter().fold(
+        CreateCommandOption::new(kind, name, tr(Locale::English, id)),
+        |option, (tag, description)| option.description_localized(tag, description),
+    )
+}
+
+/// Registers the bot's slash commands with Discord.
+pub async fn register(ctx: &Context) {
+    let mut language = option(CommandOptionType::String, "language", "option-language")
+        .required(true)
+        .add_string_choice_localized(
+            tr(Locale::English, "language-auto"),
+            "auto",
+            translations("language-auto"),
+        );
+    for locale in Locale::ALL {
+        language = language.add_string_choice(locale.name(), locale.as_str());
+    }
+
+    let mut platform =
+        option(CommandOptionType::String, "platform", "option-platform").required(true);
+    for choice in Platform::ALL {
+        platform = platform.add_string_choice(choice.name(), choice.as_str());
+    }
+
+    let mut fair_play =
+        option(CommandOptionType::String, "mode", "option-fairplay-mode").required(true);
+    for mode in [
+        FairPlay::Block,
+        FairPlay::Delay(DEFAULT_DELAY_MOVES),
+        FairPlay::AfterGame,
+    ] {
+        let id = format!("fairplay-mode-{}", mode.as_str());
+        fair_play = fair_play.add_string_choice_localized(
+            tr(Locale::English, &id),
+            mode.as_str(),
+            translations(&id),
+        );
+    }
+
+    let commands = vec![
+        command("analyze", "command-analyze")
+            .add_option(platform)
+            .add_option(option(
+                CommandOptionType::String,
+                "user",
+                "option-analyze-user",
+            )),
+        command("game", "command-game")
+            .add_option(option(CommandOptionType::String, "game", "option-game").required(true))
+            .add_option(option(CommandOptionType::Integer, "move", "option-move").min_int_value(0)),
+        command("subscribe", "command-subscribe")
+            .default_member_permissions(Permissions::MANAGE_CHANNELS)
+            .add_option(option(CommandOptionType::String, "user", "option-user").required(true))
+            .add_option(option(CommandOptionType::Boolean, "live", "option-live")),
+        command("unsubscribe", "command-unsubscribe")
+            .default_member_permissions(Permissions::MANAGE_CHANNELS)
+            .add_option(option(CommandOptionType::String, "user", "option-user").required(true)),
+        command("link", "command-link"),
+        command("unlink", "command-unlink"),
+        command("fairplay", "command-fairplay")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .add_option(fair_play)
+            .add_option(
+                option(CommandOptionType::Integer, "moves", "option-fairplay-moves")
+                    .min_int_value(1)
+                    .max_int_value(40),
+            ),
+        command("language", "command-language")
+            .add_option(language)
+            .add_option(option(
+                CommandOptionType::Boolean,
+                "server",
+                "option-server",
+            )),
+    ];
+
+    if let Err(why) = Command::set_global_commands(&ctx.http, commands).await {
+        error!("Error registering commands: {why:?}");
+    }
+}
+
+pub async fn handle(ctx: Context, command: CommandInteraction) {
+    metrics::COMMANDS
+        .with_label_values(&[command.data.name.as_str()])
+        .inc();
+
+    // Discord gives up on an answer after three seconds, and the lookups
+    // below can take longer than that when lichess or the database is slow
+    if let Err(why) = command.defer_ephemeral(&ctx.http).await {
+        warn!("Error deferring command: {why:?}");
+        return;
+    }
+
+    let locale = locale::resolve(
+        Some(command.user.id),
+        command.guild_id,
+        Some(&command.locale),
+    );
+
+    // the analysis is posted in the channel, not as a reply
+    match command.data.name.as_str() {
+        "analyze" => return analyze(&ctx, &command, locale).await,
+        "game" => return game(&ctx, &command, locale).await,
+        _ => {}
+    }
+
+    let content = match command.data.name.as_str() {
+        "subscribe" => subscribe(&command, locale),
+        "unsubscribe" => unsubscribe(&command, locale),
+        "language" => language(&command, locale),
+        "link" => link(&command, locale),
+        "unlink" => unlink(&command, locale),
+        "fairplay" => fair_play(&command, locale),
+        name => {
+            warn!("Unhandled command {:?}", name);
+            tr(locale, "command-unsupported")
+        }
+    };
+
+    reply(&ctx, &command, content).await;
+}
+
+/// Fills in the deferred answer to `command`, which only the caller sees.
+async fn reply(ctx: &Context, command: &CommandInteraction, content: String) {
+    let response = EditInteractionResponse::new().content(content);
+    if let Err(why) = command.edit_response(&ctx.http, response).await {
+        warn!("Error responding to command: {why:?}");
+    }
+}
+
+fn string_option(command: &CommandInteraction, name: &str) -> Option<String> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match &option.value {
+            CommandDataOptionValue::String(value) => Some(value.trim().to_string()),
+            _ => None,
+        })
+}
+
+fn int_option(command: &CommandInteraction, name: &str) -> Option<i64> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_i64())
+}
+
+fn bool_option(command: &CommandInteraction, name: &str) -> Option<bool> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_bool())
+}
+
+/// Posts an analysis of a player's game on any platform, like `!help` does
+/// for lichess. Without a user it's the caller's own linked lichess account.
+async fn analyze(ctx: &Context, command: &CommandInteraction, locale: Locale) {
+    let platform = string_option(command, "platform")
+        .and_then(|platform| Platform::parse(&platform))
+        .unwrap_or_default();
+    let user = match string_option(command, "user") {
+        Some(user) if !user.is_empty() => Some(user),
+        _ if platform == Platform::Lichess => {
+            LinkedAccount::find_by_user_id(command.user.id.to_string())
+                .ok()
+                .filter(|linked| linked.verified)
+                .map(|linked| linked.lichess_user)
+        }
+        _ => None,
+    };
+    let user = match user {
+        Some(user) => user,
+        None => return reply(ctx, command, tr(locale, "analyze-missing-user")).await,
+    };
+    Span::current().record("target", user.as_str());
+    let target = Target::Player(Player::new(platform, &user));
+
+    let started = tr_args(
+        locale,
+        "analyze-started",
+        &[("user", user.into()), ("platform", platform.name().into())],
+    );
+    post_requested(ctx, command, target, started, locale).await;
+}
+
+/// Posts an analysis of one lichess game, at the `move` option's ply, the
+/// url's `#ply` anchor or else the latest position.
+async fn game(ctx: &Context, command: &CommandInteraction, locale: Locale) {
+    let game_ref = string_option(command, "game").and_then(|game| GameRef::parse(&game));
+    let mut game_ref = match game_ref {
+        Some(game_ref) => game_ref,
+        None => return reply(ctx, command, tr(locale, "game-invalid")).await,
+    };
+    if let Some(ply) = int_option(command, "move") {
+        game_ref.ply = Some(ply.max(0) as usize);
+    }
+    Span::current().record("target", game_ref.id.as_str());
+
+    let started = tr_args(locale, "game-started", &[("game", game_ref.url().into())]);
+    post_requested(ctx, command, Target::Game(game_ref), started, locale).await;
+}
+
+/// Answers with `started` and posts an analysis of `target`, unless fair
+/// play holds it back, in which case the reply says why.
+async fn post_requested(
+    ctx: &Context,
+    command: &CommandInteraction,
+    target: Target,
+    started: String,
+    locale: Locale,
+) {
+    let (channel_id, user_id, guild_id) = (command.channel_id, command.user.id, command.guild_id);
+    let target = match guard::allowed(ctx, channel_id, user_id, guild_id, target, locale).await {
+        Ok(target) => target,
+        Err(refused) => return reply(ctx, command, refused).await,
+    };
+    reply(ctx, command, started).await;
+
+    let posted = interactions::post_analysis(ctx, channel_id, &target, Some(user_id), locale).await;
+    if let Err(why) = posted {
+        error!("Error posting analysis: {}", why);
+    }
+}
+
+fn subscribe(command: &CommandInteraction, locale: Locale) -> String {
+    let user = match string_option(command, "user") {
+        Some(user) if !user.is_empty() => user,
+        _ => return tr(locale, "subscribe-missing-user"),
+    };
+    Span::current().record("target", user.as_str());
+    let live = bool_option(command, "live").unwrap_or(false);
+    let channel_id = command.channel_id.to_string();
+
+    if Subscription::find_by_channel_and_user(channel_id.clone(), user.clone()).is_ok() {
+        return tr_args(locale, "subscribe-exists", &[("user", user.into())]);
+    }
+
+    match Subscription::insert(Subscription::new(channel_id, user.clone(), live)) {
+        Ok(_) => tr_args(locale, "subscribe-done", &[("user", user.into())]),
+        Err(why) => {
+            error!("Error saving subscription: {why:?}");
+            tr(locale, "subscribe-failed")
+        }
+    }
+}
+
+fn unsubscribe(command: &CommandInteraction, locale: Locale) -> String {
+    let user = match string_option(command, "user") {
+        Some(user) if !user.is_empty() => user,
+        _ => return tr(locale, "subscribe-missing-user"),
+    };
+    Span::current().record("target", user.as_str());
+
+    let subscription =
+        Subscription::find_by_channel_and_user(command.channel_id.to_string(), user.clone());
+
+    match subscription {
+        Ok(subscription) => match subscription.delete() {
+            Ok(_) => tr_args(locale, "unsubscribe-done", &[("user", user.into())]),
+            Err(why) => {
+                error!("Error deleting subscription: {why:?}");
+                tr(locale, "unsubscribe-failed")
+            }
+        },
+        Err(_) => tr_args(locale, "unsubscribe-missing", &[("user", user.into())]),
+    }
+}
+
+fn can_manage_server(command: &CommandInteraction) -> bool {
+    command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_guild())
+}
+
+/// Sends the caller to sign in with lichess, which tells us which account
+/// is theirs, so fair play can tell their own games apart and `/analyze`
+/// knows who they mean without a name.
+fn link(command: &CommandInteraction, locale: Locale) -> String {
+    match crate::link::start(command.user.id, locale) {
+        Some(url) => tr_args(locale, "link-start", &[("url", url.into())]),
+        None => tr(locale, "link-unavailable"),
+    }
+}
+
+fn unlink(command: &CommandInteraction, locale: Locale) -> String {
+    match LinkedAccount::find_by_user_id(command.user.id.to_string()) {
+        Ok(linked) => match linked.delete() {
+            Ok(_) => tr(locale, "unlink-done"),
+            Err(why) => {
+                error!("Error deleting linked account: {why:?}");
+                tr(locale, "link-failed")
+            }
+        },
+        Err(_) => tr(locale, "unlink-missing"),
+    }
+}
+
+/// Sets what the server's members see of their own games in progress, which
+/// takes Manage Server.
+fn fair_play(command: &CommandInteraction, locale: Locale) -> String {
+    let guild_id = match command.guild_id {
+        Some(guild_id) => guild_id,
+        None => return tr(locale, "fairplay-not-in-server"),
+    };
+    if !can_manage_server(command) {
+        return tr(locale, "fairplay-needs-manage-server");
+    }
+
+    let moves = int_option(command, "moves").unwrap_or(DEFAULT_DELAY_MOVES as i64);
+    let mode = FairPlay::from_settings(
+        &string_option(command, "mode").unwrap_or_default(),
+        moves.clamp(0, i32::MAX as i64) as i32,
+    );
+
+    if let Err(why) = GuildSettings::set_fair_play(guild_id.to_string(), mode) {
+        error!("Error saving fair play: {why:?}");
+        return tr(locale, "fairplay-failed");
+    }
+
+    match mode {
+        FairPlay::Block => tr(locale, "fairplay-set-block"),
+        FairPlay::Delay(moves) => tr_args(locale, "fairplay-set-delay", &[("moves", moves.into())]),
+        FairPlay::AfterGame => tr(locale, "fairplay-set-after-game"),
+    }
+}
+
+/// Sets or clears the caller's language, or with `server` the server's,
+/// which takes Manage Server. "auto" clears it.
+fn language(command: &CommandInteraction, locale: Locale) -> String {
+    let chosen = string_option(command, "language").and_then(|tag| Locale::from_tag(&tag));
+    let server = bool_option(command, "server").unwrap_or(false);
+
+    let saved = if server {
+        let guild_id = match command.guild_id {
+            Some(guild_id) => guild_id,
+            None => return tr(locale, "language-not-in-server"),
+        };
+        if !can_manage_server(command) {
+            return tr(locale, "language-needs-manage-server");
+        }
+        GuildSettings::set_locale(guild_id.to_string(), chosen)
+    } else {
+        UserSettings::set_locale(command.user.id.to_string(), chosen)
+    };
+
+    if let Err(why) = saved {
+        error!("Error saving language: {why:?}");
+        return tr(locale, "language-failed");
+    }
+
+    // answer in the language that's now in effect
+    match (chosen, server) {
+        (Some(chosen), true) => tr(chosen, "language-set-server"),
+        (Some(chosen), false) => tr(chosen, "language-set-user"),
+        (None, server) => {
+            let locale = locale::resolve(
+                Some(command.user.id),
+                command.guild_id,
+                Some(&command.locale),
+            );
+            let id = if server {
+                "language-cleared-server"
+            } else {
+                "language-cleared-user"
+            };
+            tr(locale, id)
+        }
+    }
+}
@@ -1,10 +1,45 @@
 use std::env;
+use std::sync::Mutex;
 
-use diesel::{Connection, MysqlConnection};
+use diesel::result::{ConnectionResult, Error};
+use diesel::{Connection, MysqlConnection, RunQueryDsl};
 use dotenv::dotenv;
+
+use crate::metrics;
+
 pub fn get_dbo() -> MysqlConnection {
+    try_get_dbo().unwrap_or_else(|why| panic!("Error connecting to the database: {why}"))
+}
+
+pub fn try_get_dbo() -> ConnectionResult<MysqlConnection> {
     dotenv().ok();
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    MysqlConnection::establish(&database_url)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+    MysqlConnection::establish(&database_url).inspect_err(|_| metrics::DB_ERRORS.inc())
+}
+
+/// The connection `ping` keeps between probes.
+static PING_CONNECTION: Mutex<Option<MysqlConnection>> = Mutex::new(None);
+
+/// Whether the database answers right now. One connection is kept for
+/// this and only replaced when it stops answering, so frequent probes
+/// don't open a connection each.
+pub fn ping() -> bool {
+    let mut connection = PING_CONNECTION.lock().unwrap_or_else(|e| e.into_inner());
+    if connection.is_none() {
+        *connection = try_get_dbo().ok();
+    }
+    let answered = connection
+        .as_mut()
+        .is_some_and(|conn| diesel::sql_query("SELECT 1").execute(conn).is_ok());
+    if !answered {
+        *connection = None;
+    }
+    answered
+}
+
+/// Counts a failed query. A missing row is an answer, not a failure.
+pub fn db_error(why: &Error) {
+    if !matches!(why, Error::NotFound) {
+        metrics::DB_ERRORS.inc();
+    }
 }
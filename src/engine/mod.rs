@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::env;
+use std::process::Stdio;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{ChildStdout, Command};
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+use tracing::{instrument, warn};
+
+use crate::board::{get_stock_fish, StockfishResponse};
+use crate::metrics;
+use crate::models::error::Error;
+use crate::position::Variant;
+
+/// stockfish.online rejects anything deeper than this.
+pub const ONLINE_MAX_DEPTH: u8 = 15;
+
+/// Deepest search we'll ask of a local engine.
+pub const LOCAL_MAX_DEPTH: u8 = 30;
+
+/// Depths "Analyze deeper" steps through, one embed edit per step, as far
+/// as the engine goes.
+const DEEPER_DEPTHS: [u8; 3] = [20, 25, 30];
+
+/// Seconds a local search may take before we stop it.
+const DEFAULT_ENGINE_TIMEOUT_SECS: u64 = 60;
+
+/// How long a stopped engine has to come back with its best move so far.
+const STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// Searches kept in `CACHE` before it starts over.
+const CACHE_SIZE: usize = 1024;
+
+/// Searches we've already run, by FEN, variant and depth, so stepping back
+/// and forth through a game or refreshing a position that hasn't changed is
+/// instant.
+type Cache = HashMap<(String, Variant, u8), StockfishResponse>;
+static CACHE: LazyLock<std::sync::Mutex<Cache>> =
+    LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// Path to a local UCI engine, if one is configured.
+fn local_engine_path() -> Option<String> {
+    env::var("STOCKFISH_PATH").ok().filter(|path| !path.is_empty())
+}
+
+/// Whether analyses come from stockfish.online, which stops at
+/// `ONLINE_MAX_DEPTH`, the same depth a plain analysis already runs at.
+pub fn is_online() -> bool {
+    local_engine_path().is_none()
+}
+
+/// The deepest search the configured engine supports.
+pub fn max_depth() -> u8 {
+    match local_engine_path() {
+        Some(_) => LOCAL_MAX_DEPTH,
+        None => ONLINE_MAX_DEPTH,
+    }
+}
+
+/// The depths "Analyze deeper" steps through from `depth`, ending at the
+/// deepest the engine goes. Empty when it's there already.
+pub fn deeper_depths(depth: u8) -> Vec<u8> {
+    steps_between(depth, max_depth())
+}
+
+fn steps_between(depth: u8, max: u8) -> Vec<u8> {
+    let mut depths: Vec<u8> = DEEPER_DEPTHS
+        .into_iter()
+        .filter(|step| *step > depth && *step < max)
+        .collect();
+    if max > depth {
+        depths.push(max);
+    }
+    depths
+}
+
+/// The engine analyses come from, as shown to users.
+pub fn name() -> &'static str {
+    match local_engine_path() {
+        Some(_) => "Stockfish",
+        None => "stockfish.online",
+    }
+}
+
+/// Analyses `fen` to `depth`, using the local engine when `STOCKFISH_PATH` is
+/// set and stockfish.online otherwise. Evaluations are from white's side.
+pub async fn analyse(fen: &str, depth: u8) -> Result<StockfishResponse, Error> {
+    analyse_in(fen, depth, Variant::Standard).await
+}
+
+/// `analyse` for a position of `variant`. Only a local engine that knows
+/// the variant, like Fairy-Stockfish, can do these; stockfish.online plays
+/// standard chess only, so anything else is refused.
+#[instrument(name = "engine", skip(fen))]
+pub async fn analyse_in(
+    fen: &str,
+    depth: u8,
+    variant: Variant,
+) -> Result<StockfishResponse, Error> {
+    let depth = depth.min(max_depth());
+    let key = (fen.to_string(), variant, depth);
+    let cached = CACHE.lock().unwrap().get(&key).cloned();
+    metrics::cache_lookup("engine", cached.is_some());
+    if let Some(cached) = cached {
+        return Ok(cached);
+    }
+    metrics::ENGINE_DEPTH.observe(depth as f64);
+
+    let resp = match local_engine_path() {
+        Some(path) => {
+            let _timer = metrics::ENGINE_SECONDS
+                .with_label_values(&["local"])
+                .start_timer();
+            analyse_local(&path, fen, depth, variant, engine_timeout()).await
+        }
+        None if variant != Variant::Standard => {
+            return Err(Error::Invalid(format!(
+                "{} needs a local engine that plays it, set STOCKFISH_PATH",
+                variant.name()
+            )));
+        }
+        None => {
+            let _timer = metrics::ENGINE_SECONDS
+                .with_label_values(&["online"])
+                .start_timer();
+            let url = format!(
+                "https://stockfish.online/api/s/v2.php?fen={}&depth={}",
+                fen, depth
+            );
+            let url = Arc::new(Mutex::new(url.as_str()));
+            get_stock_fish(url).await
+        }
+    }?;
+
+    if resp.success {
+        let mut cache = CACHE.lock().unwrap();
+        if cache.len() >= CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(key, resp.clone());
+    }
+    Ok(resp)
+}
+
+async fn analyse_local(
+    path: &str,
+    fen: &str,
+    depth: u8,
+    variant: Variant,
+    limit: Duration,
+) -> Result<StockfishResponse, Error> {
+    let mut child = Command::new(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| Error::BasicError(format!("Error starting engine {:?}", e)))?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let mut lines = BufReader::new(stdout).lines();
+
+    let mut options = String::new();
+    if variant != Variant::Standard {
+        options.push_str(&format!(
+            "setoption name UCI_Variant value {}\n",
+            variant.uci_variant()
+        ));
+    }
+    if variant == Variant::Chess960 {
+        options.push_str("setoption name UCI_Chess960 value true\n");
+    }
+    let commands = format!(
+        "uci\n{}isready\nposition fen {}\ngo depth {}\n",
+        options, fen, depth
+    );
+    stdin
+        .write_all(commands.as_bytes())
+        .await
+        .map_err(|e| Error::BasicError(format!("Error writing to engine {:?}", e)))?;
+
+    let mut search = Search {
+        black_to_move: fen.split_whitespace().nth(1) == Some("b"),
+        ..Search::default()
+    };
+
+    let bestmove = match timeout(limit, search.read(&mut lines)).await {
+        Ok(bestmove) => bestmove?,
+        Err(_) => {
+            // settle for what it found so far
+            warn!("Engine took longer than {:?}, stopping it", limit);
+            stdin
+                .write_all(b"stop\n")
+                .await
+                .map_err(|e| Error::BasicError(format!("Error writing to engine {:?}", e)))?;
+            timeout(STOP_GRACE, search.read(&mut lines))
+                .await
+                .map_err(|_| Error::BasicError("Engine didn't stop".to_string()))??
+        }
+    };
+
+    let _ = stdin.write_all(b"quit\n").await;
+    Ok(StockfishResponse {
+        success: true,
+        evaluation: search.evaluation,
+        mate: search.mate,
+        bestmove,
+        continuation: search.continuation,
+    })
+}
+
+/// How long a local search may take, unless `ENGINE_TIMEOUT_SECS` says
+/// otherwise.
+fn engine_timeout() -> Duration {
+    let secs = env::var("ENGINE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_ENGINE_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// What a local engine has reported so far.
+#[derive(Debug, Default)]
+struct Search {
+    black_to_move: bool,
+    evaluation: Option<f32>,
+    mate: Option<i32>,
+    continuation: String,
+}
+
+impl Search {
+    /// Reads engine output until its `bestmove` line, which it returns.
+    async fn read(&mut self, lines: &mut Lines<BufReader<ChildStdout>>) -> Result<String, Error> {
+        loop {
+            let line = lines
+                .next_line()
+                .await
+                .map_err(|e| Error::BasicError(format!("Error reading from engine {:?}", e)))?;
+
+            let line = match line {
+                Some(line) => line,
+                None => return Err(Error::BasicError("Engine exited early".to_string())),
+            };
+
+            if line.starts_with("info") && line.contains(" pv ") {
+                self.info(&line);
+            }
+            if line.starts_with("bestmove") {
+                return Ok(line);
+            }
+        }
+    }
+
+    fn info(&mut self, line: &str) {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        // uci scores are from the side to move, stockfish.online reports white's side
+        let sign = if self.black_to_move { -1.0 } else { 1.0 };
+
+        if let Some(i) = parts.iter().position(|p| *p == "score") {
+            match (parts.get(i + 1), parts.get(i + 2).map(|v| v.parse::<i32>())) {
+                (Some(&"cp"), Some(Ok(cp))) => {
+                    self.evaluation = Some(sign * cp as f32 / 100.0);
+                    self.mate = None;
+                }
+                (Some(&"mate"), Some(Ok(moves))) => {
+                    self.evaluation = None;
+                    self.mate = Some(sign as i32 * moves);
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(i) = parts.iter().position(|p| *p == "pv") {
+            self.continuation = parts[i + 1..].join(" ");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn online_steps_up_to_its_limit() {
+        assert_eq!(steps_between(12, ONLINE_MAX_DEPTH), [15]);
+        assert!(steps_between(15, ONLINE_MAX_DEPTH).is_empty());
+    }
+
+    #[test]
+    fn local_steps_through_every_depth() {
+        assert_eq!(steps_between(15, LOCAL_MAX_DEPTH), [20, 25, 30]);
+        assert_eq!(steps_between(22, LOCAL_MAX_DEPTH), [25, 30]);
+        assert!(steps_between(30, LOCAL_MAX_DEPTH).is_empty());
+    }
+
+    /// An engine that never finishes a search on its own.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn slow_engine_is_stopped() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = "#!/bin/sh
+while read cmd; do
+  case \"$cmd\" in
+    go*) echo 'info depth 1 score cp 35 pv e2e4 e7e5' ;;
+    stop) echo 'bestmove e2e4 ponder e7e5' ;;
+    quit) exit 0 ;;
+  esac
+done
+";
+        let path = env::temp_dir().join(format!("slow-engine-{}", std::process::id()));
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let limit = Duration::from_millis(200);
+        let resp = analyse_local(path.to_str().unwrap(), fen, 30, Variant::Standard, limit).await;
+        let _ = std::fs::remove_file(&path);
+
+        let resp = resp.unwrap();
+        assert_eq!(resp.bestmove, "bestmove e2e4 ponder e7e5");
+        assert_eq!(resp.evaluation, Some(0.35));
+        assert_eq!(resp.continuation, "e2e4 e7e5");
+    }
+}
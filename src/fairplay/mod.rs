@@ -0,0 +1,199 @@
+use shakmaty::Color;
+
+use crate::models::error::Error;
+use crate::source::{Game, GameSource, Lichess, Platform, Target};
+
+/// Moves behind a delayed server shows its members' own games, unless it
+/// picked a number.
+pub const DEFAULT_DELAY_MOVES: u8 = 5;
+
+/// What a server lets members see of their own games while they're still
+/// playing them. lichess doesn't allow engine help during a game, so every
+/// mode holds something back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FairPlay {
+    /// Refuse to analyse it.
+    #[default]
+    Block,
+    /// Analyse the position this many moves back.
+    Delay(u8),
+    /// Wait until the game is over and post the analysis then.
+    AfterGame,
+}
+
+impl FairPlay {
+    /// The mode as stored in guild settings, "block", "delay" or "after-game".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FairPlay::Block => "block",
+            FairPlay::Delay(_) => "delay",
+            FairPlay::AfterGame => "after-game",
+        }
+    }
+
+    /// Reads a stored mode, blocking when it's one we don't know.
+    pub fn from_settings(mode: &str, moves: i32) -> FairPlay {
+        match mode {
+            "delay" => FairPlay::Delay(moves.clamp(0, u8::MAX as i32) as u8),
+            "after-game" => FairPlay::AfterGame,
+            _ => FairPlay::Block,
+        }
+    }
+
+    /// The ply that may be shown of a game in progress that's `plies` long,
+    /// or `None` if nothing may be shown yet.
+    pub fn visible_ply(&self, plies: usize) -> Option<usize> {
+        match self {
+            FairPlay::Delay(moves) => Some(plies.saturating_sub(*moves as usize * 2)),
+            FairPlay::Block | FairPlay::AfterGame => None,
+        }
+    }
+
+    /// Whether `ply` of a game in progress that's `plies` long may be shown,
+    /// `None` being the latest position.
+    pub fn allows(&self, ply: Option<usize>, plies: usize) -> bool {
+        let ply = ply.unwrap_or(plies).min(plies);
+        self.visible_ply(plies)
+            .is_some_and(|visible| ply <= visible)
+    }
+}
+
+/// `linked`'s lichess game in progress and their side in it, when `target`
+/// shows it. A player target shows whatever game they're in, so asking for
+/// your opponent finds your own game just the same; `source` looks them up.
+pub async fn own_game(
+    source: &impl GameSource,
+    linked: &str,
+    target: &Target,
+) -> Result<Option<(Game, Color)>, Error> {
+    let game = match target {
+        Target::Player(player) if player.platform == Platform::Lichess => {
+            source.current_game(&player.name).await?
+        }
+        Target::Player(_) => None,
+        Target::Game(game) => Lichess.game(&game.id).await?,
+    };
+    Ok(game.and_then(|game| match game.color_of(linked) {
+        Some(color) if !game.finished => Some((game, color)),
+        _ => None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use async_trait::async_trait;
+
+    use crate::source::Player;
+
+    /// Knows one game, and who's playing it.
+    struct FakeSource(Game);
+
+    #[async_trait]
+    impl GameSource for FakeSource {
+        async fn current_games(&self, _users: &[String]) -> Result<HashMap<String, String>, Error> {
+            Ok(HashMap::new())
+        }
+
+        async fn current_game(&self, user: &str) -> Result<Option<Game>, Error> {
+            Ok(Some(self.0.clone()).filter(|game| game.color_of(user).is_some()))
+        }
+    }
+
+    fn game(finished: bool) -> Game {
+        Game {
+            id: "abcdefgh".to_string(),
+            url: "https://lichess.org/abcdefgh".to_string(),
+            white: "Alice".to_string(),
+            black: "Bob".to_string(),
+            pgn: "1. e4 e5".to_string(),
+            fen: None,
+            finished,
+            rules: "standard".to_string(),
+        }
+    }
+
+    fn player(name: &str, platform: Platform) -> Target {
+        Target::Player(Player::new(platform, name))
+    }
+
+    #[tokio::test]
+    async fn own_game_found_by_own_name() {
+        let source = FakeSource(game(false));
+        let found = own_game(&source, "alice", &player("Alice", Platform::Lichess))
+            .await
+            .unwrap();
+        assert_eq!(
+            found.map(|(game, color)| (game.id, color)),
+            Some(("abcdefgh".to_string(), Color::White))
+        );
+    }
+
+    #[tokio::test]
+    async fn own_game_found_by_opponents_name() {
+        let source = FakeSource(game(false));
+        let found = own_game(&source, "alice", &player("bob", Platform::Lichess))
+            .await
+            .unwrap();
+        assert_eq!(found.map(|(_, color)| color), Some(Color::White));
+    }
+
+    #[tokio::test]
+    async fn other_and_finished_games_arent_own() {
+        let (playing, finished) = (FakeSource(game(false)), FakeSource(game(true)));
+        let bob = player("bob", Platform::Lichess);
+
+        assert!(own_game(&playing, "carol", &bob).await.unwrap().is_none());
+        assert!(own_game(&finished, "alice", &bob).await.unwrap().is_none());
+
+        let bob = player("bob", Platform::ChessCom);
+        assert!(own_game(&playing, "alice", &bob).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn delay_shows_moves_far_enough_back() {
+        let mode = FairPlay::Delay(5);
+        assert_eq!(mode.visible_ply(30), Some(20));
+        assert!(mode.allows(Some(0), 30));
+        assert!(mode.allows(Some(20), 30));
+        assert_eq!(mode.visible_ply(6), Some(0));
+    }
+
+    #[test]
+    fn delay_hides_recent_and_live_positions() {
+        let mode = FairPlay::Delay(5);
+        // one step on from the last visible ply, as the next button does
+        assert!(!mode.allows(Some(21), 30));
+        assert!(!mode.allows(Some(30), 30));
+        assert!(!mode.allows(None, 30));
+    }
+
+    #[test]
+    fn no_delay_shows_everything() {
+        let mode = FairPlay::Delay(0);
+        assert!(mode.allows(None, 30));
+        assert!(mode.allows(Some(30), 30));
+    }
+
+    #[test]
+    fn block_and_after_game_show_nothing() {
+        for mode in [FairPlay::Block, FairPlay::AfterGame] {
+            assert_eq!(mode.visible_ply(30), None);
+            assert!(!mode.allows(Some(0), 30));
+            assert!(!mode.allows(None, 30));
+        }
+    }
+
+    #[test]
+    fn unknown_settings_block() {
+        assert_eq!(FairPlay::from_settings("delay", 3), FairPlay::Delay(3));
+        assert_eq!(
+            FairPlay::from_settings("after-game", 0),
+            FairPlay::AfterGame
+        );
+        assert_eq!(FairPlay::from_settings("anything", 3), FairPlay::Block);
+    }
+}
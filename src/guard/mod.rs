@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use serenity::all::{ChannelId, Context, GuildId, UserId};
+use tracing::{error, info, warn, Instrument};
+
+use crate::fairplay::{own_game, FairPlay};
+use crate::i18n::{tr, Locale};
+use crate::interactions::post_analysis;
+use crate::models::guild_settings::GuildSettings;
+use crate::models::linked_account::LinkedAccount;
+use crate::position;
+use crate::shutdown;
+use crate::source::{GameRef, Lichess, Target};
+
+/// How often a game held back until it's over is checked on.
+const AFTER_GAME_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Checks before we stop waiting for a game to end (six hours).
+const AFTER_GAME_MAX_CHECKS: u32 = 360;
+
+/// What fair play allows someone to see of a target.
+#[derive(Debug)]
+pub enum Verdict {
+    /// It isn't their own game in progress.
+    Allow,
+    /// It is, and their server shows it this far behind instead.
+    Delay(Target),
+    /// It is, and their server waits until it's over.
+    AfterGame(GameRef),
+    /// It is and their server refuses, or we couldn't tell. Holds the
+    /// catalog id of the reply.
+    Refuse(&'static str),
+}
+
+/// Checks `user_id` asking about `target` against lichess's fair-play rules:
+/// analysing your own game while it's being played is engine help, so the
+/// requester's linked account decides whether it's theirs and their server's
+/// setting what happens then.
+pub async fn check(user_id: UserId, guild_id: Option<GuildId>, target: &Target) -> Verdict {
+    let linked = match LinkedAccount::find_by_user_id(user_id.to_string()) {
+        Ok(linked) => linked.lichess_user,
+        Err(_) => return Verdict::Allow,
+    };
+
+    let (game, color) = match own_game(&Lichess, &linked, target).await {
+        Ok(Some(own)) => own,
+        Ok(None) => return Verdict::Allow,
+        Err(why) => {
+            warn!("Couldn't check fair play: {}", why);
+            return Verdict::Refuse("fair-play-unchecked");
+        }
+    };
+
+    let mode = guild_id
+        .and_then(|guild_id| GuildSettings::find_by_guild_id(guild_id.to_string()).ok())
+        .map(|settings| settings.fair_play())
+        .unwrap_or_default();
+    info!(
+        "{} asked about their own game {}, {}",
+        linked,
+        game.id,
+        mode.as_str()
+    );
+
+    match mode {
+        FairPlay::Block => Verdict::Refuse("fair-play-refused"),
+        FairPlay::AfterGame => Verdict::AfterGame(GameRef {
+            id: game.id,
+            ply: None,
+            orientation: color,
+        }),
+        FairPlay::Delay(_) => {
+            let plies = position::pgn_sans(&game.pgn).len();
+            // a move far enough back is fine as it is
+            if let Target::Game(GameRef { ply, .. }) = target {
+                if mode.allows(*ply, plies) {
+                    return Verdict::Allow;
+                }
+            }
+            Verdict::Delay(Target::Game(GameRef {
+                id: game.id,
+                ply: mode.visible_ply(plies),
+                orientation: color,
+            }))
+        }
+    }
+}
+
+/// Applies `check` to a request for a new analysis: the target to post, or
+/// what to tell the requester instead. Games held back until they're over
+/// are posted to `channel_id` when they are.
+pub async fn allowed(
+    ctx: &Context,
+    channel_id: ChannelId,
+    user_id: UserId,
+    guild_id: Option<GuildId>,
+    target: Target,
+    locale: Locale,
+) -> Result<Target, String> {
+    match check(user_id, guild_id, &target).await {
+        Verdict::Allow => Ok(target),
+        Verdict::Delay(delayed) => Ok(delayed),
+        Verdict::AfterGame(game) => {
+            post_after_game(ctx.clone(), channel_id, game, locale);
+            Err(tr(locale, "fair-play-after-game"))
+        }
+        Verdict::Refuse(id) => Err(tr(locale, id)),
+    }
+}
+
+/// Applies `allowed` to a post nobody asked for, like a subscription's, of
+/// `lichess_user`'s games: it's whoever linked that account who mustn't be
+/// helped.
+pub async fn allowed_for_player(
+    ctx: &Context,
+    channel_id: ChannelId,
+    guild_id: Option<GuildId>,
+    lichess_user: &str,
+    target: Target,
+    locale: Locale,
+) -> Result<Target, String> {
+    let user_id = LinkedAccount::find_by_lichess_user(lichess_user.to_string())
+        .ok()
+        .and_then(|linked| linked.user_id.parse::<u64>().ok())
+        .map(UserId::new);
+    match user_id {
+        Some(user_id) => allowed(ctx, channel_id, user_id, guild_id, target, locale).await,
+        None => Ok(target),
+    }
+}
+
+/// Posts an analysis of `game` to `channel_id` once lichess says it's over.
+fn post_after_game(ctx: Context, channel_id: ChannelId, game: GameRef, locale: Locale) {
+    tokio::spawn(
+        async move {
+            if !wait_until_over(&game.id).await {
+                return;
+            }
+            // posted for the channel once the game is over, so it's the
+            // moderators' to delete
+            let target = Target::Game(game);
+            if let Err(why) = post_analysis(&ctx, channel_id, &target, None, locale).await {
+                error!("Error posting analysis: {}", why);
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+/// Checks on lichess game `game_id` until it's over. False if we gave up
+/// or are shutting down first.
+pub async fn wait_until_over(game_id: &str) -> bool {
+    for _ in 0..AFTER_GAME_MAX_CHECKS {
+        if !shutdown::sleep(AFTER_GAME_INTERVAL).await {
+            return false;
+        }
+        match Lichess.game(game_id).await {
+            Ok(Some(found)) if found.finished => return true,
+            Ok(_) => {}
+            Err(why) => warn!("Error checking game {}: {}", game_id, why),
+        }
+    }
+    info!("Stopped waiting for game {} to end", game_id);
+    false
+}
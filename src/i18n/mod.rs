@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::LazyLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+/// The languages there's a catalog for in `locales/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+    Spanish,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 3] = [Locale::English, Locale::German, Locale::Spanish];
+
+    /// The tag we store, which is also the catalog's directory.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::English => "en-US",
+            Locale::German => "de",
+            Locale::Spanish => "es-ES",
+        }
+    }
+
+    /// The language's name in itself, for the `/language` choices.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Locale::English => "English",
+            Locale::German => "Deutsch",
+            Locale::Spanish => "Español",
+        }
+    }
+
+    /// Matches a stored tag or a Discord locale on its language, so "en-GB"
+    /// and "es-419" are covered too.
+    pub fn from_tag(tag: &str) -> Option<Locale> {
+        let language = tag.split(['-', '_']).next()?.to_lowercase();
+        match language.as_str() {
+            "en" => Some(Locale::English),
+            "de" => Some(Locale::German),
+            "es" => Some(Locale::Spanish),
+            _ => None,
+        }
+    }
+
+    /// The Discord locales command descriptions are translated for. English
+    /// is what Discord shows when there's no translation.
+    pub fn discord_locales(&self) -> &'static [&'static str] {
+        match self {
+            Locale::English => &[],
+            Locale::German => &["de"],
+            Locale::Spanish => &["es-ES", "es-419"],
+        }
+    }
+
+    fn catalog(&self) -> &'static str {
+        match self {
+            Locale::English => include_str!("../../locales/en-US/bot.ftl"),
+            Locale::German => include_str!("../../locales/de/bot.ftl"),
+            Locale::Spanish => include_str!("../../locales/es-ES/bot.ftl"),
+        }
+    }
+}
+
+/// The locale used when nobody picked one and Discord didn't tell us, from
+/// `DEFAULT_LOCALE`, English otherwise.
+pub fn default_locale() -> Locale {
+    env::var("DEFAULT_LOCALE")
+        .ok()
+        .and_then(|tag| Locale::from_tag(&tag))
+        .unwrap_or_default()
+}
+
+static BUNDLES: LazyLock<HashMap<Locale, FluentBundle<FluentResource>>> =
+    LazyLock::new(|| Locale::ALL.into_iter().map(|l| (l, bundle(l))).collect());
+
+fn bundle(locale: Locale) -> FluentBundle<FluentResource> {
+    let id: LanguageIdentifier = locale.as_str().parse().unwrap();
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // Discord shows the isolation marks fluent puts around arguments
+    bundle.set_use_isolating(false);
+
+    let resource =
+        FluentResource::try_new(locale.catalog().to_string()).unwrap_or_else(|(_, errors)| {
+            panic!("Error parsing the {} catalog: {errors:?}", locale.as_str())
+        });
+    bundle.add_resource(resource).unwrap_or_else(|errors| {
+        panic!("Error loading the {} catalog: {errors:?}", locale.as_str())
+    });
+    bundle
+}
+
+fn format(locale: Locale, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+    let bundle = BUNDLES.get(&locale)?;
+    let pattern = bundle.get_message(id)?.value()?;
+
+    let mut errors = vec![];
+    let text = bundle.format_pattern(pattern, args, &mut errors);
+    if !errors.is_empty() {
+        warn!(
+            "Error formatting {:?} in {}: {errors:?}",
+            id,
+            locale.as_str()
+        );
+    }
+    Some(text.into_owned())
+}
+
+/// The message `id` in `locale`, falling back to English and then to the id
+/// itself so a missing translation never loses the reply.
+pub fn tr(locale: Locale, id: &str) -> String {
+    tr_args(locale, id, &[])
+}
+
+/// `tr` with the message's `{ $name }` placeholders filled from `args`.
+pub fn tr_args(locale: Locale, id: &str, args: &[(&str, FluentValue)]) -> String {
+    let args = (!args.is_empty()).then(|| {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, value.clone());
+        }
+        fluent_args
+    });
+
+    format(locale, id, args.as_ref())
+        .or_else(|| {
+            warn!("{:?} is missing from the {} catalog", id, locale.as_str());
+            format(Locale::English, id, args.as_ref())
+        })
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// The letters SAN uses for king, queen, rook, bishop and knight in `locale`.
+pub fn piece_letters(locale: Locale) -> [char; 5] {
+    let mut letters = ['K', 'Q', 'R', 'B', 'N'];
+    for (letter, localized) in letters.iter_mut().zip(tr(locale, "piece-letters").chars()) {
+        *letter = localized;
+    }
+    letters
+}
@@ -0,0 +1,1079 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serenity::all::{
+    ButtonStyle, ChannelId, ComponentInteraction, Context, CreateActionRow, CreateAttachment,
+    CreateButton, CreateInteractionResponse, CreateInteractionResponseFollowup,
+    CreateInteractionResponseMessage, CreateMessage, EditMessage, Interaction, MessageId,
+    Permissions, UserId,
+};
+use serenity::prelude::TypeMapKey;
+use tokio::sync::Mutex;
+use tracing::{debug, error, field, info, info_span, warn, Instrument, Span};
+
+use crate::commands;
+use crate::report;
+use crate::shutdown;
+use crate::source::{GameRef, GameSource, Lichess, Platform, Target};
+use crate::board::{
+    decode_fen, evaluate, help_target, opposite, GetStockFishResponse, DEFAULT_DEPTH,
+};
+use crate::engine;
+use crate::guard::{self, Verdict};
+use crate::i18n::{tr, Locale};
+use crate::locale;
+use crate::logging;
+use crate::metrics;
+use crate::position;
+use crate::models::analysis::Analysis;
+use crate::models::game_view::GameView;
+use crate::models::error::Error;
+use crate::view::AnalysisView;
+
+/// How often a tracked game is refreshed.
+const TRACK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Upper bound on refreshes for one tracked game (about an hour).
+const TRACK_MAX_REFRESHES: u32 = 120;
+
+/// Failed refreshes in a row it takes to wait the longest between tries,
+/// which doubles `TRACK_INTERVAL` for each.
+const TRACK_MAX_BACKOFF: u32 = 3;
+
+/// Deep analyses allowed to run at once in one guild, unless
+/// `DEEP_ANALYSIS_PER_GUILD` says otherwise.
+const DEFAULT_DEEP_ANALYSIS_PER_GUILD: usize = 1;
+
+/// Custom id used by every button before actions were introduced.
+const LEGACY_CUSTOM_ID: &str = "testButton";
+
+/// Messages that currently have a tracking loop running.
+pub struct TrackedGames;
+
+impl TypeMapKey for TrackedGames {
+    type Value = Arc<Mutex<HashSet<MessageId>>>;
+}
+
+/// Running deep analyses per guild (or per channel for DMs).
+pub struct DeepAnalyses;
+
+impl TypeMapKey for DeepAnalyses {
+    type Value = Arc<std::sync::Mutex<HashMap<u64, usize>>>;
+}
+
+/// Holds one of a guild's deep analysis slots until dropped.
+pub(crate) struct DeepAnalysisSlot {
+    running: Arc<std::sync::Mutex<HashMap<u64, usize>>>,
+    key: u64,
+}
+
+impl DeepAnalysisSlot {
+    pub(crate) fn acquire(
+        running: Arc<std::sync::Mutex<HashMap<u64, usize>>>,
+        key: u64,
+    ) -> Option<Self> {
+        let cap = env::var("DEEP_ANALYSIS_PER_GUILD")
+            .ok()
+            .and_then(|cap| cap.parse().ok())
+            .unwrap_or(DEFAULT_DEEP_ANALYSIS_PER_GUILD);
+
+        {
+            let mut guard = running.lock().unwrap();
+            let count = guard.entry(key).or_insert(0);
+            if *count >= cap {
+                return None;
+            }
+            *count += 1;
+        }
+
+        Some(Self { running, key })
+    }
+}
+
+impl Drop for DeepAnalysisSlot {
+    fn drop(&mut self) {
+        let mut guard = self.running.lock().unwrap();
+        if let Some(count) = guard.get_mut(&self.key) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Refresh,
+    Flip,
+    Deeper,
+    Lines,
+    Threats,
+    Track,
+    Delete,
+    First,
+    Previous,
+    Next,
+    Last,
+}
+
+impl Action {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Action::Refresh => "refresh",
+            Action::Flip => "flip",
+            Action::Deeper => "deeper",
+            Action::Lines => "lines",
+            Action::Threats => "threats",
+            Action::Track => "track",
+            Action::Delete => "delete",
+            Action::First => "first",
+            Action::Previous => "previous",
+            Action::Next => "next",
+            Action::Last => "last",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Action> {
+        match s {
+            "refresh" => Some(Action::Refresh),
+            "flip" => Some(Action::Flip),
+            "deeper" => Some(Action::Deeper),
+            "lines" => Some(Action::Lines),
+            "threats" => Some(Action::Threats),
+            "track" => Some(Action::Track),
+            "delete" => Some(Action::Delete),
+            "first" => Some(Action::First),
+            "previous" => Some(Action::Previous),
+            "next" => Some(Action::Next),
+            "last" => Some(Action::Last),
+            _ => None,
+        }
+    }
+}
+
+/// What the viewer has toggled on an analysis message, and who asked for
+/// it. Discord hands the custom id back on every click, so this is the only
+/// state we keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewState {
+    pub flipped: bool,
+    pub lines: bool,
+    pub depth: u8,
+    pub threats: bool,
+    /// The user whose command posted the message, who may delete it.
+    /// `None` for subscriptions and messages from before we kept it.
+    pub requester: Option<u64>,
+}
+
+impl Default for ViewState {
+    fn default() -> Self {
+        Self {
+            flipped: false,
+            lines: false,
+            depth: DEFAULT_DEPTH,
+            threats: false,
+            requester: None,
+        }
+    }
+}
+
+/// A button press, encoded as `action:flipped:lines:depth:threats:requester`
+/// in the custom id, without the requester when there's none. Ids from
+/// before threats existed stop at the depth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentId {
+    pub action: Action,
+    pub state: ViewState,
+}
+
+impl ComponentId {
+    pub fn new(action: Action, state: ViewState) -> Self {
+        Self { action, state }
+    }
+
+    pub fn to_custom_id(self) -> String {
+        let mut custom_id = format!(
+            "{}:{}:{}:{}:{}",
+            self.action.as_str(),
+            self.state.flipped as u8,
+            self.state.lines as u8,
+            self.state.depth,
+            self.state.threats as u8
+        );
+        if let Some(requester) = self.state.requester {
+            custom_id.push_str(&format!(":{}", requester));
+        }
+        custom_id
+    }
+
+    pub fn parse(custom_id: &str) -> Result<Self, Error> {
+        if custom_id == LEGACY_CUSTOM_ID {
+            return Ok(Self::new(Action::Refresh, ViewState::default()));
+        }
+
+        let parts: Vec<&str> = custom_id.split(':').collect();
+        if !(4..=6).contains(&parts.len()) {
+            return Err(Error::BasicError(format!(
+                "Malformed custom id {:?}",
+                custom_id
+            )));
+        }
+
+        let action = Action::from_str(parts[0]).ok_or_else(|| {
+            Error::BasicError(format!("Unknown action in custom id {:?}", custom_id))
+        })?;
+        let flag = |s: &str| match s {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            _ => Err(Error::BasicError(format!(
+                "Malformed flag in custom id {:?}",
+                custom_id
+            ))),
+        };
+        let depth = parts[3].parse::<u8>().map_err(|_| {
+            Error::BasicError(format!("Malformed depth in custom id {:?}", custom_id))
+        })?;
+        let requester = match parts.get(5) {
+            Some(requester) => Some(requester.parse::<u64>().map_err(|_| {
+                Error::BasicError(format!("Malformed requester in custom id {:?}", custom_id))
+            })?),
+            None => None,
+        };
+
+        Ok(Self::new(
+            action,
+            ViewState {
+                flipped: flag(parts[1])?,
+                lines: flag(parts[2])?,
+                depth,
+                threats: match parts.get(4) {
+                    Some(threats) => flag(threats)?,
+                    None => false,
+                },
+                requester,
+            },
+        ))
+    }
+}
+
+/// The button bar shown under an analysis embed, labelled in `locale`.
+/// Messages showing one game get a row for stepping through it.
+pub fn action_buttons(
+    state: ViewState,
+    target: Option<&Target>,
+    disabled: bool,
+    locale: Locale,
+) -> Vec<CreateActionRow> {
+    let step = |action: Action, label: &str| {
+        CreateButton::new(ComponentId::new(action, state).to_custom_id())
+            .label(label)
+            .style(ButtonStyle::Secondary)
+            .disabled(disabled)
+    };
+    let button = |action: Action, label: &str, style: ButtonStyle| {
+        step(action, &tr(locale, label)).style(style)
+    };
+
+    let lines_label = if state.lines {
+        "button-hide-lines"
+    } else {
+        "button-show-lines"
+    };
+    let threats_label = if state.threats {
+        "button-hide-threats"
+    } else {
+        "button-show-threats"
+    };
+
+    let mut analysis = vec![
+        button(Action::Refresh, "button-refresh", ButtonStyle::Primary),
+        button(Action::Flip, "button-flip", ButtonStyle::Secondary),
+    ];
+    // the footer says when the engine can't go any deeper
+    if !engine::deeper_depths(state.depth).is_empty() {
+        analysis.push(button(
+            Action::Deeper,
+            "button-deeper",
+            ButtonStyle::Secondary,
+        ));
+    }
+    analysis.push(button(Action::Lines, lines_label, ButtonStyle::Secondary));
+    analysis.push(button(
+        Action::Threats,
+        threats_label,
+        ButtonStyle::Secondary,
+    ));
+
+    let mut rows = vec![
+        CreateActionRow::Buttons(analysis),
+        CreateActionRow::Buttons(vec![
+            button(Action::Track, "button-track", ButtonStyle::Success)
+                .disabled(disabled || target.is_some_and(|target| !target.is_live())),
+            button(Action::Delete, "button-delete", ButtonStyle::Danger),
+        ]),
+    ];
+
+    if let Some(Target::Game(game)) = target {
+        let at_start = game.ply == Some(0);
+        let at_end = game.ply.is_none();
+        rows.push(CreateActionRow::Buttons(vec![
+            step(Action::First, "⏮").disabled(disabled || at_start),
+            step(Action::Previous, "◀").disabled(disabled || at_start),
+            step(Action::Next, "▶").disabled(disabled || at_end),
+            step(Action::Last, "⏭").disabled(disabled || at_end),
+        ]));
+    }
+
+    rows
+}
+
+/// Handles `interaction` inside a span carrying who asked for what, so every
+/// line logged while scraping, analysing and rendering for it can be tied
+/// back to the request. `target` is filled in once we know the player or game.
+pub async fn handle(ctx: Context, interaction: Interaction) {
+    let span = match &interaction {
+        Interaction::Component(component) => info_span!(
+            "interaction",
+            request_id = %logging::request_id(),
+            guild = ?component.guild_id.map(|id| id.get()),
+            user = %component.user.id,
+            custom_id = %component.data.custom_id,
+            target = field::Empty,
+        ),
+        Interaction::Command(command) => info_span!(
+            "command",
+            request_id = %logging::request_id(),
+            guild = ?command.guild_id.map(|id| id.get()),
+            user = %command.user.id,
+            command = %command.data.name,
+            target = field::Empty,
+        ),
+        _ => Span::none(),
+    };
+
+    async move {
+        if shutdown::is_shutting_down() {
+            return reply_restarting(&ctx, interaction).await;
+        }
+
+        match interaction {
+            Interaction::Component(component) => handle_component(ctx, component).await,
+            Interaction::Command(command) => commands::handle(ctx, command).await,
+            other => debug!("Ignoring interaction {:?}", other.kind()),
+        }
+    }
+    .instrument(span)
+    .await
+}
+
+async fn handle_component(ctx: Context, component: ComponentInteraction) {
+    // Discord gives up on an answer after three seconds, and the lookups
+    // below can take longer than that when lichess or the database is slow
+    let acknowledged = component
+        .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+        .await;
+    if let Err(why) = acknowledged {
+        warn!("Error acknowledging button: {why:?}");
+        return;
+    }
+
+    let locale = locale::resolve(
+        Some(component.user.id),
+        component.guild_id,
+        Some(&component.locale),
+    );
+
+    let id = match ComponentId::parse(&component.data.custom_id) {
+        Ok(id) => id,
+        Err(why) => {
+            warn!("{}", why);
+            reply_ephemeral(&ctx, &component, &tr(locale, "reply-unsupported-button")).await;
+            return;
+        }
+    };
+
+    metrics::INTERACTIONS
+        .with_label_values(&[id.action.as_str()])
+        .inc();
+
+    let target = match find_target(component.message.id) {
+        Some(target) => target,
+        None => {
+            warn!("No target found for message {:?}", component.message.id);
+            reply_ephemeral(&ctx, &component, &tr(locale, "reply-unknown-game")).await;
+            return;
+        }
+    };
+    Span::current().record("target", target.name());
+    info!("{} pressed", id.action.as_str());
+
+    // everything but Delete shows the position to whoever pressed, so fair
+    // play gets a say; a delayed position can be re-drawn but not stepped
+    // through, deepened or tracked
+    let target = if id.action == Action::Delete {
+        target
+    } else {
+        let redraw = matches!(
+            id.action,
+            Action::Refresh | Action::Flip | Action::Lines | Action::Threats
+        );
+        match guard::check(component.user.id, component.guild_id, &target).await {
+            Verdict::Allow => target,
+            Verdict::Delay(delayed) if redraw => delayed,
+            Verdict::Delay(_) => {
+                reply_ephemeral(&ctx, &component, &tr(locale, "fair-play-refused")).await;
+                return;
+            }
+            Verdict::AfterGame(_) => {
+                reply_ephemeral(&ctx, &component, &tr(locale, "fair-play-wait")).await;
+                return;
+            }
+            Verdict::Refuse(id) => {
+                reply_ephemeral(&ctx, &component, &tr(locale, id)).await;
+                return;
+            }
+        }
+    };
+
+    match id.action {
+        Action::Refresh => refresh_from(&ctx, component, &target, id.state, locale).await,
+        Action::Flip => {
+            let state = ViewState {
+                flipped: !id.state.flipped,
+                ..id.state
+            };
+            refresh_from(&ctx, component, &target, state, locale).await
+        }
+        Action::Deeper => deepen(ctx, component, target, id.state, locale).await,
+        Action::Lines => {
+            let state = ViewState {
+                lines: !id.state.lines,
+                ..id.state
+            };
+            refresh_from(&ctx, component, &target, state, locale).await
+        }
+        Action::Threats => {
+            let state = ViewState {
+                threats: !id.state.threats,
+                ..id.state
+            };
+            refresh_from(&ctx, component, &target, state, locale).await
+        }
+        Action::Track => track(ctx, component, target, id.state, locale).await,
+        Action::Delete => {
+            let permissions = component
+                .member
+                .as_ref()
+                .map(|member| member.permissions.unwrap_or_else(Permissions::empty));
+            if !may_delete(component.user.id, id.state.requester, permissions) {
+                reply_ephemeral(&ctx, &component, &tr(locale, "reply-delete-refused")).await;
+                return;
+            }
+            if let Err(why) = component.message.delete(&ctx.http).await {
+                warn!("Error deleting message: {why:?}");
+                return;
+            }
+            forget_target(component.message.id);
+        }
+        Action::First | Action::Previous | Action::Next | Action::Last => match target {
+            Target::Game(game) => navigate(&ctx, component, game, id.action, id.state, locale).await,
+            Target::Player(_) => {
+                reply_ephemeral(&ctx, &component, &tr(locale, "reply-unsupported-button")).await
+            }
+        },
+    }
+}
+
+/// Whether `user_id` may delete an analysis `requester` asked for. Anyone
+/// who can manage messages may too. `permissions` are the member's in the
+/// channel, `None` in a DM, where there's nobody else to delete it.
+fn may_delete(user_id: UserId, requester: Option<u64>, permissions: Option<Permissions>) -> bool {
+    if requester == Some(user_id.get()) {
+        return true;
+    }
+    match permissions {
+        Some(permissions) => permissions.manage_messages(),
+        None => true,
+    }
+}
+
+/// What `message_id` is an analysis of, from whichever table remembered it.
+fn find_target(message_id: MessageId) -> Option<Target> {
+    if let Ok(view) = GameView::find_by_message_id(message_id.to_string()) {
+        return Some(Target::Game(view.game_ref()));
+    }
+    crate::models::message::Message::find_by_channel_id(message_id.to_string())
+        .ok()
+        .map(|channel| Target::Player(channel.player()))
+}
+
+/// Remembers what `message_id` shows so its buttons keep working.
+fn remember_target(target: &Target, message_id: MessageId) {
+    let _ = match target {
+        Target::Player(player) => crate::models::message::Message::insert(
+            crate::models::message::Message::new(player, message_id.to_string()),
+        ),
+        Target::Game(game) => GameView::insert(GameView::new(message_id.to_string(), game)),
+    };
+}
+
+fn forget_target(message_id: MessageId) {
+    if let Ok(view) = GameView::find_by_message_id(message_id.to_string()) {
+        let _ = view.delete();
+    }
+    if let Ok(channel) =
+        crate::models::message::Message::find_by_channel_id(message_id.to_string())
+    {
+        let _ = channel.delete();
+    }
+}
+
+async fn reply_restarting(ctx: &Context, interaction: Interaction) {
+    // no settings lookups on the way out, the database may already be gone
+    let locale = match &interaction {
+        Interaction::Component(component) => Locale::from_tag(&component.locale),
+        Interaction::Command(command) => Locale::from_tag(&command.locale),
+        _ => None,
+    };
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(tr(locale.unwrap_or_default(), "reply-restarting"))
+            .ephemeral(true),
+    );
+    let _ = match interaction {
+        Interaction::Component(component) => component.create_response(&ctx.http, response).await,
+        Interaction::Command(command) => command.create_response(&ctx.http, response).await,
+        _ => Ok(()),
+    };
+}
+
+/// Answers whoever pressed a button, which `handle_component` already
+/// acknowledged, with a message only they see.
+async fn reply_ephemeral(ctx: &Context, component: &ComponentInteraction, content: &str) {
+    let followup = CreateInteractionResponseFollowup::new()
+        .content(content)
+        .ephemeral(true);
+    let _ = component.create_followup(&ctx.http, followup).await;
+}
+
+async fn refresh_from(
+    ctx: &Context,
+    component: ComponentInteraction,
+    target: &Target,
+    state: ViewState,
+    locale: Locale,
+) {
+    // a refresh is a new position, so go back to the normal depth
+    let state = ViewState {
+        depth: DEFAULT_DEPTH,
+        ..state
+    };
+    let mut message = *component.message;
+    let _ = refresh(ctx, &mut message, target, state, true, false, locale).await;
+}
+
+/// Steps a `/game` message through its game and analyses the position it
+/// lands on. The ply is saved first so the next press starts from there.
+async fn navigate(
+    ctx: &Context,
+    component: ComponentInteraction,
+    game: GameRef,
+    action: Action,
+    state: ViewState,
+    locale: Locale,
+) {
+    let plies = match Lichess.game(&game.id).await {
+        Ok(Some(found)) => position::pgn_sans(&found.pgn).len(),
+        Ok(None) => {
+            warn!("No lichess game {}", game.id);
+            return;
+        }
+        Err(why) => {
+            error!("Error fetching game {}", why);
+            return;
+        }
+    };
+    let current = game.ply.unwrap_or(plies).min(plies);
+    let ply = match action {
+        Action::First => 0,
+        Action::Previous => current.saturating_sub(1),
+        Action::Next => current + 1,
+        _ => plies,
+    };
+    // the end is saved as the latest position so an unfinished game keeps up
+    let ply = if ply >= plies { None } else { Some(ply) };
+
+    // the button was checked against where the message was, not where it's
+    // going, and stepping on from a delayed position can reach the live one
+    let destination = Target::Game(GameRef {
+        ply,
+        ..game.clone()
+    });
+    let ply = match guard::check(component.user.id, component.guild_id, &destination).await {
+        Verdict::Allow => ply,
+        Verdict::Delay(Target::Game(delayed)) => delayed.ply,
+        Verdict::Delay(Target::Player(_)) | Verdict::AfterGame(_) => {
+            reply_ephemeral(ctx, &component, &tr(locale, "fair-play-refused")).await;
+            return;
+        }
+        Verdict::Refuse(id) => {
+            reply_ephemeral(ctx, &component, &tr(locale, id)).await;
+            return;
+        }
+    };
+
+    if let Err(why) = GameView::set_ply(component.message.id.to_string(), ply) {
+        error!("Error saving ply: {why:?}");
+    }
+
+    // a new position, so back to the normal depth like a refresh
+    let state = ViewState {
+        depth: DEFAULT_DEPTH,
+        ..state
+    };
+    let target = Target::Game(GameRef { ply, ..game });
+    let mut message = *component.message;
+    let _ = refresh(ctx, &mut message, &target, state, true, false, locale).await;
+}
+
+/// Keeps refreshing the message until the game ends or we hit the refresh cap.
+async fn track(
+    ctx: Context,
+    component: ComponentInteraction,
+    target: Target,
+    state: ViewState,
+    locale: Locale,
+) {
+    if !target.is_live() {
+        reply_ephemeral(&ctx, &component, &tr(locale, "reply-not-live")).await;
+        return;
+    }
+
+    let message = *component.message.clone();
+    let state = ViewState {
+        depth: DEFAULT_DEPTH,
+        ..state
+    };
+
+    if !start_tracking(ctx.clone(), message, target, state, true, locale).await {
+        reply_ephemeral(&ctx, &component, &tr(locale, "reply-already-tracked")).await;
+    }
+}
+
+/// Spawns the tracking loop for `message`. Returns false if it's already tracked.
+pub async fn start_tracking(
+    ctx: Context,
+    message: serenity::all::Message,
+    target: Target,
+    state: ViewState,
+    show_loading: bool,
+    locale: Locale,
+) -> bool {
+    let tracked = {
+        let data = ctx.data.read().await;
+        data.get::<TrackedGames>().cloned()
+    };
+    let tracked = match tracked {
+        Some(tracked) => tracked,
+        None => return false,
+    };
+
+    let message_id = message.id;
+    if !tracked.lock().await.insert(message_id) {
+        return false;
+    }
+
+    tokio::spawn(async move {
+        // remember which game this is so we can report on it once it's over,
+        // reports are built from lichess's export so only lichess games get one
+        let game_id = match &target {
+            Target::Player(player) if player.platform == Platform::Lichess => Lichess
+                .current_games(&[player.name.to_lowercase()])
+                .await
+                .ok()
+                .and_then(|games| games.get(&player.name.to_lowercase()).cloned()),
+            Target::Player(_) => None,
+            Target::Game(game) => Some(game.id.clone()),
+        };
+
+        let mut message = message;
+        let mut game_over = false;
+        let mut shutting_down = false;
+        let mut failures = 0;
+        for i in 0..TRACK_MAX_REFRESHES {
+            let show_loading = show_loading && i == 0;
+            match refresh(&ctx, &mut message, &target, state, show_loading, true, locale).await {
+                Ok(()) => failures = 0,
+                Err(why) if tracked_game_over(game_id.as_deref(), &why).await => {
+                    game_over = true;
+                    break;
+                }
+                // most likely lichess having a moment, so back off and retry
+                Err(_) => failures = (failures + 1).min(TRACK_MAX_BACKOFF),
+            }
+            if !shutdown::sleep(TRACK_INTERVAL * (1 << failures)).await {
+                shutting_down = true;
+                break;
+            }
+        }
+
+        tracked.lock().await.remove(&message_id);
+
+        // put the buttons back now that we've stopped
+        let buttons = action_buttons(state, Some(&target), false, locale);
+        let _ = message.edit(&ctx.http, EditMessage::new().components(buttons)).await;
+
+        let game_id = match game_id {
+            Some(game_id) if !shutting_down => game_id,
+            _ => return,
+        };
+        // out of refreshes with the game still going, report once it's over
+        if !game_over && !guard::wait_until_over(&game_id).await {
+            return;
+        }
+        let posted = report::post_report(&ctx, message.channel_id, &game_id, locale).await;
+        if let Err(why) = posted {
+            error!("Error posting report: {}", why);
+        }
+    }
+    .in_current_span());
+
+    true
+}
+
+/// Whether a tracked game that failed to refresh is over. lichess games are
+/// looked up, anything else is over once its player isn't in a game.
+async fn tracked_game_over(game_id: Option<&str>, why: &Error) -> bool {
+    match game_id {
+        Some(game_id) => matches!(Lichess.game(game_id).await, Ok(Some(game)) if game.finished),
+        None => matches!(why, Error::NotFound(_)),
+    }
+}
+
+/// Posts a fresh analysis of `target` to `channel_id`, which `requester`
+/// may delete when someone asked for it.
+pub async fn post_analysis(
+    ctx: &Context,
+    channel_id: ChannelId,
+    target: &Target,
+    requester: Option<UserId>,
+    locale: Locale,
+) -> Result<serenity::all::Message, Error> {
+    let state = ViewState {
+        requester: requester.map(UserId::get),
+        ..ViewState::default()
+    };
+    let loading_message = AnalysisView::loading(target, state, locale).create_message(false);
+
+    let mut message = channel_id
+        .send_message(&ctx.http, loading_message)
+        .await
+        .map_err(|e| Error::BasicError(format!("Error sending message {:?}", e)))?;
+
+    remember_target(target, message.id);
+
+    refresh(ctx, &mut message, target, state, false, false, locale).await?;
+    Ok(message)
+}
+
+async fn refresh(
+    ctx: &Context,
+    message: &mut serenity::all::Message,
+    target: &Target,
+    state: ViewState,
+    show_loading: bool,
+    tracking: bool,
+    locale: Locale,
+) -> Result<(), Error> {
+    let _job = shutdown::Job::start(message.channel_id, message.id, state.requester, locale);
+
+    if show_loading {
+        let loading_message = AnalysisView::loading(target, state, locale).edit_message(true);
+        let _ = message.edit(&ctx.http, loading_message).await;
+    }
+
+    let stock_resp = help_target(target, state.flipped, state.depth, state.threats).await;
+
+    if let Err(why) = stock_resp {
+        error!("Error getting help {:?}", why.to_string());
+        let failed_message = AnalysisView::failed(target, &why, state, locale).edit_message(false);
+        let _ = message.edit(&ctx.http, failed_message).await;
+        return Err(why);
+    }
+
+    publish(ctx, message, target, state, stock_resp.unwrap(), tracking, locale).await
+}
+
+/// Re-runs the message's last analysed position at increasing depths,
+/// editing the embed after each one.
+async fn deepen(
+    ctx: Context,
+    component: ComponentInteraction,
+    target: Target,
+    state: ViewState,
+    locale: Locale,
+) {
+    let cached = Analysis::find_latest_by_message_id(component.message.id.to_string());
+    metrics::cache_lookup("analysis", cached.is_ok());
+    let cached = match cached {
+        Ok(cached) => cached,
+        // nothing cached for older messages, so just analyse them again
+        Err(_) => return refresh_from(&ctx, component, &target, state, locale).await,
+    };
+
+    let running = {
+        let data = ctx.data.read().await;
+        data.get::<DeepAnalyses>().cloned()
+    };
+    let running = match running {
+        Some(running) => running,
+        None => return,
+    };
+
+    let key = component
+        .guild_id
+        .map(|guild_id| guild_id.get())
+        .unwrap_or(component.channel_id.get());
+    let slot = match DeepAnalysisSlot::acquire(running, key) {
+        Some(slot) => slot,
+        None => {
+            reply_ephemeral(&ctx, &component, &tr(locale, "reply-deep-analysis-busy")).await;
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let _slot = slot;
+        let mut message = *component.message;
+        let _job = shutdown::Job::start(message.channel_id, message.id, state.requester, locale);
+        let mut state = state;
+
+        let depths = engine::deeper_depths(cached.depth.clamp(0, u8::MAX as i32) as u8);
+
+        // a flipped image is drawn from the opponent's side
+        let side = if state.flipped {
+            opposite(&cached.orientation)
+        } else {
+            &cached.orientation
+        };
+
+        for (i, depth) in depths.iter().enumerate() {
+            // finish the depth we're on but don't start another
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            let mut board = match decode_fen(&cached.fen) {
+                Ok(board) => board,
+                Err(why) => {
+                    error!("{}", why);
+                    break;
+                }
+            };
+            // the image is drawn from black's side, so rotate the fen board
+            if cached.orientation == "b" {
+                let mut new_board = [[0u8; 8]; 8];
+                for i in 0..8 {
+                    for j in 0..8 {
+                        new_board[i][j] = board[7 - i][7 - j];
+                    }
+                }
+                board = new_board;
+            }
+
+            let stock_resp = evaluate(
+                board,
+                &cached.orientation,
+                side,
+                cached.last_moves(),
+                cached.fen.clone(),
+                *depth,
+                state.threats,
+                target.name().to_string(),
+                cached.variant(),
+            )
+            .await;
+
+            let stock_resp = match stock_resp {
+                Ok(stock_resp) => stock_resp,
+                Err(why) => {
+                    error!("Error analysing deeper {:?}", why.to_string());
+                    break;
+                }
+            };
+
+            state.depth = stock_resp.depth;
+            let still_working = i + 1 < depths.len();
+            if publish(&ctx, &mut message, &target, state, stock_resp, still_working, locale)
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+
+        // make sure the buttons come back even if we stopped early
+        let buttons = action_buttons(state, Some(&target), false, locale);
+        let edit_message = EditMessage::new().components(buttons);
+        let _ = message.edit(&ctx.http, edit_message).await;
+    }
+    .in_current_span());
+}
+
+/// Uploads the board `image` as `file` to the image channel and returns its
+/// url.
+pub async fn upload_board(ctx: &Context, file: &str, image: &[u8]) -> Result<String, Error> {
+    let files = vec![CreateAttachment::bytes(image.to_vec(), file)];
+
+    let temp_message = CreateMessage::new();
+    let _tchannel = ChannelId::new(167174376045805568);
+    let chan = _tchannel
+        .send_files(&ctx.http, files, temp_message)
+        .await
+        .map_err(|e| Error::BasicError(format!("Error uploading board image {:?}", e)))?;
+
+    Ok(chan.attachments[0].url.clone())
+}
+
+/// Uploads the rendered board and edits `message` to show `stock_resp`.
+async fn publish(
+    ctx: &Context,
+    message: &mut serenity::all::Message,
+    target: &Target,
+    state: ViewState,
+    stock_resp: GetStockFishResponse,
+    busy: bool,
+    locale: Locale,
+) -> Result<(), Error> {
+    let attachment_url = upload_board(ctx, &stock_resp.file, &stock_resp.image).await?;
+    let _ = Analysis::insert(Analysis::new(message.id.to_string(), &stock_resp));
+
+    // buttons stay disabled while a tracking or deepening loop owns the message
+    let view = AnalysisView::ready(target, stock_resp, attachment_url, state, locale);
+    if let Err(why) = message.edit(&ctx.http, view.edit_message(busy)).await {
+        warn!("Error sending message: {why:?}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACTIONS: [Action; 11] = [
+        Action::Refresh,
+        Action::Flip,
+        Action::Deeper,
+        Action::Lines,
+        Action::Threats,
+        Action::Track,
+        Action::Delete,
+        Action::First,
+        Action::Previous,
+        Action::Next,
+        Action::Last,
+    ];
+
+    #[test]
+    fn component_ids_round_trip() {
+        let states = [
+            ViewState::default(),
+            ViewState {
+                flipped: true,
+                lines: true,
+                depth: 30,
+                threats: true,
+                requester: Some(80351110224678912),
+            },
+            ViewState {
+                depth: 0,
+                requester: Some(u64::MAX),
+                ..ViewState::default()
+            },
+        ];
+        for action in ACTIONS {
+            for state in states {
+                let id = ComponentId::new(action, state);
+                let custom_id = id.to_custom_id();
+                // Discord caps custom ids at 100 characters
+                assert!(custom_id.len() <= 100, "{}", custom_id);
+                assert_eq!(ComponentId::parse(&custom_id).unwrap(), id, "{}", custom_id);
+            }
+        }
+    }
+
+    #[test]
+    fn component_ids_are_readable() {
+        let id = ComponentId::new(
+            Action::Delete,
+            ViewState {
+                flipped: true,
+                requester: Some(42),
+                ..ViewState::default()
+            },
+        );
+        assert_eq!(id.to_custom_id(), "delete:1:0:15:0:42");
+        let id = ComponentId::new(Action::Refresh, ViewState::default());
+        assert_eq!(id.to_custom_id(), "refresh:0:0:15:0");
+    }
+
+    #[test]
+    fn old_component_ids_still_parse() {
+        let legacy = ComponentId::parse(LEGACY_CUSTOM_ID).unwrap();
+        assert_eq!(
+            legacy,
+            ComponentId::new(Action::Refresh, ViewState::default())
+        );
+
+        // from before threats, and before the requester was kept
+        let id = ComponentId::parse("flip:1:0:20").unwrap();
+        assert_eq!(id.action, Action::Flip);
+        assert_eq!(
+            id.state,
+            ViewState {
+                flipped: true,
+                depth: 20,
+                ..ViewState::default()
+            }
+        );
+        let id = ComponentId::parse("delete:0:1:15:1").unwrap();
+        assert_eq!(id.action, Action::Delete);
+        assert!(id.state.lines && id.state.threats);
+        assert_eq!(id.state.requester, None);
+    }
+
+    #[test]
+    fn malformed_component_ids_are_errors() {
+        for custom_id in [
+            "",
+            "testbutton",
+            "refresh",
+            "refresh:0:0",
+            "refresh:0:0:15:0:42:7",
+            "resign:0:0:15:0",
+            "refresh:2:0:15:0",
+            "refresh:0:yes:15:0",
+            "refresh:0:0:256:0",
+            "refresh:0:0:-1:0",
+            "delete:0:0:15:0:",
+            "delete:0:0:15:0:someone",
+        ] {
+            assert!(ComponentId::parse(custom_id).is_err(), "{:?}", custom_id);
+        }
+    }
+
+    #[test]
+    fn only_the_requester_or_moderators_delete() {
+        let (requester, other) = (UserId::new(1), UserId::new(2));
+        let member = Some(Permissions::SEND_MESSAGES | Permissions::READ_MESSAGE_HISTORY);
+        let moderator = Some(Permissions::MANAGE_MESSAGES);
+
+        assert!(may_delete(requester, Some(1), member));
+        assert!(!may_delete(other, Some(1), member));
+        assert!(may_delete(other, Some(1), moderator));
+        // subscriptions and old messages are the moderators' alone
+        assert!(!may_delete(requester, None, member));
+        assert!(may_delete(requester, None, moderator));
+        // a DM has nobody else in it
+        assert!(may_delete(other, Some(1), None));
+    }
+}
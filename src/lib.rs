@@ -0,0 +1,56 @@
+//! The chess side of the bot, shared by the Discord bot, the HTTP API and
+//! the command line tool, and usable without Serenity, which only the bot
+//! pulls in through the default `discord` feature:
+//!
+//! - `source` and `board` find the game a player is in, or fetch one by id,
+//!   and set it up for analysis
+//! - `position` and `notation` handle FEN, PGN and SAN
+//! - `engine`, `tablebase`, `book` and `openings` judge a position
+//! - `accuracy` grades moves and games the way lichess' reports do
+//! - `renderer` draws the board image
+//! - `i18n` holds the message catalogs for the bot's replies
+//! - `fairplay` decides how much of a player's own game in progress they see
+//! - `oauth` signs people in with lichess to verify which account is theirs
+//! - `metrics` and `logging` count, time and trace all of the above
+//! - `models`, `schema` and `database` store analyses, subscriptions,
+//!   the games messages show, linked accounts and server and user settings,
+//!   behind the default `storage` feature
+
+pub mod accuracy;
+pub mod board;
+pub mod book;
+pub mod engine;
+pub mod fairplay;
+pub mod i18n;
+pub mod logging;
+pub mod metrics;
+pub mod notation;
+pub mod oauth;
+pub mod openings;
+pub mod position;
+pub mod renderer;
+pub mod source;
+pub mod tablebase;
+pub mod models {
+    pub mod error;
+    #[cfg(feature = "storage")]
+    pub mod analysis;
+    #[cfg(feature = "storage")]
+    pub mod game_view;
+    #[cfg(feature = "storage")]
+    pub mod guild_settings;
+    #[cfg(feature = "storage")]
+    pub mod linked_account;
+    #[cfg(feature = "storage")]
+    pub mod message;
+    #[cfg(feature = "storage")]
+    pub mod subscription;
+    #[cfg(feature = "storage")]
+    pub mod user_settings;
+}
+#[cfg(feature = "storage")]
+pub mod database {
+    pub mod databse;
+}
+#[cfg(feature = "storage")]
+pub mod schema;
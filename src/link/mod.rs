@@ -0,0 +1,138 @@
+use std::env;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use serde::Deserialize;
+use serenity::all::UserId;
+use tracing::{error, info, warn};
+
+use crate::i18n::{default_locale, tr, tr_args, Locale};
+use crate::models::linked_account::LinkedAccount;
+use crate::oauth::{LichessOAuth, SignIns};
+use crate::shutdown;
+
+/// How long someone has to finish signing in after `/link`.
+const LINK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// A `/link` waiting for lichess to send its user back.
+struct Pending {
+    user_id: UserId,
+    locale: Locale,
+}
+
+static PENDING: LazyLock<SignIns<Pending>> = LazyLock::new(|| SignIns::new(LINK_TIMEOUT));
+
+#[derive(Debug, Deserialize)]
+struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// Starts linking `user_id`'s lichess account: the url to sign in at, or
+/// `None` when linking isn't set up.
+pub fn start(user_id: UserId, locale: Locale) -> Option<String> {
+    let oauth = LichessOAuth::from_env()?;
+    let (state, pkce) = PENDING.start(Pending { user_id, locale });
+    match oauth.authorize_url(&pkce, &state) {
+        Ok(url) => Some(url),
+        Err(why) => {
+            error!("{}", why);
+            None
+        }
+    }
+}
+
+/// Serves the page lichess sends people back to on `OAUTH_ADDR`, at the
+/// path of `OAUTH_REDIRECT_URL`, until we shut down. Does nothing when
+/// either isn't set.
+pub async fn run() {
+    let addr = match env::var("OAUTH_ADDR").ok().filter(|addr| !addr.is_empty()) {
+        Some(addr) => addr,
+        None => return,
+    };
+    let oauth = match LichessOAuth::from_env() {
+        Some(oauth) => oauth,
+        None => {
+            warn!("OAUTH_ADDR is set without OAUTH_REDIRECT_URL, /link is off");
+            return;
+        }
+    };
+    let path = reqwest::Url::parse(&oauth.redirect_uri)
+        .map(|url| url.path().to_string())
+        .unwrap_or_else(|_| "/".to_string());
+
+    let app = Router::new().route(&path, get(callback));
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(why) => {
+            error!("Error binding OAuth callback to {:?}: {why:?}", addr);
+            return;
+        }
+    };
+    info!("OAuth callback listening on {}{}", addr, path);
+
+    let server = axum::serve(listener, app).with_graceful_shutdown(shutdown::wait());
+    if let Err(why) = server.await {
+        error!("OAuth callback error: {why:?}");
+    }
+}
+
+/// Finishes a sign-in: checks `state` is one we sent, asks lichess who
+/// signed in and links them to whoever ran `/link`.
+async fn callback(Query(params): Query<CallbackParams>) -> (StatusCode, String) {
+    let pending = params.state.and_then(|state| PENDING.finish(&state));
+    let (verifier, pending) = match pending {
+        Some(pending) => pending,
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                tr(default_locale(), "link-page-expired"),
+            )
+        }
+    };
+    let locale = pending.locale;
+
+    let code = match (params.code, params.error) {
+        (Some(code), None) => code,
+        (_, error) => {
+            info!(
+                "lichess sign-in for {} ended with {:?}",
+                pending.user_id, error
+            );
+            return (StatusCode::BAD_REQUEST, tr(locale, "link-page-denied"));
+        }
+    };
+    let oauth = match LichessOAuth::from_env() {
+        Some(oauth) => oauth,
+        None => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                tr(locale, "link-unavailable"),
+            )
+        }
+    };
+
+    let username = match oauth.username(&code, &verifier).await {
+        Ok(username) => username,
+        Err(why) => {
+            warn!("Error finishing a lichess sign-in: {}", why);
+            return (StatusCode::BAD_GATEWAY, tr(locale, "link-page-failed"));
+        }
+    };
+    match LinkedAccount::link(pending.user_id.to_string(), username.clone()) {
+        Ok(_) => {
+            info!("{} linked lichess account {}", pending.user_id, username);
+            let done = tr_args(locale, "link-done", &[("user", username.into())]);
+            (StatusCode::OK, done)
+        }
+        Err(why) => {
+            error!("Error saving linked account: {why:?}");
+            (StatusCode::INTERNAL_SERVER_ERROR, tr(locale, "link-failed"))
+        }
+    }
+}
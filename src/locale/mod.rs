@@ -0,0 +1,35 @@
+use serenity::all::{ChannelId, Context, GuildId, UserId};
+
+use crate::i18n::{self, Locale};
+use crate::models::guild_settings::GuildSettings;
+use crate::models::user_settings::UserSettings;
+
+/// The language to answer in: the user's own choice, then their server's,
+/// then the language their Discord client is set to.
+pub fn resolve(
+    user_id: Option<UserId>,
+    guild_id: Option<GuildId>,
+    discord_locale: Option<&str>,
+) -> Locale {
+    user_id
+        .and_then(|user_id| UserSettings::find_by_user_id(user_id.to_string()).ok())
+        .and_then(|settings| settings.locale())
+        .or_else(|| {
+            guild_id
+                .and_then(|guild_id| GuildSettings::find_by_guild_id(guild_id.to_string()).ok())
+                .and_then(|settings| settings.locale())
+        })
+        .or_else(|| discord_locale.and_then(Locale::from_tag))
+        .unwrap_or_else(i18n::default_locale)
+}
+
+/// The server `channel_id` is in, for posts nobody asked for like a
+/// subscription's, which go by the server's settings.
+pub async fn guild_of(ctx: &Context, channel_id: ChannelId) -> Option<GuildId> {
+    channel_id
+        .to_channel(&ctx.http)
+        .await
+        .ok()
+        .and_then(|channel| channel.guild())
+        .map(|channel| channel.guild_id)
+}
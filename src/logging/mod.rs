@@ -0,0 +1,31 @@
+use std::env;
+
+use tracing_subscriber::EnvFilter;
+
+/// Sets up `tracing` output on stderr. Levels come from `RUST_LOG` (e.g.
+/// `info` or `lichess_stockfish=debug`), falling back to `default_level`.
+/// `LOG_FORMAT=json` writes one JSON object per line, anything else is the
+/// human readable format.
+pub fn init(default_level: &str) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_level));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+
+    let json = env::var("LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"));
+    let result = if json {
+        builder.json().with_current_span(true).try_init()
+    } else {
+        builder.try_init()
+    };
+
+    if let Err(why) = result {
+        eprintln!("Error setting up logging: {why}");
+    }
+}
+
+/// A fresh id to tie one request's log lines together.
+pub fn request_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
@@ -1,391 +1,120 @@
 use core::str;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::env;
 
-use board::help;
 use dotenv::dotenv;
 use serenity::all::standard::macros::hook;
 use serenity::all::{
-    ChannelId, Context, CreateAttachment, CreateButton, CreateEmbed, CreateInteractionResponse, CreateMessage, EditMessage, EventHandler, GatewayIntents, Interaction, Message, MessageBuilder
+    Context, EventHandler, GatewayIntents, Interaction, Message, Ready, ConnectionStage, ShardStageUpdateEvent
 };
 use serenity::async_trait;
 use serenity::client::Client;
-use tokio::fs::File;
 use tokio::sync::Mutex;
+use tracing::{debug, error, field, info, instrument, warn, Span};
 
-mod board;
-mod schema;
-mod models {
-    pub mod message;
-	pub mod error;
-}
+use i18n::tr;
+use interactions::{DeepAnalyses, TrackedGames};
+use source::{Player, Target};
 
-pub mod database {
-    pub mod databse;
-}
+use lichess_stockfish::{
+    accuracy, board, book, database, engine, fairplay, i18n, logging, metrics, models, notation,
+    oauth, openings, position, renderer, source, tablebase,
+};
+
+mod commands;
+mod guard;
+mod interactions;
+mod link;
+mod locale;
+mod report;
+mod server;
+mod shutdown;
+mod view;
+mod watcher;
 
 struct Handler;
 
+static WATCHER_STARTED: AtomicBool = AtomicBool::new(false);
+
 #[async_trait]
 impl EventHandler for Handler {
+    #[instrument(
+        name = "message",
+        skip_all,
+        fields(
+            request_id = %logging::request_id(),
+            guild = ?msg.guild_id.map(|id| id.get()),
+            user = %msg.author.id,
+            target = field::Empty,
+        )
+    )]
     async fn message(&self, ctx: Context, msg: Message) {
         let args = msg.content.split_whitespace().collect::<Vec<&str>>();
         if args.len() == 1 && args[0].trim() == "!ping" {
             if let Err(why) = msg.channel_id.say(&ctx.http, "Pong!").await {
-                println!("Error sending message: {why:?}");
+                warn!("Error sending message: {why:?}");
             }
         }
 
         if args.len() == 2 && args[0] == "!help" {
+            metrics::COMMANDS.with_label_values(&["help"]).inc();
+            let locale = locale::resolve(Some(msg.author.id), msg.guild_id, None);
+            if shutdown::is_shutting_down() {
+                let _ = msg.channel_id.say(&ctx.http, tr(locale, "reply-restarting")).await;
+                return;
+            }
             let _ = msg.channel_id.broadcast_typing(&ctx.http).await.unwrap();
             let channel = args[1];
-            // to thread safe chennel
-            let og_channel = channel;
-            let channel = Arc::new(Mutex::new(&channel));
-
-			let mut description = MessageBuilder::new();
-
-			description.push_bold("Evaluation: ");
-			description.push_italic("Loading...");
-			description.push("\n");
-			
-			description.push_bold("Forced Mate?: ");
-			description.push_italic("Loading...");
-			description.push("\n");
-		
-			description.push_bold("Best Move: ");
-			description.push_italic("Loading...");
-			description.push("\n");
-			
-			let description = description.build();
-
-			let embed = CreateEmbed::default()
-				.title(og_channel.to_uppercase())
-				.description(description)
-				.image("https://dummyimage.com/1024x1024/2b2d31/ffffff.png&text=Fetching+Stockfish...")
-				;
-
-			let button = CreateButton::new("testButton")
-                .label("Refresh")
-                .style(serenity::all::ButtonStyle::Primary)
-                .custom_id("testButton")
-				.disabled(true)
-				;
-
-
-			let message = CreateMessage::new()
-				.embed(embed)
-                .button(button)
-
-				;
-
-                
-			let mut message = msg.channel_id.send_message(&ctx.http, message).await.unwrap();
-			let _  = crate::models::message::Message::insert(
-				crate::models::message::Message::new(og_channel.to_string(), message.id.to_string())
-			);
-			
-            let stock_resp = help(channel).await;
-
-			if stock_resp.is_err() {
-				println!("Error getting help {:?}", stock_resp.as_ref().err().unwrap().to_string());
-				// let _ = msg.channel_id.say(&ctx.http, format!("Error: {:?}", stock_resp.err())).await;
-				let mut description = MessageBuilder::new();
-
-				description.push_bold("Evaluation: ");
-				description.push_italic("N/A");
-				description.push("\n");
-				
-				description.push_bold("Forced Mate?: ");
-				description.push_italic("N/A");
-				description.push("\n");
-			
-				description.push_bold("Best Move: ");
-				description.push_italic("N/A");
-				description.push("\n");
-				description.push("\n");
-				description.push("\n");
-				description.push_quote(format!("Error getting help {:?}", stock_resp.err().unwrap().to_string()));
-				
-				let description = description.build();
-	
-				let embed = CreateEmbed::default()
-					.title(og_channel.to_uppercase())
-					.description(description)
-					;
-	
-				let button = CreateButton::new("testButton")
-					.label("Refresh")
-					.style(serenity::all::ButtonStyle::Danger)
-					.custom_id("testButton")
-					.disabled(true)
-					
-					;
-	
-	
-				let new_message = EditMessage::new()
-					.embed(embed)
-					.button(button)
-	
-					;
-				
-				let _ = message.edit(&ctx.http, new_message).await.unwrap();
-				return ();
-			}
-			let stock_resp = stock_resp.unwrap();
-
-
-            println!("{}", stock_resp.clone().file);
-            // send image to discord
-            let mut files: Vec<CreateAttachment> = vec![];
-            let file = File::open(format!("./pics/{}", stock_resp.clone().file)).await.unwrap();
-            files.push(CreateAttachment::file(&file, "test.png").await.unwrap());
-
-			let temp_message = CreateMessage::new();
-			let _tchannel = ChannelId::new(167174376045805568 as u64);
-			let chan = _tchannel.send_files(&ctx.http, files, temp_message).await;
-	
-			let attachment_url = chan.unwrap().attachments[0].url.clone();
-
-			let mut description = MessageBuilder::new();
-
-	
-		
-			let chance_to_win = stock_resp.evaluation;
-
-			description.push_bold("Evaluation: ");
-			if chance_to_win > 0.0 {
-				description.push("You are winning by ");
-				description.push(format!("{:.2}%", chance_to_win));
-			} else if chance_to_win < -40.0 {
-				description.push("Consider Conceeding. You are losing by ");
-				description.push(format!("{:.2}%", chance_to_win));
-			} else if chance_to_win < 0.0 {
-				description.push("You are losing by ");
-				description.push(format!("{:.2}%", chance_to_win));
-			} else {
-				println!("You are equal.");
-			}
-			description.push("\n");
-			
-			description.push_bold("Forced Mate?: ");
-			if stock_resp.clone().mate.is_some() {
-				description.push(stock_resp.clone().mate.unwrap().to_string());
-				description.push("\n");
-			} else {
-				description.push("No");
-				description.push("\n");
-			}
-
-			description.push_bold("Best Move: ");
-			description.push(stock_resp.clone().bestmove.to_string());
-			description.push("\n");
-			
-                
-			let description = description.build();
-
-			let button = CreateButton::new("testButton")
-			.label("test")
-			.style(serenity::all::ButtonStyle::Primary)
-			.custom_id("testButton")
-			.disabled(false)
-			;
-
-            let embed = CreateEmbed::default()
-                .title(og_channel.to_uppercase())
-                .description(description)
-                .image(attachment_url);
-
-       
-
-            let message1 = EditMessage::new()
-                .embed(embed)
-				.button(button)
-				;
-
-			let _ = message.edit(&ctx.http, message1).await.unwrap();
-
-            // clean up image
-            let _ = tokio::fs::remove_file(stock_resp.clone().file).await;
-            ()
+            Span::current().record("target", channel);
+            info!("!help");
+            let target = Target::Player(Player::lichess(channel));
+            let allowed =
+                guard::allowed(&ctx, msg.channel_id, msg.author.id, msg.guild_id, target, locale);
+            let target = match allowed.await {
+                Ok(target) => target,
+                Err(reply) => {
+                    let _ = msg.channel_id.say(&ctx.http, reply).await;
+                    return;
+                }
+            };
+            let requester = Some(msg.author.id);
+            let posted = interactions::post_analysis(&ctx, msg.channel_id, &target, requester, locale);
+            if let Err(why) = posted.await {
+                error!("Error posting analysis: {}", why);
+            }
         }
     }
 
-    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        println!("Interaction received");
-        // ctx.set_activity(Some(ActivityData::custom("Getting the cookie")));
-
-        // update message it was called on
-        let referenced_message = interaction.clone().message_component().unwrap().message.id;
-        let channel = crate::models::message::Message::find_by_channel_id(referenced_message.to_string());
-
-		if channel.is_err() {
-			println!("No referenced message {:?}", interaction.clone().message_component().unwrap().message);
-
-			println!("No channel found");
-			return ();
-		}
-
-		let channel = channel.unwrap();
-		let og_channel = channel.clone().lc_channel;
-		let channel = channel.lc_channel.as_str();
-
-		let channel = Arc::new(Mutex::new(&channel));
-
-        // send ping
-		let r = CreateInteractionResponse::Acknowledge;
-		let _ = interaction
-            .as_message_component()
-            .unwrap()
-            .create_response(&ctx.http, r)
-            .await;
-
-		
-
-		let mut description = MessageBuilder::new();
-
-		description.push_bold("Evaluation: ");
-		description.push_italic("Loading...");
-		description.push("\n");
-		
-		description.push_bold("Forced Mate?: ");
-		description.push_italic("Loading...");
-		description.push("\n");
-	
-		description.push_bold("Best Move: ");
-		description.push_italic("Loading...");
-		description.push("\n");
-		
-		let description = description.build();
-
-		let loading_embed = CreateEmbed::default()
-			.description(description)
-			.image("https://dummyimage.com/1024x1024/2b2d31/ffffff.png&text=Fetching+Stockfish...")
-			;
-
-		let loading_button = CreateButton::new("testButton")
-			.style(serenity::all::ButtonStyle::Primary)
-			.custom_id("testButton")
-			.disabled(true)
-			.label("Loading...")
-			;
-
-
-		let loading_message = EditMessage::new()
-			.embed(loading_embed)
-			.button(loading_button)
-
-			;
-
-		let _ = interaction.clone().message_component().unwrap().message.edit(&ctx.http, loading_message).await;
- 
-
-
-
-
-        let stock_resp = help(channel).await;
-
-		if stock_resp.is_err() {
-			let nmessage = EditMessage::new().content("Error getting help");
-			let _ = interaction.clone().message_component().unwrap().message.edit(&ctx.http, nmessage).await;
-			return ();
-		}
-
-		let stock_resp = stock_resp.unwrap();
-
-	
-
-		let mut files: Vec<CreateAttachment> = vec![];
-        let file = File::open(format!("./pics/{}", stock_resp.clone().file)).await.unwrap();
-		files.push(CreateAttachment::file(&file, stock_resp.clone().file).await.unwrap());
-		
-
-
-		let temp_message = CreateMessage::new();
-		let _tchannel = ChannelId::new(167174376045805568 as u64);
-		let chan = _tchannel.send_files(&ctx.http, files, temp_message).await;
-
-		let attachment_url = chan.unwrap().attachments[0].url.clone();
-
-
-
-		let mut description = MessageBuilder::new();
-
-		let chance_to_win = stock_resp.evaluation;
-
-		description.push_bold("Evaluation: ");
-		if chance_to_win > 0.0 {
-			description.push("You are winning by ");
-			description.push(format!("{:.2}%", chance_to_win));
-		} else if chance_to_win < -40.0 {
-			description.push("Consider Conceeding. You are losing by ");
-			description.push(format!("{:.2}%", chance_to_win));
-		} else if chance_to_win < 0.0 {
-			description.push("You are losing by ");
-			description.push(format!("{:.2}%", chance_to_win));
-		} else {
-			description.push("equal");
-
-			println!("You are equal.");
-		}
-			description.push("\n");
-			
-			description.push_bold("Forced Mate?: ");
-			if stock_resp.clone().mate.is_some() {
-				description.push(stock_resp.clone().mate.unwrap().to_string());
-				description.push("\n");
-			} else {
-				description.push("No");
-				description.push("\n");
-			}
-
-			description.push_bold("Best Move: ");
-			description.push(stock_resp.clone().bestmove.to_string());
-			description.push("\n");
-			
-                
-			let description = description.build();
-
-        let embed = CreateEmbed::default()
-			.title(og_channel.to_uppercase())
-			.description(description)
-        	.image(attachment_url)
-			// .attachment(image_path.clone())
-		
-			;
-
-        let button = CreateButton::new("testButton")
-        	.label("Refresh")
-        	.style(serenity::all::ButtonStyle::Primary)
-        	.custom_id("testButton")
-			.disabled(false)
-		;
-
-	
-		let mut message = interaction.clone().message_component().unwrap().message;
-		
-        let edit_message = EditMessage::new()
-		.embed(embed.clone())
-		.remove_all_attachments()
-		.button(button)
-		;
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!("{} is connected.", ready.user.name);
+        server::GATEWAY_CONNECTED.store(true, Ordering::SeqCst);
+        commands::register(&ctx).await;
 
+        // ready fires again on reconnects, only start one watcher
+        if !WATCHER_STARTED.swap(true, Ordering::SeqCst) {
+            tokio::spawn(watcher::run(ctx, source::Lichess));
+        }
+    }
 
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        let connected = event.new == ConnectionStage::Connected;
+        server::GATEWAY_CONNECTED.store(connected, Ordering::SeqCst);
+    }
 
-        match message.edit(&ctx.http, edit_message).await {
-            Ok(_) => (),
-            Err(why) => println!("Error sending message: {why:?}"),
-        }
-        ()
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        debug!("Interaction received");
+        interactions::handle(ctx, interaction).await;
     }
 }
 
 #[hook]
 async fn ready(_: Context, _data_about_bot: serenity::model::gateway::Ready) {
-    println!("Bot is ready.");
-    println!("{}", _data_about_bot.user.name);
+    info!("Bot is ready.");
+    info!("{}", _data_about_bot.user.name);
     // print invite link
-    println!(
+    info!(
         "Invite link: {:?}",
         format!(
             "https://discord.com/api/oauth2/authorize?client_id={}&permissions=8&scope=bot",
@@ -397,20 +126,64 @@ async fn ready(_: Context, _data_about_bot: serenity::model::gateway::Ready) {
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+    logging::init("info");
+    if env::args().nth(1).as_deref() == Some("serve") {
+        return serve().await;
+    }
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
     // Set gateway intents, which decides what events the bot will be notified about
     let intents = GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::DIRECT_MESSAGES
         | GatewayIntents::MESSAGE_CONTENT;
 
+    // the API shares the bot's deep analysis slots
+    let deep_analyses = Arc::new(std::sync::Mutex::new(HashMap::new()));
+    tokio::spawn(server::run(deep_analyses.clone()));
+    tokio::spawn(server::run_probes());
+    tokio::spawn(link::run());
+
     // Create a new instance of the Client, logging in as a bot.
     let mut client = Client::builder(&token, intents)
         .event_handler(Handler)
+        .type_map_insert::<TrackedGames>(Arc::new(Mutex::new(HashSet::new())))
+        .type_map_insert::<DeepAnalyses>(deep_analyses)
         .await
         .expect("Err creating client");
 
+    // on SIGTERM or Ctrl-C stop taking work, let what's running finish and
+    // then close the gateway, which returns from `start` below
+    let shard_manager = client.shard_manager.clone();
+    let http = client.http.clone();
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        info!("Shutting down");
+        shutdown::drain(http).await;
+        shard_manager.shutdown_all().await;
+    });
+
     // Start listening for events by starting a single shard
     if let Err(why) = client.start().await {
-        println!("Client error: {why:?}");
+        error!("Client error: {why:?}");
+    }
+    // anything still running, local engines included, is dropped with the runtime
+    info!("Stopped");
+}
+
+/// `lichess-stockfish serve`: only the HTTP API and probes, for overlays and
+/// dashboards, without logging in to Discord.
+async fn serve() {
+    if env::var("API_ADDR").map_or(true, |addr| addr.is_empty()) {
+        error!("serve needs API_ADDR to listen on");
+        return;
     }
+    server::WITHOUT_GATEWAY.store(true, Ordering::SeqCst);
+
+    tokio::spawn(async {
+        shutdown::signal().await;
+        info!("Shutting down");
+        shutdown::stop();
+    });
+    let deep_analyses = Arc::new(std::sync::Mutex::new(HashMap::new()));
+    tokio::join!(server::run(deep_analyses), server::run_probes());
+    info!("Stopped");
 }
@@ -0,0 +1,145 @@
+use std::sync::LazyLock;
+
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, TextEncoder,
+};
+use tracing::warn;
+
+/// Slash commands and `!` commands handled, by name.
+pub static COMMANDS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "lichess_stockfish_commands_total",
+        "Commands handled, by name",
+        &["command"]
+    )
+    .unwrap()
+});
+
+/// Button clicks handled, by action.
+pub static INTERACTIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "lichess_stockfish_interactions_total",
+        "Button interactions handled, by action",
+        &["action"]
+    )
+    .unwrap()
+});
+
+pub static LICHESS_FETCH_SECONDS: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "lichess_stockfish_lichess_fetch_seconds",
+        "Time spent fetching pages and statuses from lichess"
+    )
+    .unwrap()
+});
+
+pub static LICHESS_FETCH_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "lichess_stockfish_lichess_fetch_failures_total",
+        "lichess requests that failed or didn't return 200"
+    )
+    .unwrap()
+});
+
+pub static CHESSCOM_FETCH_SECONDS: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "lichess_stockfish_chesscom_fetch_seconds",
+        "Time spent fetching games and archives from chess.com"
+    )
+    .unwrap()
+});
+
+pub static CHESSCOM_FETCH_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "lichess_stockfish_chesscom_fetch_failures_total",
+        "chess.com requests that failed or didn't return 200"
+    )
+    .unwrap()
+});
+
+/// Engine search time, by backend ("local" or "online").
+pub static ENGINE_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "lichess_stockfish_engine_seconds",
+        "Time spent waiting on the engine, by backend",
+        &["backend"],
+        vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0]
+    )
+    .unwrap()
+});
+
+pub static ENGINE_DEPTH: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "lichess_stockfish_engine_depth",
+        "Depth of each engine search",
+        vec![5.0, 10.0, 12.0, 15.0, 20.0, 25.0, 30.0]
+    )
+    .unwrap()
+});
+
+pub static RENDER_SECONDS: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "lichess_stockfish_render_seconds",
+        "Time spent drawing board images"
+    )
+    .unwrap()
+});
+
+/// Cache lookups, by cache and whether they hit.
+pub static CACHE_LOOKUPS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "lichess_stockfish_cache_lookups_total",
+        "Cache lookups, by cache and result",
+        &["cache", "result"]
+    )
+    .unwrap()
+});
+
+pub static DB_ERRORS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "lichess_stockfish_db_errors_total",
+        "Database connections and queries that failed"
+    )
+    .unwrap()
+});
+
+pub static API_RATE_LIMITED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "lichess_stockfish_api_rate_limited_total",
+        "API requests turned away for going over the rate limit"
+    )
+    .unwrap()
+});
+
+/// Registers every metric up front, so they're scraped as zero before
+/// anything has happened rather than missing.
+pub fn init() {
+    LazyLock::force(&COMMANDS);
+    LazyLock::force(&INTERACTIONS);
+    LazyLock::force(&LICHESS_FETCH_SECONDS);
+    LazyLock::force(&LICHESS_FETCH_FAILURES);
+    LazyLock::force(&CHESSCOM_FETCH_SECONDS);
+    LazyLock::force(&CHESSCOM_FETCH_FAILURES);
+    LazyLock::force(&ENGINE_SECONDS);
+    LazyLock::force(&ENGINE_DEPTH);
+    LazyLock::force(&RENDER_SECONDS);
+    LazyLock::force(&CACHE_LOOKUPS);
+    LazyLock::force(&DB_ERRORS);
+    LazyLock::force(&API_RATE_LIMITED);
+}
+
+/// Records a lookup in `cache`.
+pub fn cache_lookup(cache: &str, hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    CACHE_LOOKUPS.with_label_values(&[cache, result]).inc();
+}
+
+/// Everything registered so far, in the Prometheus text format.
+pub fn gather() -> String {
+    let mut buffer = vec![];
+    if let Err(why) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        warn!("Error encoding metrics: {why:?}");
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
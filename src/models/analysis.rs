@@ -0,0 +1,93 @@
+use chrono::NaiveDateTime;
+use diesel::{deserialize::Queryable, prelude::Insertable, Selectable};
+use serde::{Deserialize, Serialize};
+
+use crate::board::GetStockFishResponse;
+use crate::database::databse::{db_error, get_dbo};
+use crate::position::Variant;
+use crate::schema::analysis;
+use diesel::prelude::*;
+
+/// The last position we analysed for a message, so follow-up actions can
+/// re-use it without scraping lichess again.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = analysis)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct Analysis {
+    pub id: i32,
+    pub message_id: String,
+    pub fen: String,
+    pub orientation: String,
+    pub last_moves: String,
+    pub depth: i32,
+    pub evaluation: f32,
+    pub mate: Option<i32>,
+    pub bestmove: String,
+    pub continuation: String,
+    pub created_at: NaiveDateTime,
+    pub eco: Option<String>,
+    pub opening: Option<String>,
+    /// "standard", "chess960" and so on, see `Variant::as_str`.
+    pub variant: String,
+}
+
+impl Analysis {
+    pub fn new(message_id: String, resp: &GetStockFishResponse) -> Self {
+        let last_moves = resp
+            .last_moves
+            .iter()
+            .map(|(x, y)| format!("{},{}", x, y))
+            .collect::<Vec<String>>()
+            .join(";");
+
+        Self {
+            id: 0,
+            message_id,
+            fen: resp.fen.clone(),
+            orientation: resp.orientation.clone(),
+            last_moves,
+            depth: resp.depth as i32,
+            evaluation: resp.evaluation,
+            mate: resp.mate,
+            bestmove: resp.bestmove.clone(),
+            continuation: resp.continuation.clone(),
+            created_at: chrono::Utc::now().naive_utc(),
+            eco: resp.opening.as_ref().map(|opening| opening.eco.clone()),
+            opening: resp.opening.as_ref().map(|opening| opening.name.clone()),
+            variant: resp.variant.as_str().to_string(),
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        Variant::parse(&self.variant).unwrap_or_default()
+    }
+
+    /// Last move squares as the (left, top) percentages `gen_board` expects.
+    pub fn last_moves(&self) -> Vec<(f32, f32)> {
+        self.last_moves
+            .split(';')
+            .filter_map(|square| {
+                let (x, y) = square.split_once(',')?;
+                Some((x.parse().ok()?, y.parse().ok()?))
+            })
+            .collect()
+    }
+
+    pub fn insert(data: Analysis) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::insert_into(analysis::table)
+            .values(data)
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    pub fn find_latest_by_message_id(message_id: String) -> Result<Self, diesel::result::Error> {
+        let conn = &mut get_dbo();
+        analysis::table
+            .filter(analysis::message_id.eq(message_id))
+            .order(analysis::id.desc())
+            .first(conn)
+            .inspect_err(db_error)
+    }
+}
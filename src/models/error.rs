@@ -3,12 +3,16 @@ use std::fmt;
 #[derive(Debug)]
 pub enum Error {
 	BasicError(String),
+	/// What was asked about doesn't exist, or has no game to analyse.
+	NotFound(String),
+	/// The request itself is wrong, like a bad FEN or an illegal move.
+	Invalid(String),
 }
 
 impl fmt::Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match self {
-			Error::BasicError(e) => write!(f, "{}", e),
+			Error::BasicError(e) | Error::NotFound(e) | Error::Invalid(e) => write!(f, "{}", e),
 		}
 	}
 
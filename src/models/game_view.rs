@@ -0,0 +1,87 @@
+use chrono::NaiveDateTime;
+use diesel::{deserialize::Queryable, prelude::Insertable, Selectable};
+use serde::{Deserialize, Serialize};
+use shakmaty::Color;
+
+use crate::database::databse::{db_error, get_dbo};
+use crate::position;
+use crate::schema::game_view;
+use crate::source::GameRef;
+use diesel::prelude::*;
+
+/// The game a message was pointed at with `/game`, where `Message` holds the
+/// player for messages that follow whatever someone is playing.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = game_view)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct GameView {
+    pub id: i32,
+    pub message_id: String,
+    pub game_id: String,
+    pub ply: Option<i32>,
+    /// "w" or "b".
+    pub orientation: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl GameView {
+    pub fn new(message_id: String, game: &GameRef) -> Self {
+        Self {
+            id: 0,
+            message_id,
+            game_id: game.id.clone(),
+            ply: game.ply.map(|ply| ply as i32),
+            orientation: position::color_to_mode(game.orientation).to_string(),
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn insert(data: GameView) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::insert_into(game_view::table)
+            .values(data)
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    pub fn delete(&self) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::delete(game_view::table)
+            .filter(game_view::id.eq(self.id))
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    /// Moves the message to `ply`, or with `None` to the latest position.
+    pub fn set_ply(message_id: String, ply: Option<usize>) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::update(game_view::table)
+            .filter(game_view::message_id.eq(message_id))
+            .set(game_view::ply.eq(ply.map(|ply| ply as i32)))
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    pub fn find_by_message_id(message_id: String) -> Result<Self, diesel::result::Error> {
+        let conn = &mut get_dbo();
+        game_view::table
+            .filter(game_view::message_id.eq(message_id))
+            .first(conn)
+            .inspect_err(db_error)
+    }
+
+    pub fn game_ref(&self) -> GameRef {
+        GameRef {
+            id: self.game_id.clone(),
+            ply: self.ply.map(|ply| ply.max(0) as usize),
+            orientation: if self.orientation == "b" {
+                Color::Black
+            } else {
+                Color::White
+            },
+        }
+    }
+}
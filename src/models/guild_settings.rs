@@ -0,0 +1,117 @@
+use chrono::NaiveDateTime;
+use diesel::{deserialize::Queryable, prelude::Insertable, Selectable};
+use serde::{Deserialize, Serialize};
+
+use crate::database::databse::{db_error, get_dbo};
+use crate::fairplay::{FairPlay, DEFAULT_DELAY_MOVES};
+use crate::i18n::Locale;
+use crate::schema::guild_settings;
+use diesel::prelude::*;
+
+/// Settings a server's admins picked for the whole server.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = guild_settings)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct GuildSettings {
+    pub id: i32,
+    pub guild_id: String,
+    pub locale: Option<String>,
+    pub created_at: NaiveDateTime,
+    /// See `FairPlay::as_str`.
+    pub fair_play: String,
+    /// Moves behind for the "delay" mode.
+    pub fair_play_moves: i32,
+}
+
+impl GuildSettings {
+    pub fn new(guild_id: String) -> Self {
+        Self {
+            id: 0,
+            guild_id,
+            locale: None,
+            created_at: chrono::Utc::now().naive_utc(),
+            fair_play: FairPlay::default().as_str().to_string(),
+            fair_play_moves: DEFAULT_DELAY_MOVES as i32,
+        }
+    }
+
+    pub fn insert(data: GuildSettings) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::insert_into(guild_settings::table)
+            .values(data)
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    pub fn find_by_guild_id(guild_id: String) -> Result<Self, diesel::result::Error> {
+        let conn = &mut get_dbo();
+        guild_settings::table
+            .filter(guild_settings::guild_id.eq(guild_id))
+            .first(conn)
+            .inspect_err(db_error)
+    }
+
+    /// The server's language, if it set one we still have a catalog for.
+    pub fn locale(&self) -> Option<Locale> {
+        self.locale.as_deref().and_then(Locale::from_tag)
+    }
+
+    pub fn fair_play(&self) -> FairPlay {
+        FairPlay::from_settings(&self.fair_play, self.fair_play_moves)
+    }
+
+    /// Sets what members see of their own games in progress.
+    pub fn set_fair_play(guild_id: String, mode: FairPlay) -> Result<(), diesel::result::Error> {
+        let moves = match mode {
+            FairPlay::Delay(moves) => moves,
+            _ => DEFAULT_DELAY_MOVES,
+        } as i32;
+
+        match Self::find_by_guild_id(guild_id.clone()) {
+            Ok(settings) => {
+                let conn = &mut get_dbo();
+                diesel::update(guild_settings::table)
+                    .filter(guild_settings::id.eq(settings.id))
+                    .set((
+                        guild_settings::fair_play.eq(mode.as_str()),
+                        guild_settings::fair_play_moves.eq(moves),
+                    ))
+                    .execute(conn)
+                    .inspect_err(db_error)?;
+                Ok(())
+            }
+            Err(diesel::result::Error::NotFound) => Self::insert(Self {
+                fair_play: mode.as_str().to_string(),
+                fair_play_moves: moves,
+                ..Self::new(guild_id)
+            }),
+            Err(why) => Err(why),
+        }
+    }
+
+    /// Sets or, with `None`, clears the server's language.
+    pub fn set_locale(
+        guild_id: String,
+        locale: Option<Locale>,
+    ) -> Result<(), diesel::result::Error> {
+        let locale = locale.map(|locale| locale.as_str().to_string());
+
+        match Self::find_by_guild_id(guild_id.clone()) {
+            Ok(settings) => {
+                let conn = &mut get_dbo();
+                diesel::update(guild_settings::table)
+                    .filter(guild_settings::id.eq(settings.id))
+                    .set(guild_settings::locale.eq(locale))
+                    .execute(conn)
+                    .inspect_err(db_error)?;
+                Ok(())
+            }
+            Err(diesel::result::Error::NotFound) => Self::insert(Self {
+                locale,
+                ..Self::new(guild_id)
+            }),
+            Err(why) => Err(why),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::NaiveDateTime;
+use diesel::{deserialize::Queryable, prelude::Insertable, Selectable};
+use serde::{Deserialize, Serialize};
+
+use crate::database::databse::{db_error, get_dbo};
+use crate::schema::linked_account;
+use diesel::prelude::*;
+
+/// The lichess account a Discord user says is theirs, so we know which games
+/// in progress not to help them with. Accounts linked by signing in with
+/// lichess are `verified`, older ones were only typed in.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = linked_account)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct LinkedAccount {
+    pub id: i32,
+    pub user_id: String,
+    pub lichess_user: String,
+    pub created_at: NaiveDateTime,
+    pub verified: bool,
+}
+
+impl LinkedAccount {
+    pub fn new(user_id: String, lichess_user: String, verified: bool) -> Self {
+        Self {
+            id: 0,
+            user_id,
+            lichess_user: lichess_user.to_lowercase(),
+            created_at: chrono::Utc::now().naive_utc(),
+            verified,
+        }
+    }
+
+    pub fn insert(data: LinkedAccount) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::insert_into(linked_account::table)
+            .values(data)
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    pub fn delete(&self) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::delete(linked_account::table)
+            .filter(linked_account::id.eq(self.id))
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    pub fn find_by_user_id(user_id: String) -> Result<Self, diesel::result::Error> {
+        let conn = &mut get_dbo();
+        linked_account::table
+            .filter(linked_account::user_id.eq(user_id))
+            .first(conn)
+            .inspect_err(db_error)
+    }
+
+    pub fn find_by_lichess_user(lichess_user: String) -> Result<Self, diesel::result::Error> {
+        let conn = &mut get_dbo();
+        linked_account::table
+            .filter(linked_account::lichess_user.eq(lichess_user.to_lowercase()))
+            .first(conn)
+            .inspect_err(db_error)
+    }
+
+    /// Links `user_id` to `lichess_user`, which they signed in as, replacing
+    /// any account they linked before.
+    pub fn link(user_id: String, lichess_user: String) -> Result<(), diesel::result::Error> {
+        if let Ok(linked) = Self::find_by_user_id(user_id.clone()) {
+            linked.delete()?;
+        }
+        Self::insert(Self::new(user_id, lichess_user, true))
+    }
+}
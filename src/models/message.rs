@@ -2,8 +2,9 @@ use chrono::NaiveDateTime;
 use diesel::{deserialize::Queryable, prelude::Insertable, Selectable};
 use serde::{Deserialize, Serialize};
 
-use crate::database::databse::get_dbo;
+use crate::database::databse::{db_error, get_dbo};
 use crate::schema::message;
+use crate::source::{Platform, Player};
 use diesel::prelude::*;
 
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Clone, Debug)]
@@ -14,23 +15,33 @@ pub struct Message {
     pub message_id: String,
     pub lc_channel: String,
     pub created_at: NaiveDateTime,
+    /// Which site `lc_channel` plays on, see `Platform::as_str`.
+    pub platform: String,
 }
 
 impl Message {
-    pub fn new(lc_channel: String, message_id: String) -> Self {
+    pub fn new(player: &Player, message_id: String) -> Self {
         Self {
             id: 0,
             message_id,
-            lc_channel,
+            lc_channel: player.name.clone(),
             created_at: chrono::Utc::now().naive_utc(),
+            platform: player.platform.as_str().to_string(),
         }
     }
 
+    /// The player whose game the message shows.
+    pub fn player(&self) -> Player {
+        let platform = Platform::parse(&self.platform).unwrap_or_default();
+        Player::new(platform, &self.lc_channel)
+    }
+
     pub fn insert(data: Message) -> Result<(), diesel::result::Error> {
         let conn = &mut get_dbo();
         diesel::insert_into(message::table)
             .values(data)
-            .execute(conn)?;
+            .execute(conn)
+            .inspect_err(db_error)?;
         Ok(())
     }
 
@@ -38,7 +49,8 @@ impl Message {
         let conn = &mut get_dbo();
         diesel::delete(message::table)
             .filter(message::id.eq(self.id))
-            .execute(conn)?;
+            .execute(conn)
+            .inspect_err(db_error)?;
         Ok(())
     }
 
@@ -49,14 +61,16 @@ impl Message {
             .set((
                 message::message_id.eq(data.message_id),
                 message::lc_channel.eq(data.lc_channel),
+                message::platform.eq(data.platform),
             ))
-            .execute(conn)?;
+            .execute(conn)
+            .inspect_err(db_error)?;
         Ok(())
     }
 
     pub fn find_all() -> Result<Vec<Self>, diesel::result::Error> {
         let conn = &mut get_dbo();
-        message::table.load(conn)
+        message::table.load(conn).inspect_err(db_error)
     }
 
     pub fn find_by_channel_id(channel_id: String) -> Result<Self, diesel::result::Error> {
@@ -64,5 +78,6 @@ impl Message {
         message::table
             .filter(message::message_id.eq(channel_id))
             .first(conn)
+            .inspect_err(db_error)
     }
 }
@@ -0,0 +1,78 @@
+use chrono::NaiveDateTime;
+use diesel::{deserialize::Queryable, prelude::Insertable, Selectable};
+use serde::{Deserialize, Serialize};
+
+use crate::database::databse::{db_error, get_dbo};
+use crate::schema::subscription;
+use diesel::prelude::*;
+
+/// A Discord channel that wants a post whenever `lc_user` starts a game.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = subscription)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct Subscription {
+    pub id: i32,
+    pub channel_id: String,
+    pub lc_user: String,
+    pub live: bool,
+    pub last_game_id: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl Subscription {
+    pub fn new(channel_id: String, lc_user: String, live: bool) -> Self {
+        Self {
+            id: 0,
+            channel_id,
+            lc_user: lc_user.to_lowercase(),
+            live,
+            last_game_id: None,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn insert(data: Subscription) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::insert_into(subscription::table)
+            .values(data)
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    pub fn delete(&self) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::delete(subscription::table)
+            .filter(subscription::id.eq(self.id))
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    pub fn set_last_game_id(&self, game_id: String) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::update(subscription::table)
+            .filter(subscription::id.eq(self.id))
+            .set(subscription::last_game_id.eq(Some(game_id)))
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    pub fn find_all() -> Result<Vec<Self>, diesel::result::Error> {
+        let conn = &mut get_dbo();
+        subscription::table.load(conn).inspect_err(db_error)
+    }
+
+    pub fn find_by_channel_and_user(
+        channel_id: String,
+        lc_user: String,
+    ) -> Result<Self, diesel::result::Error> {
+        let conn = &mut get_dbo();
+        subscription::table
+            .filter(subscription::channel_id.eq(channel_id))
+            .filter(subscription::lc_user.eq(lc_user.to_lowercase()))
+            .first(conn)
+            .inspect_err(db_error)
+    }
+}
@@ -0,0 +1,78 @@
+use chrono::NaiveDateTime;
+use diesel::{deserialize::Queryable, prelude::Insertable, Selectable};
+use serde::{Deserialize, Serialize};
+
+use crate::database::databse::{db_error, get_dbo};
+use crate::i18n::Locale;
+use crate::schema::user_settings;
+use diesel::prelude::*;
+
+/// Settings a Discord user picked for themselves, which win over their
+/// server's.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = user_settings)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct UserSettings {
+    pub id: i32,
+    pub user_id: String,
+    pub locale: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl UserSettings {
+    pub fn new(user_id: String) -> Self {
+        Self {
+            id: 0,
+            user_id,
+            locale: None,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn insert(data: UserSettings) -> Result<(), diesel::result::Error> {
+        let conn = &mut get_dbo();
+        diesel::insert_into(user_settings::table)
+            .values(data)
+            .execute(conn)
+            .inspect_err(db_error)?;
+        Ok(())
+    }
+
+    pub fn find_by_user_id(user_id: String) -> Result<Self, diesel::result::Error> {
+        let conn = &mut get_dbo();
+        user_settings::table
+            .filter(user_settings::user_id.eq(user_id))
+            .first(conn)
+            .inspect_err(db_error)
+    }
+
+    /// The user's language, if they set one we still have a catalog for.
+    pub fn locale(&self) -> Option<Locale> {
+        self.locale.as_deref().and_then(Locale::from_tag)
+    }
+
+    /// Sets or, with `None`, clears the user's language.
+    pub fn set_locale(
+        user_id: String,
+        locale: Option<Locale>,
+    ) -> Result<(), diesel::result::Error> {
+        let locale = locale.map(|locale| locale.as_str().to_string());
+
+        match Self::find_by_user_id(user_id.clone()) {
+            Ok(settings) => {
+                let conn = &mut get_dbo();
+                diesel::update(user_settings::table)
+                    .filter(user_settings::id.eq(settings.id))
+                    .set(user_settings::locale.eq(locale))
+                    .execute(conn)
+                    .inspect_err(db_error)?;
+                Ok(())
+            }
+            Err(diesel::result::Error::NotFound) => Self::insert(Self {
+                locale,
+                ..Self::new(user_id)
+            }),
+            Err(why) => Err(why),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::env;
+
+use shakmaty::san::SanPlus;
+use shakmaty::{Color, Position};
+
+use crate::i18n::{self, Locale};
+use crate::position::{self, Variant};
+
+/// Whether pieces are drawn as figurines (`♘f3`) instead of letters (`Nf3`),
+/// set with `NOTATION=figurine`.
+pub fn figurine() -> bool {
+    env::var("NOTATION").is_ok_and(|notation| notation.eq_ignore_ascii_case("figurine"))
+}
+
+fn to_figurine(san: &str) -> String {
+    san.chars()
+        .map(|c| match c {
+            'K' => '♔',
+            'Q' => '♕',
+            'R' => '♖',
+            'B' => '♗',
+            'N' => '♘',
+            c => c,
+        })
+        .collect()
+}
+
+fn styled(san: String) -> String {
+    if figurine() {
+        to_figurine(&san)
+    } else {
+        san
+    }
+}
+
+/// `san`, or any text made of SAN moves, with the piece letters of
+/// `locale`, e.g. `Sf3` in German. Figurines read the same in every
+/// language, so they're left alone.
+pub fn localize(san: &str, locale: Locale) -> String {
+    if locale == Locale::English || figurine() {
+        return san.to_string();
+    }
+
+    let letters = i18n::piece_letters(locale);
+    san.chars()
+        .map(|c| match c {
+            'K' => letters[0],
+            'Q' => letters[1],
+            'R' => letters[2],
+            'B' => letters[3],
+            'N' => letters[4],
+            c => c,
+        })
+        .collect()
+}
+
+/// `uci` as SAN in `pos`, with check and mate markers. `None` if the move
+/// isn't legal there.
+pub fn uci_to_san<P: Position + Clone>(pos: &P, uci: &str) -> Option<String> {
+    let m = position::from_uci(pos, uci).ok()?;
+    Some(styled(SanPlus::from_move(pos.clone(), m).to_string()))
+}
+
+/// The engine's best move from `fen`, a position of `variant`, in SAN,
+/// falling back to the raw move.
+pub fn best_move_to_san(fen: &str, variant: Variant, uci: &str) -> String {
+    position::parse_variant_fen(fen, variant)
+        .and_then(|pos| uci_to_san(&pos, uci))
+        .unwrap_or_else(|| uci.to_string())
+}
+
+/// A space separated line of UCI moves from `fen` as numbered SAN, e.g.
+/// `12...Nc6 13.Bb5 a6`. Stops at the first move that doesn't parse.
+pub fn line_to_san(fen: &str, variant: Variant, line: &str) -> String {
+    let mut pos = match position::parse_variant_fen(fen, variant) {
+        Some(pos) => pos,
+        None => return line.to_string(),
+    };
+
+    let mut moves = vec![];
+    for (i, uci) in line.split_whitespace().enumerate() {
+        let m = match position::from_uci(&pos, uci) {
+            Ok(m) => m,
+            Err(_) => break,
+        };
+        let number = pos.fullmoves();
+        let turn = pos.turn();
+        let san = styled(SanPlus::from_move_and_play_unchecked(&mut pos, m).to_string());
+
+        match turn {
+            Color::White => moves.push(format!("{}.{}", number, san)),
+            Color::Black if i == 0 => moves.push(format!("{}...{}", number, san)),
+            Color::Black => moves.push(san),
+        }
+    }
+
+    if moves.is_empty() {
+        return line.to_string();
+    }
+
+    moves.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn san(fen: &str, uci: &str) -> Option<String> {
+        uci_to_san(&position::parse_fen(fen).unwrap(), uci)
+    }
+
+    #[test]
+    fn knight_moves_are_disambiguated() {
+        // both knights reach d2 once the pawn has gone
+        let fen = "rnbqkbnr/pppppppp/8/8/3P4/5N2/PPP1PPPP/RNBQKB1R w KQkq - 0 1";
+        assert_eq!(san(fen, "b1d2").as_deref(), Some("Nbd2"));
+        assert_eq!(san(fen, "f3d2").as_deref(), Some("Nfd2"));
+        assert_eq!(san(START, "g1f3").as_deref(), Some("Nf3"));
+    }
+
+    #[test]
+    fn promotions_and_checks() {
+        assert_eq!(
+            san("8/4P3/8/8/8/8/k7/4K3 w - - 0 1", "e7e8q").as_deref(),
+            Some("e8=Q")
+        );
+        assert_eq!(
+            san("8/4P3/8/8/k7/8/8/4K3 w - - 0 1", "e7e8q").as_deref(),
+            Some("e8=Q+")
+        );
+        assert_eq!(
+            san("8/4P3/8/8/8/8/k7/4K3 w - - 0 1", "e7e8n").as_deref(),
+            Some("e8=N")
+        );
+    }
+
+    #[test]
+    fn castling_either_way_engines_write_it() {
+        let fen = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(san(fen, "e1g1").as_deref(), Some("O-O"));
+        assert_eq!(san(fen, "e1h1").as_deref(), Some("O-O"));
+        assert_eq!(san(fen, "e1c1").as_deref(), Some("O-O-O"));
+    }
+
+    #[test]
+    fn illegal_and_missing_moves() {
+        assert_eq!(san(START, "e2e5"), None);
+        assert_eq!(san(START, "(none)"), None);
+        assert_eq!(best_move_to_san(START, Variant::Standard, "e2e4"), "e4");
+        // the raw move is better than nothing
+        assert_eq!(
+            best_move_to_san(START, Variant::Standard, "(none)"),
+            "(none)"
+        );
+        assert_eq!(best_move_to_san(START, Variant::Standard, "e2e5"), "e2e5");
+    }
+
+    #[test]
+    fn lines_are_numbered_from_the_side_to_move() {
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 12";
+        assert_eq!(
+            line_to_san(fen, Variant::Standard, "b8c6 f1b5 a7a6"),
+            "12...Nc6 13.Bb5 a6"
+        );
+        assert_eq!(
+            line_to_san(START, Variant::Standard, "e2e4 e7e5 g1f3"),
+            "1.e4 e5 2.Nf3"
+        );
+    }
+
+    #[test]
+    fn lines_stop_at_the_first_illegal_move() {
+        assert_eq!(
+            line_to_san(START, Variant::Standard, "e2e4 e7e5 e4e5 g1f3"),
+            "1.e4 e5"
+        );
+        // nothing readable, so the line is passed on as it came
+        assert_eq!(line_to_san(START, Variant::Standard, "(none)"), "(none)");
+        assert_eq!(line_to_san(START, Variant::Standard, ""), "");
+    }
+
+    #[test]
+    fn localized_piece_letters() {
+        assert_eq!(localize("Nbd2 e8=Q O-O", Locale::English), "Nbd2 e8=Q O-O");
+        assert_eq!(localize("Nbd2 e8=Q O-O", Locale::German), "Sbd2 e8=D O-O");
+        assert_eq!(localize("Kxb2 Bb5", Locale::Spanish), "Rxb2 Ab5");
+    }
+}
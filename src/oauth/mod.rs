@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::digest::{digest, SHA256};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::metrics;
+use crate::models::error::Error;
+use crate::source::fetch;
+
+/// Where lichess lives, unless `LICHESS_OAUTH_URL` points at a stand-in.
+const LICHESS_URL: &str = "https://lichess.org";
+
+/// A PKCE verifier and the S256 challenge sent ahead of it. lichess has no
+/// client secrets, the verifier proves the token request comes from whoever
+/// started the sign-in.
+#[derive(Debug, Clone)]
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    pub fn new() -> Self {
+        // 64 hex digits, inside the 43 to 128 unreserved characters allowed
+        let verifier = format!(
+            "{}{}",
+            uuid::Uuid::new_v4().to_simple(),
+            uuid::Uuid::new_v4().to_simple()
+        );
+        Self::from_verifier(verifier)
+    }
+
+    pub fn from_verifier(verifier: String) -> Self {
+        let challenge = URL_SAFE_NO_PAD.encode(digest(&SHA256, verifier.as_bytes()));
+        Self {
+            verifier,
+            challenge,
+        }
+    }
+}
+
+impl Default for Pkce {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sign-ins in progress, keyed by the `state` they were sent off with, each
+/// holding its PKCE verifier and whatever `T` is needed to finish it.
+pub struct SignIns<T> {
+    timeout: Duration,
+    pending: Mutex<HashMap<String, SignIn<T>>>,
+}
+
+struct SignIn<T> {
+    verifier: String,
+    started: Instant,
+    data: T,
+}
+
+impl<T> SignIns<T> {
+    /// Sign-ins not finished within `timeout` are forgotten.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts a sign-in for `data`: the `state` and PKCE pair to send.
+    pub fn start(&self, data: T) -> (String, Pkce) {
+        let state = uuid::Uuid::new_v4().to_simple().to_string();
+        let pkce = Pkce::new();
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, sign_in| sign_in.started.elapsed() < self.timeout);
+        pending.insert(
+            state.clone(),
+            SignIn {
+                verifier: pkce.verifier.clone(),
+                started: Instant::now(),
+                data,
+            },
+        );
+        (state, pkce)
+    }
+
+    /// The verifier and data of the sign-in `state` came from, once, and
+    /// only if it hasn't expired.
+    pub fn finish(&self, state: &str) -> Option<(String, T)> {
+        self.pending
+            .lock()
+            .unwrap()
+            .remove(state)
+            .filter(|sign_in| sign_in.started.elapsed() < self.timeout)
+            .map(|sign_in| (sign_in.verifier, sign_in.data))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Token {
+    access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Account {
+    username: String,
+}
+
+/// Signs people in with lichess to find out which account is theirs.
+#[derive(Debug, Clone)]
+pub struct LichessOAuth {
+    pub base_url: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+}
+
+impl LichessOAuth {
+    /// Reads `OAUTH_REDIRECT_URL`, `LICHESS_OAUTH_CLIENT_ID` and
+    /// `LICHESS_OAUTH_URL`. `None` without a redirect url, there's nowhere
+    /// for lichess to send people back to.
+    pub fn from_env() -> Option<Self> {
+        let redirect_uri = env::var("OAUTH_REDIRECT_URL")
+            .ok()
+            .filter(|url| !url.is_empty())?;
+        let client_id = env::var("LICHESS_OAUTH_CLIENT_ID")
+            .ok()
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string());
+        let base_url = env::var("LICHESS_OAUTH_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| LICHESS_URL.to_string());
+        Some(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client_id,
+            redirect_uri,
+        })
+    }
+
+    /// The page to send someone to, which comes back to the redirect url
+    /// with a code and `state`.
+    pub fn authorize_url(&self, pkce: &Pkce, state: &str) -> Result<String, Error> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/oauth", self.base_url),
+            &[
+                ("response_type", "code"),
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("code_challenge_method", "S256"),
+                ("code_challenge", pkce.challenge.as_str()),
+                ("state", state),
+            ],
+        )
+        .map_err(|e| Error::BasicError(format!("Error building the sign-in url {:?}", e)))?;
+        Ok(url.to_string())
+    }
+
+    /// Trades the code lichess sent back for the username of whoever signed
+    /// in. The token is only needed for that, so it's revoked after.
+    pub async fn username(&self, code: &str, verifier: &str) -> Result<String, Error> {
+        let client = reqwest::Client::new();
+        let request = client.post(format!("{}/api/token", self.base_url)).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("code_verifier", verifier),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("client_id", self.client_id.as_str()),
+        ]);
+        let token: Token = parse(self.send(request).await?)?;
+
+        let request = client
+            .get(format!("{}/api/account", self.base_url))
+            .bearer_auth(&token.access_token);
+        let account = self.send(request).await.and_then(parse::<Account>);
+
+        // lichess answers 204, which `fetch` would count as a failure
+        let revoked = client
+            .delete(format!("{}/api/token", self.base_url))
+            .bearer_auth(&token.access_token)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        if let Err(why) = revoked {
+            warn!("Couldn't revoke a lichess token: {:?}", why);
+        }
+
+        Ok(account?.username)
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<String, Error> {
+        let body = fetch(
+            request,
+            &metrics::LICHESS_FETCH_SECONDS,
+            &metrics::LICHESS_FETCH_FAILURES,
+        )
+        .await?;
+        body.ok_or_else(|| Error::BasicError(format!("{} answered 404", self.base_url)))
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(body: String) -> Result<T, Error> {
+    serde_json::from_str(&body)
+        .map_err(|e| Error::BasicError(format!("Error parsing lichess response {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::extract::Form;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+
+    #[test]
+    fn challenge_matches_rfc_7636() {
+        // the S256 example in appendix B
+        let pkce = Pkce::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string());
+        assert_eq!(
+            pkce.challenge,
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn verifiers_are_long_enough_and_differ() {
+        let (a, b) = (Pkce::new(), Pkce::new());
+        assert!((43..=128).contains(&a.verifier.len()));
+        assert!(a.verifier.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(a.verifier, b.verifier);
+        assert_eq!(
+            a.challenge,
+            Pkce::from_verifier(a.verifier.clone()).challenge
+        );
+    }
+
+    #[test]
+    fn sign_ins_finish_once() {
+        let sign_ins = SignIns::new(Duration::from_secs(600));
+        let (state, pkce) = sign_ins.start(7);
+
+        assert_eq!(sign_ins.finish("someone-elses"), None);
+        assert_eq!(sign_ins.finish(&state), Some((pkce.verifier, 7)));
+        assert_eq!(sign_ins.finish(&state), None);
+    }
+
+    #[test]
+    fn sign_ins_expire() {
+        let sign_ins = SignIns::new(Duration::ZERO);
+        let (state, _) = sign_ins.start(7);
+        assert_eq!(sign_ins.finish(&state), None);
+    }
+
+    fn oauth(base_url: String) -> LichessOAuth {
+        LichessOAuth {
+            base_url,
+            client_id: "lichess-stockfish".to_string(),
+            redirect_uri: "https://bot.example/oauth/callback".to_string(),
+        }
+    }
+
+    #[test]
+    fn authorize_url_carries_the_challenge_and_state() {
+        let pkce = Pkce::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string());
+        let url = oauth("https://lichess.org".to_string())
+            .authorize_url(&pkce, "abc")
+            .unwrap();
+        let url = reqwest::Url::parse(&url).unwrap();
+        let params: HashMap<_, _> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(url.path(), "/oauth");
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["client_id"], "lichess-stockfish");
+        assert_eq!(params["redirect_uri"], "https://bot.example/oauth/callback");
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_eq!(params["code_challenge"], pkce.challenge);
+        assert_eq!(params["state"], "abc");
+    }
+
+    /// A stand-in for lichess that hands out a token for code "good" and
+    /// the verifier "verifier", and says it belongs to "Thibault".
+    async fn stand_in() -> String {
+        async fn token(
+            Form(form): Form<HashMap<String, String>>,
+        ) -> Result<Json<serde_json::Value>, StatusCode> {
+            let expected = [
+                ("grant_type", "authorization_code"),
+                ("code", "good"),
+                ("code_verifier", "verifier"),
+                ("client_id", "lichess-stockfish"),
+            ];
+            if expected
+                .iter()
+                .any(|(key, value)| form.get(*key).map(String::as_str) != Some(value))
+            {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            Ok(Json(serde_json::json!({
+                "token_type": "Bearer",
+                "access_token": "lio_token",
+                "expires_in": 31536000
+            })))
+        }
+
+        async fn account(headers: HeaderMap) -> Result<Json<serde_json::Value>, StatusCode> {
+            match headers.get("authorization").and_then(|v| v.to_str().ok()) {
+                Some("Bearer lio_token") => Ok(Json(serde_json::json!({
+                    "id": "thibault",
+                    "username": "Thibault"
+                }))),
+                _ => Err(StatusCode::UNAUTHORIZED),
+            }
+        }
+
+        let app = Router::new()
+            .route(
+                "/api/token",
+                post(token).delete(|| async { StatusCode::NO_CONTENT }),
+            )
+            .route("/api/account", get(account));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn code_is_traded_for_the_username() {
+        let oauth = oauth(stand_in().await);
+        assert_eq!(
+            oauth.username("good", "verifier").await.unwrap(),
+            "Thibault"
+        );
+    }
+
+    #[tokio::test]
+    async fn wrong_verifier_is_refused() {
+        let oauth = oauth(stand_in().await);
+        assert!(oauth.username("good", "someone-elses").await.is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use shakmaty::Position;
+use tracing::warn;
+
+use crate::position::{self, Ply};
+
+/// Openings we ship with: a hand-picked 123 of the most common lines, not
+/// the full ECO. They're split by ECO volume in the `eco`, `name`, `pgn` TSV
+/// layout of lichess' chess-openings files, so for everything else point
+/// `OPENINGS_PATH` at a checkout of https://github.com/lichess-org/chess-openings.
+const BUNDLED: [&str; 5] = [
+    include_str!("../assets/openings/a.tsv"),
+    include_str!("../assets/openings/b.tsv"),
+    include_str!("../assets/openings/c.tsv"),
+    include_str!("../assets/openings/d.tsv"),
+    include_str!("../assets/openings/e.tsv"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Opening {
+    pub eco: String,
+    pub name: String,
+}
+
+impl Opening {
+    /// "B90 Sicilian Defense: Najdorf Variation"
+    pub fn label(&self) -> String {
+        format!("{} {}", self.eco, self.name)
+    }
+}
+
+static OPENINGS: OnceLock<HashMap<String, Opening>> = OnceLock::new();
+
+/// Piece placement and side to move, which is all a scraped board reliably
+/// tells us. Castling rights and en passant are left out so those positions
+/// still match.
+fn key(fen: &str) -> Option<String> {
+    let mut fields = fen.split_whitespace();
+    let placement = fields.next()?;
+    let turn = fields.next().unwrap_or("w");
+    Some(format!("{} {}", placement, turn))
+}
+
+fn load_tsv(openings: &mut HashMap<String, Opening>, tsv: &str) {
+    for line in tsv.lines().skip(1) {
+        let columns: Vec<&str> = line.split('\t').collect();
+        if columns.len() < 3 {
+            continue;
+        }
+
+        let plies = match position::replay(&position::pgn_sans(columns[2])) {
+            Ok(plies) => plies,
+            Err(why) => {
+                warn!("Skipping opening {:?}: {}", columns[1], why);
+                continue;
+            }
+        };
+
+        let fen = position::to_fen(&position::final_position(&plies));
+        if let Some(key) = key(&fen) {
+            openings.insert(
+                key,
+                Opening {
+                    eco: columns[0].to_string(),
+                    name: columns[1].to_string(),
+                },
+            );
+        }
+    }
+}
+
+/// The bundled openings, plus every `.tsv` in `OPENINGS_PATH` if set, so
+/// newer or extra files can be dropped in without a rebuild.
+fn openings() -> &'static HashMap<String, Opening> {
+    OPENINGS.get_or_init(|| {
+        let mut openings = HashMap::new();
+        for tsv in BUNDLED {
+            load_tsv(&mut openings, tsv);
+        }
+
+        if let Ok(dir) = env::var("OPENINGS_PATH") {
+            match fs::read_dir(&dir) {
+                Ok(entries) => {
+                    let mut paths: Vec<_> = entries
+                        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                        .filter(|path| path.extension().is_some_and(|ext| ext == "tsv"))
+                        .collect();
+                    paths.sort();
+
+                    for path in paths {
+                        match fs::read_to_string(&path) {
+                            Ok(tsv) => load_tsv(&mut openings, &tsv),
+                            Err(why) => warn!("Error reading {:?}: {why:?}", path),
+                        }
+                    }
+                }
+                Err(why) => warn!("Error reading OPENINGS_PATH {:?}: {why:?}", dir),
+            }
+        }
+
+        openings
+    })
+}
+
+/// The opening whose position is `fen`, if it's a known one.
+pub fn lookup_fen(fen: &str) -> Option<Opening> {
+    openings().get(&key(fen)?).cloned()
+}
+
+/// The deepest known opening reached while playing `plies`. Positions are
+/// compared rather than move orders, so transpositions are found too.
+pub fn lookup_moves<P: Position + Clone>(plies: &[Ply<P>]) -> Option<Opening> {
+    let mut found = None;
+
+    for ply in plies {
+        let mut pos = ply.before.clone();
+        pos.play_unchecked(ply.played);
+        if let Some(opening) = lookup_fen(&position::to_fen(&pos)) {
+            found = Some(opening);
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_bundled_opening_replays() {
+        for tsv in BUNDLED {
+            assert!(tsv.starts_with("eco\tname\tpgn"));
+            for line in tsv.lines().skip(1) {
+                let pgn = line.split('\t').nth(2).unwrap();
+                assert!(
+                    position::replay(&position::pgn_sans(pgn)).is_ok(),
+                    "{}",
+                    line
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn openings_are_found_by_position() {
+        let sans = position::pgn_sans("1. e4 c5 2. Nf3 d6 3. d4 cxd4 4. Nxd4 Nf6 5. Nc3 a6");
+        let plies = position::replay(&sans).unwrap();
+        let opening = lookup_moves(&plies).unwrap();
+        assert_eq!(opening.label(), "B90 Sicilian Defense: Najdorf Variation");
+    }
+}
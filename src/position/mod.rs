@@ -0,0 +1,320 @@
+use serde::{Deserialize, Serialize};
+use shakmaty::fen::Fen;
+use shakmaty::san::SanPlus;
+use shakmaty::uci::UciMove;
+use shakmaty::variant::VariantPosition;
+use shakmaty::{
+    CastlingMode, Chess, Color, EnPassantMode, Move, Position, PositionError, Role, Square,
+};
+
+use crate::models::error::Error;
+
+/// The variants we can analyse. Anything else a site sends is refused by
+/// `Variant::parse` rather than read as standard chess.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    #[default]
+    Standard,
+    Chess960,
+    Crazyhouse,
+    KingOfTheHill,
+    ThreeCheck,
+}
+
+impl Variant {
+    /// Reads a variant as lichess names it in the `Variant` PGN tag or
+    /// chess.com in a game's `rules`, e.g. "King of the Hill" or
+    /// "kingofthehill". Games set up from a position play by standard rules.
+    pub fn parse(name: &str) -> Result<Variant, Error> {
+        let key: String = name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        match key.as_str() {
+            "" | "standard" | "chess" | "fromposition" => Ok(Variant::Standard),
+            "chess960" => Ok(Variant::Chess960),
+            "crazyhouse" => Ok(Variant::Crazyhouse),
+            "kingofthehill" => Ok(Variant::KingOfTheHill),
+            "threecheck" | "3check" => Ok(Variant::ThreeCheck),
+            _ => Err(Error::Invalid(format!(
+                "{} games can't be analysed",
+                name.trim()
+            ))),
+        }
+    }
+
+    /// How the variant is stored, "standard", "chess960" and so on.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Variant::Standard => "standard",
+            Variant::Chess960 => "chess960",
+            Variant::Crazyhouse => "crazyhouse",
+            Variant::KingOfTheHill => "kingofthehill",
+            Variant::ThreeCheck => "threecheck",
+        }
+    }
+
+    /// The variant's own name, for people.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::Chess960 => "Chess960",
+            Variant::Crazyhouse => "Crazyhouse",
+            Variant::KingOfTheHill => "King of the Hill",
+            Variant::ThreeCheck => "Three-check",
+        }
+    }
+
+    /// The engine's `UCI_Variant` value. Chess960 is played by the standard
+    /// rules with `UCI_Chess960` on instead.
+    pub fn uci_variant(&self) -> &'static str {
+        match self {
+            Variant::Standard | Variant::Chess960 => "chess",
+            Variant::Crazyhouse => "crazyhouse",
+            Variant::KingOfTheHill => "kingofthehill",
+            Variant::ThreeCheck => "3check",
+        }
+    }
+
+    pub fn castling_mode(&self) -> CastlingMode {
+        match self {
+            Variant::Chess960 => CastlingMode::Chess960,
+            _ => CastlingMode::Standard,
+        }
+    }
+
+    fn rules(&self) -> shakmaty::variant::Variant {
+        match self {
+            Variant::Standard | Variant::Chess960 => shakmaty::variant::Variant::Chess,
+            Variant::Crazyhouse => shakmaty::variant::Variant::Crazyhouse,
+            Variant::KingOfTheHill => shakmaty::variant::Variant::KingOfTheHill,
+            Variant::ThreeCheck => shakmaty::variant::Variant::ThreeCheck,
+        }
+    }
+
+    /// The usual starting position, which Chess960 games replace with a
+    /// `FEN` tag.
+    pub fn starting_position(&self) -> VariantPosition {
+        VariantPosition::new(self.rules())
+    }
+}
+
+/// One ply of a replayed game: the position before the move and the move played.
+#[derive(Debug, Clone)]
+pub struct Ply<P = Chess> {
+    pub before: P,
+    pub san: String,
+    pub played: Move,
+}
+
+/// Reads a tag like `[White "name"]` from a PGN.
+pub fn pgn_tag(pgn: &str, name: &str) -> Option<String> {
+    let prefix = format!("[{} \"", name);
+    pgn.lines()
+        .map(str::trim)
+        .find(|line| line.starts_with(&prefix))
+        .and_then(|line| line[prefix.len()..].strip_suffix("\"]"))
+        .map(str::to_string)
+}
+
+/// Pulls the mainline SAN moves out of a PGN, skipping tags, comments,
+/// variations, move numbers, NAGs and the result.
+pub fn pgn_sans(pgn: &str) -> Vec<String> {
+    let movetext: String = pgn
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('['))
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    let mut sans = vec![];
+    let mut comment = false;
+    let mut variation = 0;
+    let mut token = String::new();
+
+    let flush = |token: &mut String, sans: &mut Vec<String>| {
+        let is_result = matches!(token.as_str(), "1-0" | "0-1" | "1/2-1/2" | "*");
+        let word = token
+            .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+            .trim_end_matches(['!', '?']);
+        if !word.is_empty() && !word.starts_with('$') && !is_result {
+            sans.push(word.to_string());
+        }
+        token.clear();
+    };
+
+    for c in movetext.chars() {
+        match c {
+            '{' => comment = true,
+            '}' => comment = false,
+            _ if comment => {}
+            '(' => variation += 1,
+            ')' => variation -= 1,
+            _ if variation > 0 => {}
+            c if c.is_whitespace() => flush(&mut token, &mut sans),
+            c => token.push(c),
+        }
+    }
+    flush(&mut token, &mut sans);
+
+    sans
+}
+
+/// Replays `sans` from the starting position.
+pub fn replay(sans: &[String]) -> Result<Vec<Ply>, Error> {
+    replay_from(Chess::default(), sans)
+}
+
+/// Replays `sans` from `pos`, in whichever variant it's a position of.
+pub fn replay_from<P: Position + Clone>(mut pos: P, sans: &[String]) -> Result<Vec<Ply<P>>, Error> {
+    let mut plies = vec![];
+
+    for san in sans {
+        let parsed = SanPlus::from_ascii(san.as_bytes())
+            .map_err(|_| Error::Invalid(format!("Invalid move {:?}", san)))?;
+        let played = parsed
+            .san
+            .to_move(&pos)
+            .map_err(|_| Error::Invalid(format!("Illegal move {:?}", san)))?;
+
+        plies.push(Ply {
+            before: pos.clone(),
+            san: san.clone(),
+            played,
+        });
+        pos.play_unchecked(played);
+    }
+
+    Ok(plies)
+}
+
+/// The position after every ply has been played.
+pub fn final_position(plies: &[Ply]) -> Chess {
+    final_position_from(Chess::default(), plies)
+}
+
+/// The position after every ply has been played, `start` if there are none.
+pub fn final_position_from<P: Position + Clone>(start: P, plies: &[Ply<P>]) -> P {
+    match plies.last() {
+        Some(ply) => {
+            let mut pos = ply.before.clone();
+            pos.play_unchecked(ply.played);
+            pos
+        }
+        None => start,
+    }
+}
+
+/// Parses `fen` leniently: the castling rights we get from scraped boards
+/// are a guess, so ones that can't apply are dropped rather than failing.
+pub fn parse_fen(fen: &str) -> Option<Chess> {
+    let fen: Fen = fen.parse().ok()?;
+    fen.into_position(CastlingMode::Standard)
+        .or_else(PositionError::ignore_invalid_castling_rights)
+        .ok()
+}
+
+/// Parses `fen` as a position of `variant`, as leniently as `parse_fen`.
+/// Crazyhouse pockets and Three-check counters are read from the FEN.
+pub fn parse_variant_fen(fen: &str, variant: Variant) -> Option<VariantPosition> {
+    let fen: Fen = fen.parse().ok()?;
+    VariantPosition::from_setup(variant.rules(), fen.into_setup(), variant.castling_mode())
+        .or_else(PositionError::ignore_invalid_castling_rights)
+        .ok()
+}
+
+/// `fen` with the other side to move, for seeing what they'd play if given
+/// a free move. `None` when the side to move is in check, since passing
+/// isn't legal then.
+pub fn null_move_fen(fen: &str) -> Option<String> {
+    let pos = parse_fen(fen)?;
+    if pos.is_check() {
+        return None;
+    }
+    pos.swap_turn().ok().map(|pos| to_fen(&pos))
+}
+
+/// `pos` as FEN. Chess960 castling rights come out as X-FEN, Crazyhouse
+/// pockets in brackets after the board and Three-check's remaining checks
+/// after the en passant square.
+pub fn to_fen<P: Position>(pos: &P) -> String {
+    Fen::from_position(pos, EnPassantMode::Legal).to_string()
+}
+
+/// Move in the long algebraic form the engine uses, e.g. `e1g1`.
+pub fn to_uci(m: Move) -> String {
+    UciMove::from_move(m, CastlingMode::Standard).to_string()
+}
+
+/// Parses an engine move like `e7e8q`, or a drop like `N@f3`, in `pos`.
+pub fn from_uci<P: Position>(pos: &P, uci: &str) -> Result<Move, Error> {
+    let parsed = UciMove::from_ascii(uci.as_bytes())
+        .map_err(|_| Error::Invalid(format!("Invalid move {:?}", uci)))?;
+    parsed
+        .to_move(pos)
+        .map_err(|_| Error::Invalid(format!("Illegal move {:?}", uci)))
+}
+
+/// The board in the `[[u8; 8]; 8]` layout `gen_board` draws, seen from `orientation`.
+pub fn to_board<P: Position>(pos: &P, orientation: Color) -> [[u8; 8]; 8] {
+    let mut board = [[0u8; 8]; 8];
+
+    for (square, piece) in pos.board().clone() {
+        let piece_type = match piece.role {
+            Role::King => 1,
+            Role::Queen => 2,
+            Role::Rook => 3,
+            Role::Bishop => 4,
+            Role::Knight => 5,
+            Role::Pawn => 6,
+        };
+        let color = match piece.color {
+            Color::White => 1,
+            Color::Black => 2,
+        };
+
+        let (row, col) = square_to_cell(square, orientation);
+        board[row][col] = (color << 3) | piece_type;
+    }
+
+    board
+}
+
+/// Row and column of `square` on a board drawn from `orientation`.
+pub fn square_to_cell(square: Square, orientation: Color) -> (usize, usize) {
+    let file = square.file().to_usize();
+    let rank = square.rank().to_usize();
+
+    match orientation {
+        Color::White => (7 - rank, file),
+        Color::Black => (rank, 7 - file),
+    }
+}
+
+/// The `(left, top)` percentages lichess uses for a square, which is what
+/// `gen_board` takes for last move highlights.
+pub fn square_to_percent(square: Square, orientation: Color) -> (f32, f32) {
+    let (row, col) = square_to_cell(square, orientation);
+    (col as f32 * 12.5, row as f32 * 12.5)
+}
+
+/// Last move highlights for `m`.
+pub fn move_to_percent(m: Move, orientation: Color) -> Vec<(f32, f32)> {
+    let uci = to_uci(m);
+    let squares = [&uci[0..2], &uci[2..4]];
+
+    squares
+        .iter()
+        .filter_map(|square| square.parse::<Square>().ok())
+        .map(|square| square_to_percent(square, orientation))
+        .collect()
+}
+
+/// "w" or "b", the mode strings the rest of the board code uses.
+pub fn color_to_mode(color: Color) -> &'static str {
+    match color {
+        Color::White => "w",
+        Color::Black => "b",
+    }
+}
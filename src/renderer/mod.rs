@@ -0,0 +1,501 @@
+use shakmaty::{CastlingSide, Color, Move, Piece, Position, Rank, Role, Square};
+use tracing::instrument;
+
+use crate::board::get_piece_type;
+use crate::metrics;
+use crate::position::{self, Variant};
+
+macro_rules! piece_set {
+    ($name:literal) => {
+        include_bytes!(concat!(
+            "../assets/JohnPablok Cburnett Chess set/PNGs/No shadow/128h/",
+            $name
+        ))
+    };
+}
+
+/// The board and piece images, bundled so drawing works wherever we're run
+/// from.
+fn asset(name: &str) -> &'static [u8] {
+    match name {
+        "board.png" => include_bytes!("../assets/board.png"),
+        "b_bishop_png_128px.png" => piece_set!("b_bishop_png_128px.png"),
+        "b_queen_png_128px.png" => piece_set!("b_queen_png_128px.png"),
+        "b_rook_png_128px.png" => piece_set!("b_rook_png_128px.png"),
+        "b_knight_png_128px.png" => piece_set!("b_knight_png_128px.png"),
+        "b_pawn_png_128px.png" => piece_set!("b_pawn_png_128px.png"),
+        "b_king_png_128px.png" => piece_set!("b_king_png_128px.png"),
+        "w_bishop_png_128px.png" => piece_set!("w_bishop_png_128px.png"),
+        "w_queen_png_128px.png" => piece_set!("w_queen_png_128px.png"),
+        "w_rook_png_128px.png" => piece_set!("w_rook_png_128px.png"),
+        "w_knight_png_128px.png" => piece_set!("w_knight_png_128px.png"),
+        "w_pawn_png_128px.png" => piece_set!("w_pawn_png_128px.png"),
+        "w_king_png_128px.png" => piece_set!("w_king_png_128px.png"),
+        "square brown dark_png_128px.png" => piece_set!("square brown dark_png_128px.png"),
+        "square brown light_png_128px.png" => piece_set!("square brown light_png_128px.png"),
+        _ => panic!("No asset named {:?}", name),
+    }
+}
+
+fn load(name: &str) -> image::DynamicImage {
+    image::load_from_memory(asset(name))
+        .unwrap_or_else(|why| panic!("Error decoding {}: {why:?}", name))
+}
+
+/// Draws an arrow between the centres of two squares, blended over the board.
+fn draw_arrow(board: &mut image::RgbaImage, from: (f32, f32), to: (f32, f32), color: [u8; 4]) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 {
+        return;
+    }
+    let (ux, uy) = (dx / length, dy / length);
+
+    let shaft_width = 12.0;
+    let head_length = 44.0;
+    let head_width = 36.0;
+    let shaft_end = (length - head_length).max(0.0);
+
+    let min_x = (from.0.min(to.0) - head_width).max(0.0) as u32;
+    let max_x = (from.0.max(to.0) + head_width).min(board.width() as f32 - 1.0) as u32;
+    let min_y = (from.1.min(to.1) - head_width).max(0.0) as u32;
+    let max_y = (from.1.max(to.1) + head_width).min(board.height() as f32 - 1.0) as u32;
+
+    let alpha = color[3] as f32 / 255.0;
+    for x in min_x..=max_x {
+        for y in min_y..=max_y {
+            // position along and across the arrow
+            let (px, py) = (x as f32 - from.0, y as f32 - from.1);
+            let along = px * ux + py * uy;
+            let across = (px * uy - py * ux).abs();
+
+            let inside = if (0.0..=shaft_end).contains(&along) {
+                across <= shaft_width / 2.0
+            } else if along > shaft_end && along <= length {
+                across <= head_width * (length - along) / head_length
+            } else {
+                false
+            };
+            if !inside {
+                continue;
+            }
+
+            let pixel = board.get_pixel_mut(x, y);
+            for (channel, color) in pixel.0.iter_mut().zip(color).take(3) {
+                *channel = (*channel as f32 * (1.0 - alpha) + color as f32 * alpha) as u8;
+            }
+        }
+    }
+}
+
+/// Top left pixel of `square` on a board drawn from `orientation`'s side.
+fn square_origin(square: Square, orientation: Color) -> (u32, u32) {
+    let (row, col) = position::square_to_cell(square, orientation);
+    (col as u32 * 128, row as u32 * 128)
+}
+
+fn square_centre(square: Square, orientation: Color) -> (f32, f32) {
+    let (x, y) = square_origin(square, orientation);
+    (x as f32 + 64.0, y as f32 + 64.0)
+}
+
+fn fill_square(board: &mut image::RgbaImage, square: Square, orientation: Color, color: [u8; 4]) {
+    let (x, y) = square_origin(square, orientation);
+    for i in 0..128 {
+        for j in 0..128 {
+            board.get_pixel_mut(x + i, y + j).0 = color;
+        }
+    }
+}
+
+/// A soft radial glow, strongest in the middle of the square.
+fn glow_square(board: &mut image::RgbaImage, square: Square, orientation: Color, color: [u8; 3]) {
+    let (x, y) = square_origin(square, orientation);
+    for i in 0..128 {
+        for j in 0..128 {
+            let (dx, dy) = (i as f32 - 63.5, j as f32 - 63.5);
+            let alpha = (1.0 - (dx * dx + dy * dy).sqrt() / 95.0)
+                .clamp(0.0, 1.0)
+                .powf(0.6);
+
+            let pixel = board.get_pixel_mut(x + i, y + j);
+            for (channel, color) in pixel.0.iter_mut().zip(color) {
+                *channel = (*channel as f32 * (1.0 - alpha) + color as f32 * alpha) as u8;
+            }
+        }
+    }
+}
+
+/// Squares to highlight for the best move with their colors, in from and to
+/// pairs. Castling lights up the rook's squares as well as the king's.
+fn best_move_squares(best: Option<Move>, best_move: &str) -> Vec<(Square, [u8; 4])> {
+    let from_color = [204, 202, 62, 255];
+    let to_color = [206, 214, 128, 255];
+
+    match best {
+        Some(Move::Castle { king, rook }) => {
+            let side = CastlingSide::from_king_side(king < rook);
+            let color = if king.rank() == Rank::First {
+                Color::White
+            } else {
+                Color::Black
+            };
+            vec![
+                (king, from_color),
+                (side.king_to(color), to_color),
+                (rook, from_color),
+                (side.rook_to(color), to_color),
+            ]
+        }
+        Some(m) => {
+            let mut squares = vec![(m.to(), to_color)];
+            if let Some(from) = m.from() {
+                squares.insert(0, (from, from_color));
+            }
+            squares
+        }
+        // no position to check it against, so only trust well formed squares
+        None => {
+            let from = best_move.get(0..2).and_then(|sq| sq.parse::<Square>().ok());
+            let to = best_move.get(2..4).and_then(|sq| sq.parse::<Square>().ok());
+            match (from, to) {
+                (Some(from), Some(to)) => vec![(from, from_color), (to, to_color)],
+                _ => vec![],
+            }
+        }
+    }
+}
+
+/// Draws `piece` small in the top right corner of `square`.
+fn promotion_badge(board: &mut image::RgbaImage, square: Square, orientation: Color, piece: Piece) {
+    let name = format!(
+        "{}_{}_png_128px.png",
+        match piece.color {
+            Color::White => "w",
+            Color::Black => "b",
+        },
+        match piece.role {
+            Role::Queen => "queen",
+            Role::Rook => "rook",
+            Role::Bishop => "bishop",
+            Role::Knight => "knight",
+            Role::King => "king",
+            Role::Pawn => "pawn",
+        }
+    );
+    let badge = load(&name)
+        .resize(52, 52, image::imageops::FilterType::Triangle)
+        .to_rgba8();
+
+    let (x, y) = square_origin(square, orientation);
+    let (x, y) = (x + 128 - badge.width() - 4, y + 4);
+
+    // white disc behind the piece so it reads on any square
+    for i in 0..badge.width() {
+        for j in 0..badge.height() {
+            let (dx, dy) = (
+                i as f32 - badge.width() as f32 / 2.0,
+                j as f32 - badge.height() as f32 / 2.0,
+            );
+            if (dx * dx + dy * dy).sqrt() <= badge.width() as f32 / 2.0 {
+                board.get_pixel_mut(x + i, y + j).0 = [245, 245, 245, 255];
+            }
+        }
+    }
+    image::imageops::overlay(board, &badge, x as i64, y as i64);
+}
+
+/// Draws the board as a PNG. `fen` is read as a position of `variant` to
+/// mark the best move, checks and game overs.
+#[instrument(name = "render", skip_all)]
+pub fn gen_board(
+    board_bytes: [[u8; 8]; 8],
+    mode: &str,
+    fen: &str,
+    variant: Variant,
+    last_moves_x_y_vec: Vec<(f32, f32)>,
+    best_move: String,
+    threat_move: Option<String>,
+) -> Vec<u8> {
+    fn get_piece_path(piece_type: String) -> String {
+        match piece_type.as_str() {
+            "b" => "b_bishop_png_128px.png",
+            "q" => "b_queen_png_128px.png",
+            "r" => "b_rook_png_128px.png",
+            "n" => "b_knight_png_128px.png",
+            "p" => "b_pawn_png_128px.png",
+            "k" => "b_king_png_128px.png",
+            "B" => "w_bishop_png_128px.png",
+            "Q" => "w_queen_png_128px.png",
+            "R" => "w_rook_png_128px.png",
+            "N" => "w_knight_png_128px.png",
+            "P" => "w_pawn_png_128px.png",
+            "K" => "w_king_png_128px.png",
+            _ => panic!("Invalid piece type {:?}", piece_type),
+        }
+        .to_string()
+    }
+
+    let _timer = metrics::RENDER_SECONDS.start_timer();
+
+    let last_moved_img = "square brown dark_png_128px.png";
+    let last_moved_img1 = "square brown light_png_128px.png";
+
+    let orientation = if mode == "b" {
+        Color::Black
+    } else {
+        Color::White
+    };
+    let mut board = load("board.png");
+
+    for (count, (x, y)) in last_moves_x_y_vec.into_iter().enumerate() {
+        // x, y are percentages
+        let x = x / 100.0;
+        let y = y / 100.0;
+
+        let x = (x * 8.0) as u8;
+        let y = (y * 8.0) as u8;
+
+        let x = x as usize;
+        let y = y as usize;
+
+        let x = x * 128;
+        let y = y * 128;
+
+        let mut new_board = board.to_rgba8();
+        // in theory should never be more then 2
+        let last_moved = if count % 2 == 0 {
+            load(last_moved_img)
+        } else {
+            load(last_moved_img1)
+        };
+
+        let last_moved = last_moved.to_rgba8();
+        for i in 0..last_moved.width() {
+            for j in 0..last_moved.height() {
+                let pixel = last_moved.get_pixel(i, j);
+                let pixel = pixel.0;
+                if pixel[3] == 0 {
+                    continue;
+                }
+
+                let x = x + i as usize;
+                let y = y + j as usize;
+                let npixel = new_board.get_pixel_mut(x as u32, y as u32);
+                npixel.0 = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            }
+        }
+        board = image::DynamicImage::ImageRgba8(new_board);
+    }
+
+    // best move is in uci format like b8c6, or "None"/"(none)" when the
+    // game is over, so only draw it if it's a real move
+    let position = position::parse_variant_fen(fen, variant);
+    let best = position
+        .as_ref()
+        .and_then(|pos| position::from_uci(pos, &best_move).ok());
+
+    let best_squares = best_move_squares(best, &best_move);
+    let mut new_board = board.to_rgba8();
+    for (square, color) in &best_squares {
+        fill_square(&mut new_board, *square, orientation, *color);
+    }
+
+    // a king in check glows red under its piece
+    if let Some(pos) = &position {
+        if pos.is_check() {
+            if let Some(king) = pos.board().king_of(pos.turn()) {
+                glow_square(&mut new_board, king, orientation, [230, 30, 30]);
+            }
+        }
+    }
+    board = image::DynamicImage::ImageRgba8(new_board);
+
+    // place pieces on the board
+    let mut pieces = vec![];
+
+    for (i, row) in board_bytes.iter().enumerate() {
+        for (j, &piece) in row.iter().enumerate() {
+            if piece == 0 {
+                continue;
+            }
+
+            let piece_type = get_piece_type(piece);
+            let piece_name = get_piece_path(piece_type.to_string());
+            pieces.push((piece_name, j, i));
+        }
+    }
+
+    let mut board = board.to_rgba8();
+
+    for (piece_name, x, y) in pieces {
+        let mut piece = load(&piece_name);
+
+        let width = piece.width();
+
+        if width > 128 {
+            let ratio = 128.0 / width as f32;
+            let height = piece.height() as f32 * ratio;
+            piece = piece.resize(128, height as u32, image::imageops::FilterType::Nearest);
+        }
+
+        let width = piece.width() as usize;
+
+        let offset = (128 - width) / 2;
+
+        let x = (x * 128) + offset;
+
+        let height = piece.height() as usize;
+        let offset = (128 - height) / 2;
+        let y = (y * 128) + offset;
+        let piece = piece.to_rgba8();
+
+        for i in 0..piece.width() {
+            for j in 0..piece.height() {
+                let pixel = piece.get_pixel(i, j);
+                let pixel = pixel.0;
+                if pixel[3] == 0 {
+                    continue;
+                }
+
+                let x = x + i as usize;
+                let y = y + j as usize;
+                let npixel = board.get_pixel_mut(x as u32, y as u32);
+                npixel.0 = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            }
+        }
+    }
+
+    // a promotion shows what the pawn becomes in the corner of its square
+    if let Some(role) = best.and_then(|m| m.promotion()) {
+        let piece = Piece {
+            color: position
+                .as_ref()
+                .map(|pos| pos.turn())
+                .unwrap_or(Color::White),
+            role,
+        };
+        promotion_badge(&mut board, best.unwrap().to(), orientation, piece);
+    }
+
+    // game over, so grey the whole board out
+    if position.as_ref().is_some_and(|pos| pos.is_game_over()) {
+        for pixel in board.pixels_mut() {
+            for channel in pixel.0.iter_mut().take(3) {
+                *channel = (*channel as f32 * 0.55) as u8;
+            }
+        }
+    }
+
+    // the best move gets an arrow over the pieces too, the king's when
+    // castling
+    if let [(from, _), (to, _), ..] = best_squares[..] {
+        draw_arrow(
+            &mut board,
+            square_centre(from, orientation),
+            square_centre(to, orientation),
+            [40, 150, 70, 190],
+        );
+    }
+
+    // threats go on top of the pieces so they can't be missed
+    if let Some(threat_move) = threat_move {
+        let from = threat_move
+            .get(0..2)
+            .and_then(|sq| sq.parse::<Square>().ok());
+        let to = threat_move
+            .get(2..4)
+            .and_then(|sq| sq.parse::<Square>().ok());
+        if let (Some(from), Some(to)) = (from, to) {
+            draw_arrow(
+                &mut board,
+                square_centre(from, orientation),
+                square_centre(to, orientation),
+                [220, 40, 40, 190],
+            );
+        }
+    }
+
+    let mut png = std::io::Cursor::new(Vec::new());
+    board
+        .write_to(&mut png, image::ImageFormat::Png)
+        .expect("Error encoding board");
+    png.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(fen: &str, mode: &str, best_move: &str) -> image::RgbaImage {
+        let pos = position::parse_fen(fen).unwrap();
+        let orientation = if mode == "b" {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let png = gen_board(
+            position::to_board(&pos, orientation),
+            mode,
+            fen,
+            Variant::Standard,
+            vec![(56.25, 81.25), (56.25, 56.25)],
+            best_move.to_string(),
+            None,
+        );
+        assert!(png.starts_with(b"\x89PNG"));
+        image::load_from_memory(&png).unwrap().to_rgba8()
+    }
+
+    #[test]
+    fn renders_without_a_best_move() {
+        let board = render(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            "w",
+            "(none)",
+        );
+        assert_eq!(board.dimensions(), (1024, 1024));
+        assert!(best_move_squares(None, "(none)").is_empty());
+        assert!(best_move_squares(None, "None").is_empty());
+    }
+
+    #[test]
+    fn renders_castling_from_both_sides() {
+        let fen = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+        for mode in ["w", "b"] {
+            assert_eq!(render(fen, mode, "e1g1").dimensions(), (1024, 1024));
+        }
+
+        // king and rook both light up, whichever way the engine wrote it
+        let pos = position::parse_fen(fen).unwrap();
+        for uci in ["e1g1", "e1h1"] {
+            let castle = position::from_uci(&pos, uci).ok();
+            let squares: Vec<Square> = best_move_squares(castle, uci)
+                .into_iter()
+                .map(|(square, _)| square)
+                .collect();
+            assert_eq!(squares, [Square::E1, Square::G1, Square::H1, Square::F1]);
+        }
+    }
+
+    #[test]
+    fn renders_a_promotion_badge() {
+        let fen = "8/4P3/8/8/8/8/k7/4K3 w - - 0 1";
+        let plain = render(fen, "w", "e1d1");
+        let promotion = render(fen, "w", "e7e8q");
+        assert_eq!(promotion.dimensions(), (1024, 1024));
+
+        // the rim of the badge's disc, in the top right of e8
+        let (x, y) = square_origin(Square::E8, Color::White);
+        let corner = (x + 128 - 5, y + 30);
+        assert_eq!(
+            promotion.get_pixel(corner.0, corner.1).0,
+            [245, 245, 245, 255]
+        );
+        assert_ne!(plain.get_pixel(corner.0, corner.1).0, [245, 245, 245, 255]);
+
+        // black promotes on the first rank, drawn from black's side too
+        let fen = "4k3/8/8/8/8/8/p7/4K3 b - - 0 1";
+        assert_eq!(render(fen, "b", "a2a1n").dimensions(), (1024, 1024));
+    }
+}
@@ -0,0 +1,394 @@
+use std::env;
+
+use serenity::all::{ChannelId, Context, CreateEmbed, CreateMessage};
+use shakmaty::san::San;
+use shakmaty::{Chess, Color, Position};
+use tracing::{info, warn};
+
+use crate::accuracy::{game_accuracy, win_percent, Judgement, CP_CAP};
+use crate::board::get_html;
+use crate::engine;
+use crate::i18n::{tr, tr_args, Locale};
+use crate::interactions::upload_board;
+use crate::models::error::Error;
+use crate::notation;
+use crate::openings::{self, Opening};
+use crate::position::{self, Ply, Variant};
+use crate::renderer::gen_board;
+use crate::tablebase;
+
+/// Depth each position of a finished game is searched to, unless
+/// `REPORT_DEPTH` says otherwise. Kept low since we search every ply.
+const DEFAULT_REPORT_DEPTH: u8 = 12;
+
+/// How many of the worst moves get a board in the report.
+const WORST_MOMENTS: usize = 3;
+
+/// Tries at each position before it's left out of the report.
+const SCORE_ATTEMPTS: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct MoveReview {
+    pub ply: Ply,
+    pub best_move: Option<String>,
+    pub cp_loss: i32,
+    pub win_drop: f32,
+    pub judgement: Option<Judgement>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlayerReport {
+    pub acpl: f32,
+    pub accuracy: f32,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameReport {
+    pub white: String,
+    pub black: String,
+    pub result: String,
+    pub opening: Option<Opening>,
+    pub white_report: PlayerReport,
+    pub black_report: PlayerReport,
+    pub moves: Vec<MoveReview>,
+    /// Moves left ungraded because a position next to them couldn't be
+    /// evaluated
+    pub gaps: usize,
+}
+
+/// White's centipawn score for `pos` and the engine's best move, if any.
+async fn score(pos: &Chess, depth: u8) -> Result<(i32, Option<String>), Error> {
+    if pos.is_checkmate() {
+        let cp = match pos.turn() {
+            Color::White => -CP_CAP,
+            Color::Black => CP_CAP,
+        };
+        return Ok((cp, None));
+    }
+
+    if pos.is_game_over() {
+        return Ok((0, None));
+    }
+
+    if let Some(probe) = tablebase::probe_position(pos) {
+        return Ok((probe.white_cp(pos.turn(), CP_CAP), probe.best_move));
+    }
+
+    let stockfish = engine::analyse(&position::to_fen(pos), depth).await?;
+    let cp = match (stockfish.evaluation, stockfish.mate) {
+        (_, Some(mate)) if mate > 0 => CP_CAP,
+        (_, Some(mate)) if mate < 0 => -CP_CAP,
+        (Some(evaluation), _) => (evaluation * 100.0) as i32,
+        _ => 0,
+    };
+    let best_move = stockfish
+        .bestmove
+        .replace("bestmove ", "")
+        .split_whitespace()
+        .next()
+        .map(str::to_string);
+
+    Ok((cp.clamp(-CP_CAP, CP_CAP), best_move))
+}
+
+/// `score`, retried, or `None` once it's failed `SCORE_ATTEMPTS` times.
+async fn score_or_gap(pos: &Chess, depth: u8) -> Option<(i32, Option<String>)> {
+    for attempt in 1..=SCORE_ATTEMPTS {
+        match score(pos, depth).await {
+            Ok(score) => return Some(score),
+            Err(why) => warn!(
+                "Error evaluating {} (attempt {}): {}",
+                position::to_fen(pos),
+                attempt,
+                why
+            ),
+        }
+    }
+    None
+}
+
+/// `color`'s counts and averages over `moves`, with `accuracy` from
+/// `game_accuracy` since it needs the whole game.
+fn summarise(moves: &[MoveReview], color: Color, accuracy: Option<f32>) -> PlayerReport {
+    let moves: Vec<&MoveReview> = moves
+        .iter()
+        .filter(|review| review.ply.before.turn() == color)
+        .collect();
+
+    if moves.is_empty() {
+        return PlayerReport::default();
+    }
+
+    let count = moves.len() as f32;
+    let judged = |judgement: Judgement| {
+        moves
+            .iter()
+            .filter(|review| review.judgement == Some(judgement))
+            .count()
+    };
+
+    PlayerReport {
+        acpl: moves.iter().map(|review| review.cp_loss as f32).sum::<f32>() / count,
+        accuracy: accuracy.unwrap_or_default(),
+        inaccuracies: judged(Judgement::Inaccuracy),
+        mistakes: judged(Judgement::Mistake),
+        blunders: judged(Judgement::Blunder),
+    }
+}
+
+/// Evaluates every position of `pgn` and grades each move. Positions the
+/// engine keeps failing on leave the moves either side of them ungraded
+/// rather than losing the whole report.
+pub async fn analyse_game(pgn: &str) -> Result<GameReport, Error> {
+    let depth = env::var("REPORT_DEPTH")
+        .ok()
+        .and_then(|depth| depth.parse().ok())
+        .unwrap_or(DEFAULT_REPORT_DEPTH);
+
+    let plies = position::replay(&position::pgn_sans(pgn))?;
+    let opening = openings::lookup_moves(&plies);
+
+    let mut scores = vec![];
+    for ply in &plies {
+        scores.push(score_or_gap(&ply.before, depth).await);
+    }
+    scores.push(score_or_gap(&position::final_position(&plies), depth).await);
+    if scores.iter().all(Option::is_none) {
+        return Err(Error::BasicError(
+            "Couldn't evaluate any position of the game".to_string(),
+        ));
+    }
+
+    let first = plies
+        .first()
+        .map(|ply| ply.before.turn())
+        .unwrap_or(Color::White);
+    let wins: Vec<Option<f32>> = scores
+        .iter()
+        .map(|score| score.as_ref().map(|(cp, _)| win_percent(*cp)))
+        .collect();
+
+    let mut moves = vec![];
+    let mut gaps = 0;
+    for (i, ply) in plies.into_iter().enumerate() {
+        let sign = match ply.before.turn() {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+        let ((before, best_move), (after, _)) = match (&scores[i], &scores[i + 1]) {
+            (Some(before), Some(after)) => (before.clone(), after.clone()),
+            _ => {
+                gaps += 1;
+                continue;
+            }
+        };
+
+        let win_before = win_percent(sign * before);
+        let win_after = win_percent(sign * after);
+        let win_drop = (win_before - win_after).max(0.0);
+
+        moves.push(MoveReview {
+            ply,
+            best_move,
+            cp_loss: (sign * (before - after)).max(0),
+            win_drop,
+            judgement: Judgement::from_drop(win_drop),
+        });
+    }
+
+    Ok(GameReport {
+        white: position::pgn_tag(pgn, "White").unwrap_or("White".to_string()),
+        black: position::pgn_tag(pgn, "Black").unwrap_or("Black".to_string()),
+        result: position::pgn_tag(pgn, "Result").unwrap_or("*".to_string()),
+        opening,
+        white_report: summarise(
+            &moves,
+            Color::White,
+            game_accuracy(&wins, first, Color::White),
+        ),
+        black_report: summarise(
+            &moves,
+            Color::Black,
+            game_accuracy(&wins, first, Color::Black),
+        ),
+        moves,
+        gaps,
+    })
+}
+
+pub async fn fetch_pgn(game_id: &str) -> Result<String, Error> {
+    get_html(format!(
+        "https://lichess.org/game/export/{}?clocks=false&evals=false",
+        game_id
+    ))
+    .await
+}
+
+/// "23." for white's 23rd move, "23..." for black's.
+fn move_label(review: &MoveReview) -> String {
+    let number = review.ply.before.fullmoves();
+    match review.ply.before.turn() {
+        Color::White => format!("{}.", number),
+        Color::Black => format!("{}...", number),
+    }
+}
+
+fn player_field(report: &PlayerReport, locale: Locale) -> String {
+    tr_args(
+        locale,
+        "report-player",
+        &[
+            ("accuracy", format!("{:.1}", report.accuracy).into()),
+            ("acpl", format!("{:.0}", report.acpl).into()),
+            ("inaccuracies", report.inaccuracies.into()),
+            ("mistakes", report.mistakes.into()),
+            ("blunders", report.blunders.into()),
+        ],
+    )
+}
+
+/// Renders the position before `review`'s move, with the move played as the
+/// last move and the engine's choice as the best move. The file name to
+/// upload it as and the PNG.
+fn render_moment(game_id: &str, i: usize, review: &MoveReview) -> Option<(String, Vec<u8>)> {
+    let best_move = review.best_move.clone()?;
+    if best_move.len() < 4 {
+        return None;
+    }
+
+    let orientation = review.ply.before.turn();
+    let image = gen_board(
+        position::to_board(&review.ply.before, orientation),
+        position::color_to_mode(orientation),
+        &position::to_fen(&review.ply.before),
+        Variant::Standard,
+        position::move_to_percent(review.ply.played, orientation),
+        best_move,
+        None,
+    );
+
+    Some((format!("{}-{}.png", game_id, i), image))
+}
+
+/// Analyses the finished game `game_id` and posts the report to `channel_id`
+/// in `locale`. Games that are still going are skipped.
+pub async fn post_report(
+    ctx: &Context,
+    channel_id: ChannelId,
+    game_id: &str,
+    locale: Locale,
+) -> Result<(), Error> {
+    let pgn = fetch_pgn(game_id).await?;
+    if position::pgn_tag(&pgn, "Result").as_deref() == Some("*") {
+        return Err(Error::BasicError(format!("Game {} hasn't finished", game_id)));
+    }
+
+    info!("Building report for {}", game_id);
+    let report = analyse_game(&pgn).await?;
+
+    let mut worst: Vec<(usize, &MoveReview)> = report
+        .moves
+        .iter()
+        .enumerate()
+        .filter(|(_, review)| review.judgement.is_some())
+        .collect();
+    worst.sort_by(|a, b| b.1.win_drop.total_cmp(&a.1.win_drop));
+    worst.truncate(WORST_MOMENTS);
+
+    let mut description = tr_args(
+        locale,
+        "report-result",
+        &[("result", report.result.clone().into())],
+    );
+    if let Some(opening) = &report.opening {
+        description.push_str(&format!(
+            "\n{}: {}",
+            tr(locale, "label-opening"),
+            opening.label()
+        ));
+    }
+    if report.gaps > 0 {
+        description.push('\n');
+        description.push_str(&tr_args(
+            locale,
+            "report-gaps",
+            &[("moves", report.gaps.into())],
+        ));
+    }
+
+    let title = tr_args(
+        locale,
+        "report-title",
+        &[
+            ("white", report.white.clone().into()),
+            ("black", report.black.clone().into()),
+        ],
+    );
+    let mut embed = CreateEmbed::default()
+        .title(title)
+        .url(format!("https://lichess.org/{}", game_id))
+        .description(description)
+        .field(
+            format!("{}: {}", tr(locale, "white"), report.white),
+            player_field(&report.white_report, locale),
+            true,
+        )
+        .field(
+            format!("{}: {}", tr(locale, "black"), report.black),
+            player_field(&report.black_report, locale),
+            true,
+        );
+
+    for (i, review) in worst {
+        let judgement = review.judgement.unwrap();
+        let best = review
+            .best_move
+            .as_deref()
+            .and_then(|uci| position::from_uci(&review.ply.before, uci).ok())
+            .map(|m| San::from_move(&review.ply.before, m).to_string());
+
+        let drop = format!("{:.1}", review.win_drop);
+        let mut value = tr_args(locale, "report-win-drop", &[("drop", drop.into())]);
+        if let Some(best) = best {
+            let best = notation::localize(&best, locale);
+            value.push_str(&tr_args(
+                locale,
+                "report-best-was",
+                &[("move", best.into())],
+            ));
+        }
+
+        if let Some((file, image)) = render_moment(game_id, i, review) {
+            match upload_board(ctx, &file, &image).await {
+                Ok(url) => value.push_str(&format!(
+                    "\n[{}]({})",
+                    tr(locale, "report-view-board"),
+                    url
+                )),
+                Err(why) => warn!("{}", why),
+            }
+        }
+
+        embed = embed.field(
+            format!(
+                "{}{}{} ({})",
+                move_label(review),
+                notation::localize(&review.ply.san, locale),
+                judgement.annotation(),
+                judgement.name(locale)
+            ),
+            value,
+            false,
+        );
+    }
+
+    channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await
+        .map_err(|e| Error::BasicError(format!("Error sending report {:?}", e)))?;
+
+    Ok(())
+}
@@ -1,10 +1,92 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    analysis (id) {
+        id -> Integer,
+        message_id -> Text,
+        fen -> Text,
+        orientation -> Text,
+        last_moves -> Text,
+        depth -> Integer,
+        evaluation -> Float,
+        mate -> Nullable<Integer>,
+        bestmove -> Text,
+        continuation -> Text,
+        created_at -> Timestamp,
+        eco -> Nullable<Text>,
+        opening -> Nullable<Text>,
+        variant -> Varchar,
+    }
+}
+
+diesel::table! {
+    game_view (id) {
+        id -> Integer,
+        message_id -> Text,
+        game_id -> Text,
+        ply -> Nullable<Integer>,
+        orientation -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    guild_settings (id) {
+        id -> Integer,
+        guild_id -> Text,
+        locale -> Nullable<Text>,
+        created_at -> Timestamp,
+        fair_play -> Varchar,
+        fair_play_moves -> Integer,
+    }
+}
+
+diesel::table! {
+    linked_account (id) {
+        id -> Integer,
+        user_id -> Text,
+        lichess_user -> Text,
+        created_at -> Timestamp,
+        verified -> Bool,
+    }
+}
+
 diesel::table! {
     message (id) {
         id -> Integer,
         message_id -> Text,
         lc_channel -> Text,
         created_at -> Timestamp,
+        platform -> Varchar,
     }
 }
+
+diesel::table! {
+    subscription (id) {
+        id -> Integer,
+        channel_id -> Text,
+        lc_user -> Text,
+        live -> Bool,
+        last_game_id -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    user_settings (id) {
+        id -> Integer,
+        user_id -> Text,
+        locale -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::allow_tables_to_appear_in_same_query!(
+    analysis,
+    game_view,
+    guild_settings,
+    linked_account,
+    message,
+    subscription,
+    user_settings,
+);
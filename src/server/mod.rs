@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, Path, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use ring::constant_time;
+use serde::Deserialize;
+use shakmaty::Color;
+use tokio::sync::Mutex;
+use tracing::{error, info, instrument, warn};
+
+use crate::board::{help, help_fen, help_pgn, GetStockFishResponse, DEFAULT_DEPTH};
+use crate::database::databse;
+use crate::interactions::DeepAnalysisSlot;
+use crate::logging;
+use crate::metrics;
+use crate::models::analysis::Analysis;
+use crate::models::error::Error;
+use crate::position::{self, Variant};
+use crate::renderer::gen_board;
+use crate::shutdown;
+
+/// How long a user's analysis is served from memory, unless
+/// `API_CACHE_SECS` says otherwise. Overlays poll far more often than
+/// positions change.
+const DEFAULT_API_CACHE_SECS: u64 = 10;
+
+/// Analyses kept in the API's cache before it starts over, on top of
+/// dropping expired ones.
+const API_CACHE_SIZE: usize = 1024;
+
+/// Deep analysis slots are keyed by guild id, which is never zero, so API
+/// requests share one slot pool of their own.
+const API_SLOT_KEY: u64 = 0;
+
+/// Requests each address may make to the API a minute, unless
+/// `API_RATE_LIMIT` says otherwise. 0 turns the limit off.
+const DEFAULT_API_RATE_LIMIT: u32 = 60;
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Whether the bot's gateway connection is up, for `/readyz`.
+pub static GATEWAY_CONNECTED: AtomicBool = AtomicBool::new(false);
+
+/// Set when serving without the bot, so `/readyz` doesn't wait for a
+/// gateway that never connects.
+pub static WITHOUT_GATEWAY: AtomicBool = AtomicBool::new(false);
+
+type Cache = std::sync::Mutex<HashMap<String, (Instant, GetStockFishResponse)>>;
+
+/// When each address's current window started and its requests in it.
+type Limits = std::sync::Mutex<HashMap<IpAddr, (Instant, u32)>>;
+
+#[derive(Clone)]
+struct ApiState {
+    api_key: Option<String>,
+    deep_analyses: Arc<std::sync::Mutex<HashMap<u64, usize>>>,
+    cache: Arc<Cache>,
+    cache_for: Duration,
+    rate_limit: u32,
+    limits: Arc<Limits>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnalyzeParams {
+    #[serde(default)]
+    flip: bool,
+    depth: Option<u8>,
+    #[serde(default)]
+    threats: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RenderParams {
+    fen: String,
+    #[serde(default)]
+    black: bool,
+}
+
+/// An error as a status and a JSON `{"error": ...}` body.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.1 }));
+        (self.0, body).into_response()
+    }
+}
+
+/// Mistakes in the request are the caller's, anything else is lichess, the
+/// engine or us failing.
+impl From<Error> for ApiError {
+    fn from(why: Error) -> Self {
+        let status = match why {
+            Error::Invalid(_) => StatusCode::BAD_REQUEST,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::BasicError(_) => StatusCode::BAD_GATEWAY,
+        };
+        ApiError(status, why.to_string())
+    }
+}
+
+/// Serves the HTTP API on `API_ADDR` until we shut down. Does nothing
+/// when it isn't set. Deep analyses take slots from the same pool as the
+/// bot's "Analyze deeper" button, and each address gets `API_RATE_LIMIT`
+/// requests a minute. Unless `METRICS_ADDR` gives them their own address,
+/// `/metrics`, `/healthz` and `/readyz` are served alongside and don't need
+/// the API key or count against the limit, so probes and scrapers can
+/// reach them.
+pub async fn run(deep_analyses: Arc<std::sync::Mutex<HashMap<u64, usize>>>) {
+    let addr = match env::var("API_ADDR").ok().filter(|addr| !addr.is_empty()) {
+        Some(addr) => addr,
+        None => return,
+    };
+    let cache_for = env::var("API_CACHE_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_API_CACHE_SECS);
+    let rate_limit = env::var("API_RATE_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_API_RATE_LIMIT);
+
+    let state = ApiState {
+        api_key: env::var("API_KEY").ok().filter(|key| !key.is_empty()),
+        deep_analyses,
+        cache: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        cache_for: Duration::from_secs(cache_for),
+        rate_limit,
+        limits: Arc::new(std::sync::Mutex::new(HashMap::new())),
+    };
+    metrics::init();
+    if state.api_key.is_none() {
+        warn!("API_KEY is not set, the API is open to anyone who can reach it");
+    }
+
+    let mut app = Router::new()
+        .route("/analyze/{user}", get(analyze_user))
+        .route("/analyze", axum::routing::post(analyze_body))
+        .route("/render", get(render))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .layer(middleware::from_fn_with_state(state.clone(), limit));
+    if metrics_addr().is_none() {
+        app = app.merge(probes());
+    }
+
+    serve("API", &addr, app.with_state(state)).await;
+}
+
+/// Serves `/metrics`, `/healthz` and `/readyz` on `METRICS_ADDR` until we
+/// shut down, whether or not the API is on. Does nothing when it isn't set.
+pub async fn run_probes() {
+    let addr = match metrics_addr() {
+        Some(addr) => addr,
+        None => return,
+    };
+    metrics::init();
+    serve("Metrics", &addr, probes()).await;
+}
+
+fn metrics_addr() -> Option<String> {
+    env::var("METRICS_ADDR")
+        .ok()
+        .filter(|addr| !addr.is_empty())
+}
+
+/// The routes for probes and scrapers, which never need the API key.
+fn probes<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route("/metrics", get(metrics_text))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+}
+
+async fn serve(what: &str, addr: &str, app: Router) {
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(why) => {
+            error!("Error binding {} to {:?}: {why:?}", what, addr);
+            return;
+        }
+    };
+    info!("{} listening on {}", what, addr);
+
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let server = axum::serve(listener, app).with_graceful_shutdown(shutdown::wait());
+    if let Err(why) = server.await {
+        error!("{} error: {why:?}", what);
+    }
+}
+
+/// Answers 429 once the caller's address has made `rate_limit` requests in
+/// the current minute. Checked before the key, so guessing it is slow too.
+async fn limit(
+    State(state): State<ApiState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.rate_limit == 0 {
+        return next.run(request).await;
+    }
+
+    let allowed = {
+        let mut limits = state.limits.lock().unwrap();
+        limits.retain(|_, (started, _)| started.elapsed() < RATE_LIMIT_WINDOW);
+        let (_, count) = limits
+            .entry(addr.ip())
+            .or_insert_with(|| (Instant::now(), 0));
+        *count += 1;
+        *count <= state.rate_limit
+    };
+    if !allowed {
+        metrics::API_RATE_LIMITED.inc();
+        return ApiError(
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many requests, try again in a minute".to_string(),
+        )
+        .into_response();
+    }
+    next.run(request).await
+}
+
+/// Accepts `Authorization: Bearer <key>` or `X-Api-Key: <key>` when
+/// `API_KEY` is set.
+async fn authorize(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let expected = match &state.api_key {
+        Some(key) => key,
+        None => return next.run(request).await,
+    };
+
+    let headers = request.headers();
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| {
+            headers
+                .get("x-api-key")
+                .and_then(|value| value.to_str().ok())
+        });
+
+    // compared in constant time so response times don't give the key away
+    let matches = given.is_some_and(|given| {
+        constant_time::verify_slices_are_equal(given.as_bytes(), expected.as_bytes()).is_ok()
+    });
+    if !matches {
+        return ApiError(StatusCode::UNAUTHORIZED, "Invalid API key".to_string()).into_response();
+    }
+    next.run(request).await
+}
+
+/// Holds a deep analysis slot when `depth` is past what a plain `!help`
+/// searches, so the API can't starve the bot of its engine.
+fn deep_slot(state: &ApiState, depth: u8) -> Result<Option<DeepAnalysisSlot>, ApiError> {
+    if depth <= DEFAULT_DEPTH {
+        return Ok(None);
+    }
+
+    match DeepAnalysisSlot::acquire(state.deep_analyses.clone(), API_SLOT_KEY) {
+        Some(slot) => Ok(Some(slot)),
+        None => Err(ApiError(
+            StatusCode::TOO_MANY_REQUESTS,
+            "A deep analysis is already running, try again when it's done".to_string(),
+        )),
+    }
+}
+
+/// The analysis without its rendered board, since `/render` can draw it
+/// again from the FEN.
+fn without_board(resp: GetStockFishResponse) -> GetStockFishResponse {
+    GetStockFishResponse {
+        image: Vec::new(),
+        ..resp
+    }
+}
+
+/// `GET /analyze/{user}?flip=&depth=&threats=`, the game `user` is playing
+/// on lichess right now.
+#[instrument(name = "api", skip_all, fields(request_id = %logging::request_id(), target = %user))]
+async fn analyze_user(
+    State(state): State<ApiState>,
+    Path(user): Path<String>,
+    Query(params): Query<AnalyzeParams>,
+) -> Result<Json<GetStockFishResponse>, ApiError> {
+    let depth = params.depth.unwrap_or(DEFAULT_DEPTH);
+    let key = format!(
+        "{}:{}:{}:{}",
+        user.to_lowercase(),
+        params.flip,
+        depth,
+        params.threats
+    );
+
+    if let Some((at, resp)) = state.cache.lock().unwrap().get(&key) {
+        if at.elapsed() < state.cache_for {
+            metrics::cache_lookup("api", true);
+            return Ok(Json(resp.clone()));
+        }
+    }
+    metrics::cache_lookup("api", false);
+
+    let _slot = deep_slot(&state, depth)?;
+    let channel = user.as_str();
+    let channel = Arc::new(Mutex::new(&channel));
+    let resp = without_board(help(channel, params.flip, depth, params.threats).await?);
+
+    let _ = Analysis::insert(Analysis::new(format!("api:{}", user), &resp));
+    {
+        // the key comes from whoever's calling, so don't let it grow unbounded
+        let mut cache = state.cache.lock().unwrap();
+        cache.retain(|_, (at, _)| at.elapsed() < state.cache_for);
+        if cache.len() >= API_CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(key, (Instant::now(), resp.clone()));
+    }
+
+    Ok(Json(resp))
+}
+
+/// `POST /analyze?depth=&threats=` with a FEN or a PGN as the body.
+#[instrument(name = "api", skip_all, fields(request_id = %logging::request_id()))]
+async fn analyze_body(
+    State(state): State<ApiState>,
+    Query(params): Query<AnalyzeParams>,
+    body: String,
+) -> Result<Json<GetStockFishResponse>, ApiError> {
+    let body = body.trim();
+    if body.is_empty() {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            "Expected a FEN or PGN body".to_string(),
+        ));
+    }
+
+    let depth = params.depth.unwrap_or(DEFAULT_DEPTH);
+    let _slot = deep_slot(&state, depth)?;
+    let name = uuid::Uuid::new_v4().to_string();
+
+    let resp = match position::parse_fen(body) {
+        Some(_) => help_fen(body, vec![], depth, params.threats, name).await?,
+        // anything that isn't a FEN is taken as a PGN, which fails to
+        // replay before the engine is involved if it isn't one either
+        None => help_pgn(body, depth, params.threats, name).await?,
+    };
+
+    Ok(Json(without_board(resp)))
+}
+
+/// `GET /render?fen=&black=`, the position as a PNG.
+#[instrument(name = "api", skip_all, fields(request_id = %logging::request_id(), fen = %params.fen))]
+async fn render(Query(params): Query<RenderParams>) -> Result<Response, ApiError> {
+    let pos = position::parse_fen(&params.fen).ok_or_else(|| {
+        ApiError(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid FEN {:?}", params.fen),
+        )
+    })?;
+    let orientation = if params.black {
+        Color::Black
+    } else {
+        Color::White
+    };
+
+    // drawing and encoding the image is CPU work, keep it off the runtime
+    let png = tokio::task::spawn_blocking(move || {
+        gen_board(
+            position::to_board(&pos, orientation),
+            position::color_to_mode(orientation),
+            &position::to_fen(&pos),
+            Variant::Standard,
+            vec![],
+            "None".to_string(),
+            None,
+        )
+    })
+    .await
+    .map_err(|why| {
+        ApiError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error drawing board {:?}", why),
+        )
+    })?;
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "image/png".parse().unwrap());
+    Ok((headers, Bytes::from(png)).into_response())
+}
+
+/// `GET /metrics`, in the Prometheus text format.
+async fn metrics_text() -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        "text/plain; version=0.0.4".parse().unwrap(),
+    );
+    (headers, metrics::gather()).into_response()
+}
+
+/// `GET /healthz`, answered as long as the process is serving requests.
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// `GET /readyz`, 200 once the gateway is connected and the database
+/// answers, 503 with whatever isn't otherwise. Without the bot there's no
+/// gateway and it's reported as `null`.
+async fn readyz() -> Response {
+    let gateway = match WITHOUT_GATEWAY.load(Ordering::SeqCst) {
+        true => None,
+        false => Some(GATEWAY_CONNECTED.load(Ordering::SeqCst)),
+    };
+    let database = tokio::task::spawn_blocking(databse::ping)
+        .await
+        .unwrap_or(false);
+
+    let status = if gateway.unwrap_or(true) && database {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = Json(serde_json::json!({ "gateway": gateway, "database": database }));
+    (status, body).into_response()
+}
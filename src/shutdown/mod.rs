@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use serenity::all::{ChannelId, CreateEmbed, EditMessage, Http, MessageId};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::i18n::{tr, Locale};
+use crate::interactions::{action_buttons, ViewState};
+
+/// How long in-flight analyses get to finish once we're asked to stop,
+/// unless `SHUTDOWN_TIMEOUT_SECS` says otherwise.
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 20;
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN: Notify = Notify::const_new();
+
+static NEXT_JOB: AtomicU64 = AtomicU64::new(0);
+/// A job's message, who asked for it and the language it's written in.
+type JobMessage = (ChannelId, MessageId, Option<u64>, Locale);
+
+static JOBS: LazyLock<std::sync::Mutex<HashMap<u64, JobMessage>>> =
+    LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// An analysis that owns a message until it's done. Messages whose job is
+/// still running when the bot stops are edited so they don't sit at
+/// "Loading..." with their buttons disabled. `requester` keeps the right to
+/// delete it and `locale` is the language the message is written in.
+pub struct Job {
+    id: u64,
+}
+
+impl Job {
+    pub fn start(
+        channel_id: ChannelId,
+        message_id: MessageId,
+        requester: Option<u64>,
+        locale: Locale,
+    ) -> Self {
+        let id = NEXT_JOB.fetch_add(1, Ordering::SeqCst);
+        JOBS.lock()
+            .unwrap()
+            .insert(id, (channel_id, message_id, requester, locale));
+        Self { id }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        JOBS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Whether we've been asked to stop and shouldn't start anything new.
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Resolves once shutdown has begun.
+pub async fn wait() {
+    loop {
+        let notified = SHUTDOWN.notified();
+        if is_shutting_down() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Sleeps for `duration`, waking early if we start shutting down. Returns
+/// false when it was cut short.
+pub async fn sleep(duration: Duration) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => true,
+        _ = wait() => false,
+    }
+}
+
+/// Resolves on Ctrl-C or SIGTERM.
+pub async fn signal() {
+    let ctrl_c = tokio::signal::ctrl_c();
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = ctrl_c => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(why) => {
+                warn!("Error listening for SIGTERM: {why:?}");
+                let _ = ctrl_c.await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = ctrl_c.await;
+}
+
+/// Stops new work and wakes everything waiting on `wait`.
+pub fn stop() {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+    SHUTDOWN.notify_waiters();
+}
+
+/// Stops new work, gives running jobs until the timeout to finish and then
+/// marks whatever is left as interrupted.
+pub async fn drain(http: Arc<Http>) {
+    stop();
+
+    let timeout = env::var("SHUTDOWN_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS);
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+
+    loop {
+        let running = JOBS.lock().unwrap().len();
+        if running == 0 {
+            info!("All analyses finished");
+            return;
+        }
+        if Instant::now() >= deadline {
+            warn!(
+                "{} analyses still running, marking them interrupted",
+                running
+            );
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+
+    let unfinished: Vec<JobMessage> = JOBS.lock().unwrap().values().copied().collect();
+    for (channel_id, message_id, requester, locale) in unfinished {
+        let state = ViewState {
+            requester,
+            ..ViewState::default()
+        };
+        let embed = CreateEmbed::default()
+            .title(tr(locale, "restarted-title"))
+            .description(tr(locale, "restarted-description"));
+        let edit_message = EditMessage::new()
+            .embed(embed)
+            .remove_all_attachments()
+            .components(action_buttons(state, None, false, locale));
+
+        if let Err(why) = channel_id
+            .edit_message(&http, message_id, edit_message)
+            .await
+        {
+            warn!("Error marking message {} interrupted: {why:?}", message_id);
+        }
+    }
+}
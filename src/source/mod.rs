@@ -0,0 +1,819 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use async_trait::async_trait;
+use prometheus::{Histogram, IntCounter};
+use serde::Deserialize;
+use shakmaty::Color;
+
+use crate::metrics;
+use crate::models::error::Error;
+use crate::position::{self, Variant};
+
+/// Somewhere we can ask which games players are in right now.
+#[async_trait]
+pub trait GameSource: Send + Sync {
+    /// Maps each user (lowercased) that is currently playing to their game id.
+    /// Users that aren't playing are left out.
+    async fn current_games(&self, users: &[String]) -> Result<HashMap<String, String>, Error>;
+
+    /// The game `user` is playing, or failing that the last one they
+    /// finished. `None` if they've never played or don't exist.
+    async fn current_game(&self, user: &str) -> Result<Option<Game>, Error>;
+}
+
+/// The game each of `watched` has started since the last one we saw of
+/// theirs, lined up with `watched`, asking `source` once for everyone.
+/// `watched` pairs a user with that last game id.
+pub async fn new_games(
+    source: &impl GameSource,
+    watched: &[(&str, Option<&str>)],
+) -> Result<Vec<Option<String>>, Error> {
+    let mut users: Vec<String> = watched
+        .iter()
+        .map(|(user, _)| user.to_lowercase())
+        .collect();
+    users.sort();
+    users.dedup();
+    if users.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let games = source.current_games(&users).await?;
+    Ok(watched
+        .iter()
+        .map(|(user, last_game_id)| {
+            games
+                .get(&user.to_lowercase())
+                .filter(|game_id| Some(game_id.as_str()) != *last_game_id)
+                .cloned()
+        })
+        .collect())
+}
+
+/// The sites we can follow players on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    #[default]
+    Lichess,
+    ChessCom,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 2] = [Platform::Lichess, Platform::ChessCom];
+
+    /// How the platform is stored and passed around, "lichess" or "chesscom".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Platform::Lichess => "lichess",
+            Platform::ChessCom => "chesscom",
+        }
+    }
+
+    /// The site's own name, for people.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Lichess => "lichess",
+            Platform::ChessCom => "chess.com",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Platform> {
+        match s {
+            "lichess" => Some(Platform::Lichess),
+            "chesscom" => Some(Platform::ChessCom),
+            _ => None,
+        }
+    }
+}
+
+/// A user on one of the platforms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+    pub platform: Platform,
+    pub name: String,
+}
+
+impl Player {
+    pub fn new(platform: Platform, name: &str) -> Self {
+        Self {
+            platform,
+            name: name.to_string(),
+        }
+    }
+
+    pub fn lichess(name: &str) -> Self {
+        Self::new(Platform::Lichess, name)
+    }
+
+    /// "MAGNUS" for lichess players, who came first, "MAGNUS · chess.com"
+    /// for everyone else.
+    pub fn title(&self) -> String {
+        match self.platform {
+            Platform::Lichess => self.name.to_uppercase(),
+            platform => format!("{} · {}", self.name.to_uppercase(), platform.name()),
+        }
+    }
+}
+
+/// A lichess game picked out by url or id, at the ply and from the side a
+/// `#23` anchor and `/black` suffix ask for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRef {
+    pub id: String,
+    /// Half-moves to replay, or `None` for the latest position.
+    pub ply: Option<usize>,
+    pub orientation: Color,
+}
+
+impl GameRef {
+    /// Reads `abcdEFGH`, `lichess.org/abcdEFGH` or a full url like
+    /// `https://lichess.org/abcdEFGHijkl/black#23`. Player links carry four
+    /// extra characters, which we drop.
+    pub fn parse(input: &str) -> Option<GameRef> {
+        let input = input.trim();
+        let (input, anchor) = match input.split_once('#') {
+            Some((input, anchor)) => (input, Some(anchor)),
+            None => (input, None),
+        };
+        let ply = match anchor {
+            Some(anchor) => Some(anchor.parse().ok()?),
+            None => None,
+        };
+
+        let path = input.split('?').next().unwrap_or(input);
+        let path = path
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_start_matches("www.");
+        let path = path.strip_prefix("lichess.org").unwrap_or(path);
+        let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+
+        let id = segments.next()?;
+        if !(id.len() == 8 || id.len() == 12) || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        let orientation = match segments.next() {
+            None | Some("white") => Color::White,
+            Some("black") => Color::Black,
+            Some(_) => return None,
+        };
+        if segments.next().is_some() {
+            return None;
+        }
+
+        Some(GameRef {
+            id: id[..8].to_string(),
+            ply,
+            orientation,
+        })
+    }
+
+    /// The game's lichess url, with the side and ply when they aren't the
+    /// defaults.
+    pub fn url(&self) -> String {
+        let mut url = format!("https://lichess.org/{}", self.id);
+        if self.orientation == Color::Black {
+            url.push_str("/black");
+        }
+        if let Some(ply) = self.ply {
+            url.push_str(&format!("#{}", ply));
+        }
+        url
+    }
+}
+
+/// What an analysis message follows: whatever game a player is in, or one
+/// game we were pointed at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Player(Player),
+    Game(GameRef),
+}
+
+impl Target {
+    /// The player's name or the game id, for logs and image file names.
+    pub fn name(&self) -> &str {
+        match self {
+            Target::Player(player) => &player.name,
+            Target::Game(game) => &game.id,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            Target::Player(player) => player.title(),
+            Target::Game(game) => game.url().trim_start_matches("https://").to_string(),
+        }
+    }
+
+    /// Whether refreshing can show a different position, so tracking is
+    /// worth it. A game pinned to a ply never changes.
+    pub fn is_live(&self) -> bool {
+        match self {
+            Target::Player(_) => true,
+            Target::Game(game) => game.ply.is_none(),
+        }
+    }
+}
+
+/// A game as a source reports it, whichever site it's on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    pub id: String,
+    pub url: String,
+    pub white: String,
+    pub black: String,
+    /// The moves so far, with whatever tags the site adds.
+    pub pgn: String,
+    /// The current position, when the site sends one alongside the moves.
+    pub fen: Option<String>,
+    pub finished: bool,
+    /// The rules as the site names them, "Standard", "chess960" and so on.
+    pub rules: String,
+}
+
+impl Game {
+    /// The side `user` plays, if they're in this game.
+    pub fn color_of(&self, user: &str) -> Option<Color> {
+        if self.white.eq_ignore_ascii_case(user) {
+            Some(Color::White)
+        } else if self.black.eq_ignore_ascii_case(user) {
+            Some(Color::Black)
+        } else {
+            None
+        }
+    }
+
+    /// The variant the game is played in, or an error naming it when it's
+    /// one we can't analyse.
+    pub fn variant(&self) -> Result<Variant, Error> {
+        Variant::parse(&self.rules)
+    }
+
+    /// Where the moves start from: the PGN's `FEN` tag for Chess960 and
+    /// games set up from a position, the usual start otherwise.
+    pub fn start_fen(&self) -> Option<String> {
+        position::pgn_tag(&self.pgn, "FEN")
+    }
+}
+
+/// Sends `request`, timing it and counting failures against the site's
+/// metrics. A 404 is `None`, the sites use it for unknown users and games.
+pub(crate) async fn fetch(
+    request: reqwest::RequestBuilder,
+    seconds: &Histogram,
+    failures: &IntCounter,
+) -> Result<Option<String>, Error> {
+    let timer = seconds.start_timer();
+    let res = request.send().await.map_err(|e| {
+        failures.inc();
+        Error::BasicError(format!("Error fetching {:?}", e))
+    })?;
+    timer.observe_duration();
+
+    match res.status().as_u16() {
+        200 => {}
+        404 => return Ok(None),
+        _ => {
+            failures.inc();
+            return Err(Error::BasicError(format!(
+                "Error fetching {} {:?}",
+                res.url(),
+                res.status()
+            )));
+        }
+    }
+
+    let body = res
+        .text()
+        .await
+        .map_err(|e| Error::BasicError(format!("Error fetching {:?}", e)))?;
+    Ok(Some(body))
+}
+
+/// lichess allows this many ids per status request.
+const LICHESS_STATUS_BATCH: usize = 100;
+
+pub struct Lichess;
+
+/// Finished games kept in `FINISHED_GAMES` before it starts over.
+const FINISHED_GAMES_SIZE: usize = 256;
+
+/// Finished lichess games never change, so each is only fetched once.
+static FINISHED_GAMES: LazyLock<Mutex<HashMap<String, Game>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+impl Lichess {
+    /// Any game by id, finished or not. `None` if there's no such game.
+    pub async fn game(&self, id: &str) -> Result<Option<Game>, Error> {
+        if let Some(game) = FINISHED_GAMES.lock().unwrap().get(id) {
+            return Ok(Some(game.clone()));
+        }
+
+        let url = format!("https://lichess.org/game/export/{}", id);
+        let pgn = fetch(
+            reqwest::Client::new().get(url),
+            &metrics::LICHESS_FETCH_SECONDS,
+            &metrics::LICHESS_FETCH_FAILURES,
+        )
+        .await?;
+
+        let game = pgn.and_then(|pgn| parse_lichess_game(&pgn));
+        if let Some(game) = game.as_ref().filter(|game| game.finished) {
+            let mut finished = FINISHED_GAMES.lock().unwrap();
+            if finished.len() >= FINISHED_GAMES_SIZE {
+                finished.clear();
+            }
+            finished.insert(id.to_string(), game.clone());
+        }
+        Ok(game)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LichessStatus {
+    name: String,
+    #[serde(rename = "playingId")]
+    playing_id: Option<String>,
+}
+
+#[async_trait]
+impl GameSource for Lichess {
+    async fn current_games(&self, users: &[String]) -> Result<HashMap<String, String>, Error> {
+        let mut games = HashMap::new();
+
+        for batch in users.chunks(LICHESS_STATUS_BATCH) {
+            let url = format!(
+                "https://lichess.org/api/users/status?ids={}&withGameIds=true",
+                batch.join(",")
+            );
+
+            let timer = metrics::LICHESS_FETCH_SECONDS.start_timer();
+            let res = reqwest::get(url).await.map_err(|e| {
+                metrics::LICHESS_FETCH_FAILURES.inc();
+                Error::BasicError(format!("Error fetching status {:?}", e))
+            })?;
+            timer.observe_duration();
+
+            if res.status().as_u16() != 200 {
+                metrics::LICHESS_FETCH_FAILURES.inc();
+                return Err(Error::BasicError(format!(
+                    "Error fetching status {:?}",
+                    res.status()
+                )));
+            }
+
+            let body = res
+                .text()
+                .await
+                .map_err(|e| Error::BasicError(format!("Error fetching status {:?}", e)))?;
+            let statuses: Vec<LichessStatus> = serde_json::from_str(&body)
+                .map_err(|e| Error::BasicError(format!("Error parsing status {:?}", e)))?;
+
+            for status in statuses {
+                if let Some(playing_id) = status.playing_id {
+                    games.insert(status.name.to_lowercase(), playing_id);
+                }
+            }
+        }
+
+        Ok(games)
+    }
+
+    async fn current_game(&self, user: &str) -> Result<Option<Game>, Error> {
+        // the ongoing game, or the last one if there isn't one
+        let url = format!("https://lichess.org/api/user/{}/current-game", user);
+        let pgn = fetch(
+            reqwest::Client::new().get(url),
+            &metrics::LICHESS_FETCH_SECONDS,
+            &metrics::LICHESS_FETCH_FAILURES,
+        )
+        .await?;
+
+        Ok(pgn.and_then(|pgn| parse_lichess_game(&pgn)))
+    }
+}
+
+/// Reads a game from a lichess PGN export.
+fn parse_lichess_game(pgn: &str) -> Option<Game> {
+    let url = position::pgn_tag(pgn, "Site")?;
+    let id = url.rsplit('/').next()?.to_string();
+
+    Some(Game {
+        id,
+        url,
+        white: position::pgn_tag(pgn, "White")?,
+        black: position::pgn_tag(pgn, "Black")?,
+        pgn: pgn.to_string(),
+        fen: None,
+        finished: position::pgn_tag(pgn, "Result").is_some_and(|result| result != "*"),
+        rules: position::pgn_tag(pgn, "Variant").unwrap_or_else(standard_rules),
+    })
+}
+
+const CHESSCOM_API: &str = "https://api.chess.com/pub/player";
+
+/// chess.com turns away requests that don't say who they are.
+const CHESSCOM_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// chess.com's public API. Only daily games are visible while they're being
+/// played, live ones show up in the monthly archives once they're over.
+pub struct ChessCom;
+
+#[derive(Debug, Deserialize)]
+struct ChessComGames {
+    games: Vec<ChessComGame>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChessComArchives {
+    archives: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChessComGame {
+    url: String,
+    #[serde(default)]
+    pgn: String,
+    fen: Option<String>,
+    #[serde(default = "standard_rules")]
+    rules: String,
+    white: ChessComPlayer,
+    black: ChessComPlayer,
+    /// Set on games in progress.
+    last_activity: Option<i64>,
+    /// Set on finished games.
+    end_time: Option<i64>,
+}
+
+fn standard_rules() -> String {
+    "chess".to_string()
+}
+
+/// Games in progress link to the players' profiles, archived ones inline
+/// them.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ChessComPlayer {
+    Profile { username: String },
+    Url(String),
+}
+
+impl ChessComPlayer {
+    fn username(&self) -> String {
+        match self {
+            ChessComPlayer::Profile { username } => username.clone(),
+            ChessComPlayer::Url(url) => url.rsplit('/').next().unwrap_or(url).to_string(),
+        }
+    }
+}
+
+impl ChessCom {
+    async fn get(&self, url: String) -> Result<Option<String>, Error> {
+        let request = reqwest::Client::new()
+            .get(url)
+            .header(reqwest::header::USER_AGENT, CHESSCOM_USER_AGENT);
+        fetch(
+            request,
+            &metrics::CHESSCOM_FETCH_SECONDS,
+            &metrics::CHESSCOM_FETCH_FAILURES,
+        )
+        .await
+    }
+
+    /// `user`'s daily games in progress, most recently moved in first.
+    async fn daily_games(&self, user: &str) -> Result<Vec<Game>, Error> {
+        let url = format!("{}/{}/games", CHESSCOM_API, user.to_lowercase());
+        match self.get(url).await? {
+            Some(body) => parse_chesscom_games(&body, false),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// The last game `user` finished, from their latest monthly archive.
+    async fn last_finished_game(&self, user: &str) -> Result<Option<Game>, Error> {
+        let url = format!("{}/{}/games/archives", CHESSCOM_API, user.to_lowercase());
+        let archives = match self.get(url).await? {
+            Some(body) => parse_chesscom_archives(&body)?,
+            None => return Ok(None),
+        };
+        let latest = match archives.last() {
+            Some(latest) => latest.clone(),
+            None => return Ok(None),
+        };
+
+        match self.get(latest).await? {
+            Some(body) => Ok(parse_chesscom_games(&body, true)?.into_iter().next()),
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl GameSource for ChessCom {
+    async fn current_games(&self, users: &[String]) -> Result<HashMap<String, String>, Error> {
+        let mut games = HashMap::new();
+
+        // no batch endpoint, so one request per user
+        for user in users {
+            if let Some(game) = self.daily_games(user).await?.into_iter().next() {
+                games.insert(user.to_lowercase(), game.id);
+            }
+        }
+
+        Ok(games)
+    }
+
+    async fn current_game(&self, user: &str) -> Result<Option<Game>, Error> {
+        if let Some(game) = self.daily_games(user).await?.into_iter().next() {
+            return Ok(Some(game));
+        }
+        self.last_finished_game(user).await
+    }
+}
+
+/// Reads a chess.com games list, either a player's games in progress or a
+/// monthly archive, newest first. Variants are kept, analysing one we don't
+/// support fails with its name.
+fn parse_chesscom_games(body: &str, finished: bool) -> Result<Vec<Game>, Error> {
+    let mut games: Vec<ChessComGame> = serde_json::from_str::<ChessComGames>(body)
+        .map_err(|e| Error::BasicError(format!("Error parsing chess.com games {:?}", e)))?
+        .games;
+    games.sort_by_key(|game| std::cmp::Reverse(game.last_activity.or(game.end_time)));
+
+    Ok(games
+        .into_iter()
+        .map(|game| Game {
+            id: game.url.rsplit('/').next().unwrap_or_default().to_string(),
+            white: game.white.username(),
+            black: game.black.username(),
+            url: game.url,
+            pgn: game.pgn,
+            fen: game.fen,
+            finished,
+            rules: game.rules,
+        })
+        .collect())
+}
+
+/// Reads the list of a player's monthly archive urls, oldest first.
+fn parse_chesscom_archives(body: &str) -> Result<Vec<String>, Error> {
+    serde_json::from_str::<ChessComArchives>(body)
+        .map(|archives| archives.archives)
+        .map_err(|e| Error::BasicError(format!("Error parsing chess.com archives {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers `current_games` from a fixed list and remembers who it was
+    /// asked about.
+    struct FakeSource {
+        playing: HashMap<String, String>,
+        asked: Mutex<Vec<Vec<String>>>,
+        fail: bool,
+    }
+
+    impl FakeSource {
+        fn new(playing: &[(&str, &str)]) -> Self {
+            Self {
+                playing: playing
+                    .iter()
+                    .map(|(user, game_id)| (user.to_string(), game_id.to_string()))
+                    .collect(),
+                asked: Mutex::new(Vec::new()),
+                fail: false,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl GameSource for FakeSource {
+        async fn current_games(&self, users: &[String]) -> Result<HashMap<String, String>, Error> {
+            self.asked.lock().unwrap().push(users.to_vec());
+            if self.fail {
+                return Err(Error::BasicError("source is down".to_string()));
+            }
+            Ok(self
+                .playing
+                .iter()
+                .filter(|(user, _)| users.contains(user))
+                .map(|(user, game_id)| (user.clone(), game_id.clone()))
+                .collect())
+        }
+
+        async fn current_game(&self, _user: &str) -> Result<Option<Game>, Error> {
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn new_games_are_the_ones_not_seen_yet() {
+        let source = FakeSource::new(&[("alice", "game2"), ("bob", "game3")]);
+        let watched = [
+            ("alice", Some("game1")),
+            ("bob", Some("game3")),
+            ("carol", None),
+        ];
+
+        let games = new_games(&source, &watched).await.unwrap();
+        assert_eq!(games, vec![Some("game2".to_string()), None, None]);
+    }
+
+    #[tokio::test]
+    async fn new_games_ask_once_per_user() {
+        let source = FakeSource::new(&[("alice", "game2")]);
+        // two channels subscribed to the same player, one spelt differently
+        let watched = [("Alice", None), ("alice", Some("game2")), ("bob", None)];
+
+        let games = new_games(&source, &watched).await.unwrap();
+        assert_eq!(games, vec![Some("game2".to_string()), None, None]);
+        assert_eq!(
+            *source.asked.lock().unwrap(),
+            vec![vec!["alice".to_string(), "bob".to_string()]]
+        );
+    }
+
+    #[tokio::test]
+    async fn new_games_without_anyone_watched_ask_nothing() {
+        let source = FakeSource::new(&[]);
+        assert!(new_games(&source, &[]).await.unwrap().is_empty());
+        assert!(source.asked.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn new_games_pass_on_source_errors() {
+        let source = FakeSource {
+            fail: true,
+            ..FakeSource::new(&[("alice", "game2")])
+        };
+        assert!(new_games(&source, &[("alice", None)]).await.is_err());
+    }
+
+    fn game_ref(id: &str, ply: Option<usize>, orientation: Color) -> Option<GameRef> {
+        Some(GameRef {
+            id: id.to_string(),
+            ply,
+            orientation,
+        })
+    }
+
+    #[test]
+    fn game_refs_from_bare_ids() {
+        assert_eq!(
+            GameRef::parse("abcdEFGH"),
+            game_ref("abcdEFGH", None, Color::White)
+        );
+        // a player's link has four more characters naming their side
+        assert_eq!(
+            GameRef::parse(" abcdEFGHijkl "),
+            game_ref("abcdEFGH", None, Color::White)
+        );
+    }
+
+    #[test]
+    fn game_refs_from_urls() {
+        for input in [
+            "lichess.org/abcdEFGH",
+            "https://lichess.org/abcdEFGH",
+            "http://www.lichess.org/abcdEFGH/",
+            "https://lichess.org/abcdEFGHijkl?theme=brown",
+            "https://lichess.org/abcdEFGH/white",
+        ] {
+            assert_eq!(
+                GameRef::parse(input),
+                game_ref("abcdEFGH", None, Color::White),
+                "{}",
+                input
+            );
+        }
+        assert_eq!(
+            GameRef::parse("https://lichess.org/abcdEFGH/black"),
+            game_ref("abcdEFGH", None, Color::Black)
+        );
+    }
+
+    #[test]
+    fn game_refs_with_ply_anchors() {
+        assert_eq!(
+            GameRef::parse("https://lichess.org/abcdEFGHijkl/black#23"),
+            game_ref("abcdEFGH", Some(23), Color::Black)
+        );
+        assert_eq!(
+            GameRef::parse("abcdEFGH#0"),
+            game_ref("abcdEFGH", Some(0), Color::White)
+        );
+        assert_eq!(GameRef::parse("abcdEFGH#"), None);
+        assert_eq!(GameRef::parse("abcdEFGH#last"), None);
+    }
+
+    #[test]
+    fn game_refs_round_trip_through_urls() {
+        let game = game_ref("abcdEFGH", Some(23), Color::Black).unwrap();
+        assert_eq!(game.url(), "https://lichess.org/abcdEFGH/black#23");
+        assert_eq!(GameRef::parse(&game.url()), Some(game));
+    }
+
+    #[test]
+    fn game_refs_reject_garbage() {
+        for input in [
+            "",
+            "   ",
+            "abcdEFG",
+            "abcdEFGHi",
+            "abcd-FGH",
+            "https://lichess.org/",
+            "https://lichess.org/@/DrNykterstein",
+            "https://lichess.org/abcdEFGH/red",
+            "https://lichess.org/abcdEFGH/black/extra",
+            "https://chess.com/game/live/123456789",
+        ] {
+            assert_eq!(GameRef::parse(input), None, "{:?}", input);
+        }
+    }
+
+    fn chesscom_fixture(name: &str) -> String {
+        let path = format!(
+            "{}/tests/fixtures/chesscom/{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn chesscom_games_in_progress() {
+        let games = parse_chesscom_games(&chesscom_fixture("ongoing"), false).unwrap();
+
+        // the most recently played first
+        let ids: Vec<&str> = games.iter().map(|game| game.id.as_str()).collect();
+        assert_eq!(ids, ["771303388", "771245602"]);
+
+        let game = &games[0];
+        assert_eq!(game.url, "https://www.chess.com/game/daily/771303388");
+        assert_eq!(
+            (game.white.as_str(), game.black.as_str()),
+            ("hikaru", "magnuscarlsen")
+        );
+        assert_eq!(game.color_of("Hikaru"), Some(Color::White));
+        assert!(!game.finished);
+        assert_eq!(position::pgn_sans(&game.pgn), ["d4", "Nf6", "c4", "e6"]);
+        assert_eq!(
+            game.fen.as_deref(),
+            Some("rnbqkb1r/pppp1ppp/4pn2/8/2PP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3")
+        );
+        assert_eq!(game.variant().unwrap(), Variant::Standard);
+
+        let chess960 = &games[1];
+        assert_eq!(chess960.variant().unwrap(), Variant::Chess960);
+        assert_eq!(
+            chess960.start_fen().as_deref(),
+            Some("rbbnkqrn/pppppppp/8/8/8/8/PPPPPPPP/RBBNKQRN w GAga - 0 1")
+        );
+    }
+
+    #[test]
+    fn chesscom_finished_games() {
+        let games = parse_chesscom_games(&chesscom_fixture("finished"), true).unwrap();
+
+        let ids: Vec<&str> = games.iter().map(|game| game.id.as_str()).collect();
+        assert_eq!(ids, ["92200174813", "92114485167"]);
+
+        let game = &games[0];
+        assert_eq!(
+            (game.white.as_str(), game.black.as_str()),
+            ("Firouzja2003", "Hikaru")
+        );
+        assert_eq!(game.color_of("hikaru"), Some(Color::Black));
+        assert!(game.finished);
+        assert_eq!(position::pgn_sans(&game.pgn), ["f3", "e5", "g4", "Qh4#"]);
+    }
+
+    #[test]
+    fn chesscom_empty_archive() {
+        assert!(parse_chesscom_games(&chesscom_fixture("empty"), true)
+            .unwrap()
+            .is_empty());
+        assert!(parse_chesscom_archives(&chesscom_fixture("no-archives"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn chesscom_archives_oldest_first() {
+        let archives = parse_chesscom_archives(&chesscom_fixture("archives")).unwrap();
+        assert_eq!(
+            archives.last().map(String::as_str),
+            Some("https://api.chess.com/pub/player/hikaru/games/2024/10")
+        );
+        assert_eq!(archives.len(), 2);
+    }
+
+    #[test]
+    fn chesscom_bad_json_is_an_error() {
+        assert!(parse_chesscom_games("<html>", false).is_err());
+        assert!(parse_chesscom_archives(&chesscom_fixture("ongoing")).is_err());
+    }
+}
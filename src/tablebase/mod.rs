@@ -0,0 +1,279 @@
+use std::env;
+use std::sync::OnceLock;
+
+use fluent_bundle::FluentValue;
+use serde::{Deserialize, Serialize};
+use shakmaty::san::San;
+use shakmaty::{Chess, Color, Position};
+use shakmaty_syzygy::{Tablebase, Wdl};
+use tracing::{info, warn};
+
+use crate::i18n::{tr, tr_args, Locale};
+use crate::notation;
+use crate::position;
+
+static TABLEBASE: OnceLock<Option<Tablebase<Chess>>> = OnceLock::new();
+
+/// The exact result of a position, from the side to move's point of view.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Probe {
+    #[serde(with = "wdl")]
+    pub wdl: Wdl,
+    /// Plies to the next capture or pawn move, as the DTZ tables count them.
+    pub dtz: i32,
+    pub best_move: Option<String>,
+    pub best_move_san: Option<String>,
+}
+
+impl Probe {
+    /// "Win in 23 with DTZ-optimal Kf5" in `locale`.
+    pub fn describe(&self, locale: Locale) -> String {
+        let plies = || [("plies", FluentValue::from(self.dtz.abs()))];
+        let outcome = match self.wdl {
+            Wdl::Win => tr_args(locale, "tablebase-win", &plies()),
+            Wdl::Loss => tr_args(locale, "tablebase-loss", &plies()),
+            Wdl::CursedWin => tr(locale, "tablebase-cursed-win"),
+            Wdl::BlessedLoss => tr(locale, "tablebase-blessed-loss"),
+            Wdl::Draw => tr(locale, "tablebase-draw"),
+        };
+
+        match &self.best_move_san {
+            Some(san) => tr_args(
+                locale,
+                "tablebase-best-move",
+                &[
+                    ("outcome", FluentValue::from(outcome)),
+                    ("move", FluentValue::from(notation::localize(san, locale))),
+                ],
+            ),
+            None => outcome,
+        }
+    }
+
+    /// The result as a win chance for the side to move, in the same -100..100
+    /// range `evaluate` reports.
+    pub fn chance_to_win(&self) -> f32 {
+        match self.wdl {
+            Wdl::Win => 100.0,
+            Wdl::Loss => -100.0,
+            _ => 0.0,
+        }
+    }
+
+    /// The result in centipawns from white's point of view.
+    pub fn white_cp(&self, turn: Color, cap: i32) -> i32 {
+        let cp = match self.wdl {
+            Wdl::Win => cap,
+            Wdl::Loss => -cap,
+            _ => 0,
+        };
+        match turn {
+            Color::White => cp,
+            Color::Black => -cp,
+        }
+    }
+}
+
+/// `Wdl` as the names lichess' tablebase API uses, since shakmaty-syzygy
+/// doesn't serialize it.
+mod wdl {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use shakmaty_syzygy::Wdl;
+
+    pub fn serialize<S: Serializer>(wdl: &Wdl, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match wdl {
+            Wdl::Win => "win",
+            Wdl::CursedWin => "cursed-win",
+            Wdl::Draw => "draw",
+            Wdl::BlessedLoss => "blessed-loss",
+            Wdl::Loss => "loss",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Wdl, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "win" => Ok(Wdl::Win),
+            "cursed-win" => Ok(Wdl::CursedWin),
+            "draw" => Ok(Wdl::Draw),
+            "blessed-loss" => Ok(Wdl::BlessedLoss),
+            "loss" => Ok(Wdl::Loss),
+            other => Err(de::Error::unknown_variant(
+                other,
+                &["win", "cursed-win", "draw", "blessed-loss", "loss"],
+            )),
+        }
+    }
+}
+
+/// Tables found in the directories listed in `SYZYGY_PATH`, separated like
+/// `PATH`. `None` when nothing is configured or nothing could be opened.
+fn tablebase() -> Option<&'static Tablebase<Chess>> {
+    TABLEBASE
+        .get_or_init(|| {
+            let paths = env::var("SYZYGY_PATH").ok()?;
+            let mut tablebase = Tablebase::new();
+            let mut found = 0;
+
+            for dir in env::split_paths(&paths) {
+                match tablebase.add_directory(&dir) {
+                    Ok(count) => found += count,
+                    Err(why) => warn!("Error reading syzygy tables in {:?}: {why:?}", dir),
+                }
+            }
+
+            info!("Loaded {} syzygy tables", found);
+            (found > 0).then_some(tablebase)
+        })
+        .as_ref()
+}
+
+/// Probes `fen` if it has few enough pieces for the loaded tables.
+pub fn probe(fen: &str) -> Option<Probe> {
+    probe_position(&position::parse_fen(fen)?)
+}
+
+pub fn probe_position(pos: &Chess) -> Option<Probe> {
+    let tablebase = tablebase()?;
+    if pos.board().occupied().count() > tablebase.max_pieces() {
+        return None;
+    }
+
+    let wdl = match tablebase.probe_wdl_after_zeroing(pos) {
+        Ok(wdl) => wdl,
+        Err(why) => {
+            warn!("Error probing tablebase: {why:?}");
+            return None;
+        }
+    };
+    let dtz = tablebase
+        .probe_dtz(pos)
+        .map(|dtz| dtz.ignore_rounding().0)
+        .unwrap_or(0);
+    let best_move = tablebase.best_move(pos).ok().flatten().map(|(m, _)| m);
+
+    Some(Probe {
+        wdl,
+        dtz,
+        best_move: best_move.map(position::to_uci),
+        best_move_san: best_move.map(|m| San::from_move(pos, m).to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The 3 and 4 piece tables aren't bundled, so the tests that probe them
+    // are ignored. Run them with `SYZYGY_PATH=... cargo test -- --ignored`.
+
+    #[test]
+    #[ignore = "needs the KQvK and KRvK syzygy tables in SYZYGY_PATH"]
+    fn kqvk_mate_in_one() {
+        let probe = probe("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1").unwrap();
+        assert_eq!(probe.wdl, Wdl::Win);
+        assert_eq!(probe.dtz, 1);
+        assert_eq!(probe.best_move.as_deref(), Some("b1b8"));
+        assert_eq!(
+            probe.describe(Locale::English),
+            "Win in 1 with DTZ-optimal Qb8"
+        );
+    }
+
+    #[test]
+    #[ignore = "needs the KQvK and KRvK syzygy tables in SYZYGY_PATH"]
+    fn kqvk_losing_side() {
+        let probe = probe("8/8/8/4k3/8/8/8/3QK3 b - - 0 1").unwrap();
+        assert_eq!(probe.wdl, Wdl::Loss);
+        assert!(probe.dtz < 0);
+        assert_eq!(probe.chance_to_win(), -100.0);
+        assert_eq!(probe.white_cp(Color::Black, 1000), 1000);
+    }
+
+    #[test]
+    #[ignore = "needs the KQvK and KRvK syzygy tables in SYZYGY_PATH"]
+    fn krvk_win_and_draw() {
+        let probe_win = probe("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(probe_win.wdl, Wdl::Win);
+        assert!(probe_win.dtz > 0);
+        assert!(probe_win.best_move.is_some());
+
+        // the only move takes the rook
+        let probe_draw = probe("8/8/8/8/8/8/1R6/k6K b - - 0 1").unwrap();
+        assert_eq!(probe_draw.wdl, Wdl::Draw);
+        assert_eq!(probe_draw.best_move_san.as_deref(), Some("Kxb2"));
+        assert_eq!(probe_draw.chance_to_win(), 0.0);
+    }
+
+    #[test]
+    #[ignore = "needs the KQvK and KRvK syzygy tables in SYZYGY_PATH"]
+    fn too_many_pieces_are_not_probed() {
+        assert!(probe("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_none());
+    }
+
+    #[test]
+    fn describe_wins_losses_and_draws() {
+        let probe = |wdl, dtz, san: Option<&str>| Probe {
+            wdl,
+            dtz,
+            best_move: None,
+            best_move_san: san.map(str::to_string),
+        };
+        assert_eq!(
+            probe(Wdl::Win, 23, Some("Kf5")).describe(Locale::English),
+            "Win in 23 with DTZ-optimal Kf5"
+        );
+        assert_eq!(
+            probe(Wdl::Loss, -12, None).describe(Locale::English),
+            "Loss in 12"
+        );
+        assert_eq!(
+            probe(Wdl::CursedWin, 101, None).describe(Locale::English),
+            "Win, but drawn by the 50 move rule"
+        );
+        assert_eq!(
+            probe(Wdl::BlessedLoss, -101, None).describe(Locale::English),
+            "Loss, but saved by the 50 move rule"
+        );
+        assert_eq!(
+            probe(Wdl::Draw, 0, Some("Kxb2")).describe(Locale::English),
+            "Draw with DTZ-optimal Kxb2"
+        );
+    }
+
+    #[test]
+    fn describe_in_other_languages() {
+        let probe = Probe {
+            wdl: Wdl::Win,
+            dtz: 5,
+            best_move: Some("d1d8".to_string()),
+            best_move_san: Some("Qd8#".to_string()),
+        };
+        assert_eq!(
+            probe.describe(Locale::German),
+            "Gewinn in 5 mit dem DTZ-optimalen Zug Dd8#"
+        );
+        assert_eq!(
+            probe.describe(Locale::Spanish),
+            "Victoria en 5 con Dd8#, óptima según DTZ"
+        );
+    }
+
+    #[test]
+    fn probes_round_trip_through_json() {
+        let probe = Probe {
+            wdl: Wdl::BlessedLoss,
+            dtz: -101,
+            best_move: None,
+            best_move_san: None,
+        };
+        let json = serde_json::to_value(&probe).unwrap();
+        assert_eq!(json["wdl"], "blessed-loss");
+        let back: Probe = serde_json::from_value(json).unwrap();
+        assert_eq!(back.wdl, Wdl::BlessedLoss);
+        assert_eq!(back.dtz, -101);
+        assert!(serde_json::from_str::<Probe>(
+            r#"{"wdl":"mate","dtz":0,"best_move":null,"best_move_san":null}"#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use serenity::all::{
+    Colour, CreateActionRow, CreateEmbed, CreateEmbedFooter, CreateMessage, EditMessage, Timestamp,
+};
+
+use crate::board::{self, GetStockFishResponse, Threat};
+use crate::book;
+use crate::engine;
+use crate::i18n::{tr, tr_args, Locale};
+use crate::interactions::{action_buttons, ViewState};
+use crate::models::error::Error;
+use crate::notation;
+use crate::position::{self, Variant};
+use crate::source::Target;
+
+/// Shown in place of the board while the engine is working.
+const LOADING_IMAGE: &str =
+    "https://dummyimage.com/1024x1024/2b2d31/ffffff.png&text=Fetching+Stockfish...";
+
+const LOADING_COLOUR: Colour = Colour(0x5865f2);
+const FAILED_COLOUR: Colour = Colour(0x992d22);
+const WINNING_COLOUR: Colour = Colour(0x2ecc71);
+const EQUAL_COLOUR: Colour = Colour(0x95a5a6);
+const LOSING_COLOUR: Colour = Colour(0xe67e22);
+const LOSING_BADLY_COLOUR: Colour = Colour(0xe74c3c);
+
+/// Below this win chance the player is told to consider resigning.
+const LOSING_BADLY: f32 = -40.0;
+
+/// Where an analysis is at.
+pub enum AnalysisState {
+    Loading,
+    Failed(String),
+    /// The analysis and the url of its uploaded board.
+    Ready(Box<GetStockFishResponse>, String),
+}
+
+/// Everything an analysis message shows, so `!help`, the buttons and
+/// subscriptions all draw the same embed for the same state.
+pub struct AnalysisView {
+    /// The player or game this is an analysis of.
+    pub target: Target,
+    pub state: AnalysisState,
+    pub view: ViewState,
+    pub locale: Locale,
+}
+
+impl AnalysisView {
+    pub fn loading(target: &Target, view: ViewState, locale: Locale) -> Self {
+        Self {
+            target: target.clone(),
+            state: AnalysisState::Loading,
+            view,
+            locale,
+        }
+    }
+
+    pub fn failed(target: &Target, why: &Error, view: ViewState, locale: Locale) -> Self {
+        Self {
+            target: target.clone(),
+            state: AnalysisState::Failed(why.to_string()),
+            view,
+            locale,
+        }
+    }
+
+    pub fn ready(
+        target: &Target,
+        resp: GetStockFishResponse,
+        image_url: String,
+        view: ViewState,
+        locale: Locale,
+    ) -> Self {
+        Self {
+            target: target.clone(),
+            state: AnalysisState::Ready(Box::new(resp), image_url),
+            view,
+            locale,
+        }
+    }
+
+    fn label(&self, id: &str) -> String {
+        tr(self.locale, id)
+    }
+
+    pub fn embed(&self) -> CreateEmbed {
+        let embed = CreateEmbed::default()
+            .title(self.target.title())
+            .timestamp(Timestamp::now());
+
+        match &self.state {
+            AnalysisState::Loading => {
+                let loading = format!("*{}*", tr(self.locale, "loading"));
+                self.placeholder(embed, &loading)
+                    .colour(LOADING_COLOUR)
+                    .image(LOADING_IMAGE)
+                    .footer(self.footer(self.view.depth))
+            }
+            AnalysisState::Failed(why) => {
+                let not_available = format!("*{}*", tr(self.locale, "not-available"));
+                let error = tr_args(
+                    self.locale,
+                    "error-getting-help",
+                    &[("error", format!("{:?}", why).into())],
+                );
+                self.placeholder(embed, &not_available)
+                    .colour(FAILED_COLOUR)
+                    .description(format!("> {}", error))
+                    .footer(self.footer(self.view.depth))
+            }
+            AnalysisState::Ready(resp, image_url) => self
+                .result(embed, resp)
+                .image(image_url)
+                .footer(self.footer(resp.depth)),
+        }
+    }
+
+    /// The fields a finished analysis fills in, all showing `value`.
+    fn placeholder(&self, embed: CreateEmbed, value: &str) -> CreateEmbed {
+        embed
+            .field(self.label("label-evaluation"), value, true)
+            .field(self.label("label-forced-mate"), value, true)
+            .field(self.label("label-best-move"), value, true)
+    }
+
+    fn result(&self, mut embed: CreateEmbed, resp: &GetStockFishResponse) -> CreateEmbed {
+        let locale = self.locale;
+
+        embed = embed
+            .colour(colour(resp.evaluation))
+            .field(
+                self.label("label-evaluation"),
+                describe_evaluation(resp.evaluation, locale),
+                true,
+            )
+            .field(
+                self.label("label-forced-mate"),
+                match resp.mate {
+                    Some(mate) => mate.to_string(),
+                    None => tr(locale, "forced-mate-none"),
+                },
+                true,
+            );
+
+        if let Some(game_over) =
+            board::game_over_in(&resp.fen, resp.variant, locale).or_else(|| resp.game_over.clone())
+        {
+            embed = embed.field(self.label("label-game-over"), game_over, true);
+        } else {
+            let label = if resp.turn == resp.player {
+                "label-your-move"
+            } else {
+                "label-opponent-move"
+            };
+            let mut best_move = notation::best_move_to_san(&resp.fen, resp.variant, &resp.bestmove);
+            best_move = notation::localize(&best_move, locale);
+            if resp.book_moves.iter().any(|m| m.uci == resp.bestmove) {
+                best_move = format!("{} {}", best_move, tr(locale, "book-marker"));
+            }
+            embed = embed.field(self.label(label), best_move, true);
+        }
+
+        if !resp.book_moves.is_empty() {
+            let book_moves = notation::localize(&book::describe(&resp.book_moves), locale);
+            embed = embed.field(self.label("label-book-moves"), book_moves, false);
+        }
+
+        if let Some(probe) = &resp.tablebase {
+            let tablebase = probe.describe(locale);
+            embed = embed.field(self.label("label-tablebase"), tablebase, false);
+        }
+
+        let line = notation::line_to_san(&resp.fen, resp.variant, &resp.continuation);
+        if self.view.lines && !line.is_empty() {
+            embed = embed.field(
+                self.label("label-line"),
+                notation::localize(&line, locale),
+                false,
+            );
+        }
+
+        if let Some(threat) = &resp.threat {
+            let opponent_to_move = resp.turn != resp.player;
+            let threat = describe_threat(&resp.fen, opponent_to_move, threat, locale);
+            embed = embed.field(self.label("label-threat"), threat, false);
+        }
+
+        if let Some(opening) = &resp.opening {
+            embed = embed.field(self.label("label-opening"), opening.label(), false);
+        }
+
+        embed
+    }
+
+    /// "Stockfish · Depth: 20", noting when that's as deep as the engine
+    /// goes since "Analyze deeper" is hidden then. Online that's every
+    /// analysis, so the footer says a local engine would go further.
+    fn footer(&self, depth: u8) -> CreateEmbedFooter {
+        let depth = if !engine::deeper_depths(depth).is_empty() {
+            depth.to_string()
+        } else if engine::is_online() {
+            format!("{} ({})", depth, self.label("label-online-max-depth"))
+        } else {
+            format!("{} ({})", depth, self.label("label-max-depth"))
+        };
+        CreateEmbedFooter::new(format!(
+            "{} · {}: {}",
+            engine::name(),
+            self.label("label-depth"),
+            depth
+        ))
+    }
+
+    /// The button bar, disabled while loading or while `busy` says a loop
+    /// still owns the message.
+    pub fn buttons(&self, busy: bool) -> Vec<CreateActionRow> {
+        let disabled = busy || matches!(self.state, AnalysisState::Loading);
+        action_buttons(self.view, Some(&self.target), disabled, self.locale)
+    }
+
+    pub fn create_message(&self, busy: bool) -> CreateMessage {
+        CreateMessage::new()
+            .embed(self.embed())
+            .components(self.buttons(busy))
+    }
+
+    /// Replaces the message's embed, dropping any board a previous state left
+    /// behind.
+    pub fn edit_message(&self, busy: bool) -> EditMessage {
+        EditMessage::new()
+            .embed(self.embed())
+            .remove_all_attachments()
+            .components(self.buttons(busy))
+    }
+}
+
+/// The sidebar colour for a win chance.
+fn colour(chance_to_win: f32) -> Colour {
+    if chance_to_win > 0.0 {
+        WINNING_COLOUR
+    } else if chance_to_win < LOSING_BADLY {
+        LOSING_BADLY_COLOUR
+    } else if chance_to_win < 0.0 {
+        LOSING_COLOUR
+    } else {
+        EQUAL_COLOUR
+    }
+}
+
+fn describe_evaluation(chance_to_win: f32, locale: Locale) -> String {
+    let chance = format!("{:.2}%", chance_to_win);
+    if chance_to_win > 0.0 {
+        tr_args(locale, "evaluation-winning", &[("chance", chance.into())])
+    } else if chance_to_win < LOSING_BADLY {
+        tr_args(
+            locale,
+            "evaluation-losing-badly",
+            &[("chance", chance.into())],
+        )
+    } else if chance_to_win < 0.0 {
+        tr_args(locale, "evaluation-losing", &[("chance", chance.into())])
+    } else {
+        tr(locale, "evaluation-equal")
+    }
+}
+
+/// "Nxf7 (-2.30)", the threat in SAN with how much it would cost the player.
+/// When it's our move the threat was searched from a null move, so it's
+/// written from the position with the move passed. Threats are only
+/// searched in standard chess.
+fn describe_threat(fen: &str, opponent_to_move: bool, threat: &Threat, locale: Locale) -> String {
+    let san = if opponent_to_move {
+        notation::best_move_to_san(fen, Variant::Standard, &threat.bestmove)
+    } else {
+        position::null_move_fen(fen)
+            .map(|null_fen| {
+                notation::best_move_to_san(&null_fen, Variant::Standard, &threat.bestmove)
+            })
+            .unwrap_or_else(|| threat.bestmove.clone())
+    };
+    let san = notation::localize(&san, locale);
+
+    match (threat.mate, threat.swing) {
+        (Some(mate), _) => tr_args(
+            locale,
+            "threat-mate",
+            &[("move", san.into()), ("moves", mate.abs().into())],
+        ),
+        (None, Some(swing)) => format!("{} ({:+.2})", san, swing),
+        (None, None) => san,
+    }
+}
@@ -0,0 +1,128 @@
+use std::env;
+use std::time::Duration;
+
+use serenity::all::{ChannelId, Context};
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::guard;
+use crate::interactions::{post_analysis, start_tracking, ViewState};
+use crate::locale;
+use crate::logging;
+use crate::models::subscription::Subscription;
+use crate::shutdown;
+use crate::source::{new_games, GameSource, Player, Target};
+
+/// How often we ask the game source who is playing, unless
+/// `WATCH_INTERVAL_SECS` says otherwise.
+const DEFAULT_WATCH_INTERVAL_SECS: u64 = 60;
+
+/// Polls `source` until shutdown and posts to every subscribed channel when its
+/// player starts a new game.
+pub async fn run(ctx: Context, source: impl GameSource) {
+    let interval = env::var("WATCH_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_WATCH_INTERVAL_SECS);
+    let interval = Duration::from_secs(interval);
+
+    loop {
+        poll(&ctx, &source).await;
+        if !shutdown::sleep(interval).await {
+            return;
+        }
+    }
+}
+
+/// One pass over all subscriptions.
+pub async fn poll(ctx: &Context, source: &impl GameSource) {
+    let subscriptions = match Subscription::find_all() {
+        Ok(subscriptions) => subscriptions,
+        Err(why) => {
+            error!("Error loading subscriptions: {why:?}");
+            return;
+        }
+    };
+
+    if subscriptions.is_empty() {
+        return;
+    }
+
+    let watched: Vec<(&str, Option<&str>)> = subscriptions
+        .iter()
+        .map(|s| (s.lc_user.as_str(), s.last_game_id.as_deref()))
+        .collect();
+    let games = match new_games(source, &watched).await {
+        Ok(games) => games,
+        Err(why) => {
+            warn!("Error fetching current games: {}", why);
+            return;
+        }
+    };
+
+    for (subscription, game_id) in subscriptions.iter().zip(games) {
+        let game_id = match game_id {
+            Some(game_id) => game_id,
+            None => continue,
+        };
+
+        // remember the game first so a failed post isn't retried every poll
+        if let Err(why) = subscription.set_last_game_id(game_id.clone()) {
+            error!("Error updating subscription: {why:?}");
+            continue;
+        }
+
+        let channel_id = match subscription.channel_id.parse::<u64>() {
+            Ok(channel_id) => ChannelId::new(channel_id),
+            Err(_) => continue,
+        };
+
+        let span = info_span!(
+            "watch",
+            request_id = %logging::request_id(),
+            channel = %channel_id,
+            target = %subscription.lc_user,
+            game = %game_id,
+        );
+
+        async {
+            info!("New game, posting analysis");
+            let guild_id = locale::guild_of(ctx, channel_id).await;
+            let locale = locale::resolve(None, guild_id, None);
+            let target = Target::Player(Player::lichess(&subscription.lc_user));
+
+            // a member's own game gets no more help from a subscription than
+            // from asking for it
+            let user = &subscription.lc_user;
+            let target =
+                match guard::allowed_for_player(ctx, channel_id, guild_id, user, target, locale)
+                    .await
+                {
+                    Ok(target) => target,
+                    Err(why) => {
+                        info!("Not posting, {}", why);
+                        return;
+                    }
+                };
+            // a delayed position stays put, so there's nothing to track
+            let live = subscription.live && matches!(target, Target::Player(_));
+
+            match post_analysis(ctx, channel_id, &target, None, locale).await {
+                Ok(message) if live => {
+                    start_tracking(
+                        ctx.clone(),
+                        message,
+                        target,
+                        ViewState::default(),
+                        false,
+                        locale,
+                    )
+                    .await;
+                }
+                Ok(_) => {}
+                Err(why) => error!("Error posting analysis: {}", why),
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+}
@@ -0,0 +1,100 @@
+//! A position through the engine and out as an image, with a stand-in UCI
+//! engine so nothing leaves the machine.
+#![cfg(unix)]
+
+use std::env;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Once;
+
+use lichess_stockfish::board::{help_fen, help_pgn, GetStockFishResponse};
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Answers every search with a fixed line for whichever side is to move,
+/// scored a little better for that side.
+const ENGINE: &str = "#!/bin/sh
+side=w
+while read cmd; do
+  case \"$cmd\" in
+    uci) echo 'id name stub'; echo 'uciok' ;;
+    isready) echo 'readyok' ;;
+    'position fen '*' b '*) side=b ;;
+    'position fen '*) side=w ;;
+    go*)
+      if [ \"$side\" = b ]; then
+        echo 'info depth 12 score cp 35 pv b8c6 f1b5 a7a6'
+        echo 'bestmove b8c6 ponder f1b5'
+      else
+        echo 'info depth 12 score cp 35 pv e2e4 e7e5 g1f3'
+        echo 'bestmove e2e4 ponder e7e5'
+      fi ;;
+    quit) exit 0 ;;
+  esac
+done
+";
+
+static STUB: Once = Once::new();
+
+fn use_stub_engine() {
+    STUB.call_once(|| {
+        let path = env::temp_dir().join(format!("stub-engine-{}", std::process::id()));
+        std::fs::write(&path, ENGINE).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        env::set_var("STOCKFISH_PATH", &path);
+    });
+}
+
+fn assert_board_image(resp: &GetStockFishResponse) {
+    let board = image::load_from_memory_with_format(&resp.image, image::ImageFormat::Png)
+        .expect("the board should be a PNG");
+    assert_eq!((board.width(), board.height()), (1024, 1024));
+}
+
+#[tokio::test]
+async fn fen_is_analysed_and_drawn() {
+    use_stub_engine();
+
+    let resp = help_fen(START, vec![], 12, false, "start".to_string())
+        .await
+        .unwrap();
+    assert_eq!(resp.bestmove, "e2e4");
+    assert_eq!(resp.continuation, "e2e4 e7e5 g1f3");
+    assert_eq!((resp.turn.as_str(), resp.player.as_str()), ("w", "w"));
+    assert!(resp.evaluation > 0.0, "{}", resp.evaluation);
+    assert_eq!(resp.file, "start.png");
+    assert!(resp.game_over.is_none());
+    assert_board_image(&resp);
+}
+
+#[tokio::test]
+async fn pgn_is_analysed_for_black() {
+    use_stub_engine();
+
+    let resp = help_pgn("1. e4 e5 2. Nf3", 12, false, "pgn".to_string())
+        .await
+        .unwrap();
+    assert_eq!(resp.bestmove, "b8c6");
+    assert!(resp.is_black);
+    // the engine scores for the side to move, and black is the one asking
+    assert!(resp.evaluation > 0.0, "{}", resp.evaluation);
+    assert_eq!(resp.last_moves.len(), 2);
+    assert_eq!(
+        resp.opening.as_ref().map(|opening| opening.eco.as_str()),
+        Some("C40")
+    );
+    assert_board_image(&resp);
+}
+
+#[tokio::test]
+async fn finished_games_are_drawn_without_a_move() {
+    use_stub_engine();
+
+    let resp = help_pgn("1. f3 e5 2. g4 Qh4#", 12, false, "mate".to_string())
+        .await
+        .unwrap();
+    assert_eq!(resp.bestmove, "(none)");
+    assert!(resp.continuation.is_empty());
+    assert!(resp.game_over.is_some());
+    assert_eq!(resp.evaluation, -100.0);
+    assert_board_image(&resp);
+}